@@ -0,0 +1,174 @@
+//! ```/healthz``` and ```/metrics```, just enough http for fly's checks and a Prometheus scraper
+
+use mostlybot_api::{ConnectionHealth, ConnectionState, Metrics};
+use serde_json::json;
+use std::{io, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, error, info};
+
+/// What the endpoints report on
+#[derive(Debug, Clone)]
+pub struct HttpState {
+    pub metrics: Metrics,
+    /// The chat consumer, ```None``` when chat comes from a file or stdin
+    pub chat: Option<ConnectionHealth>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{body}\n"),
+        }
+    }
+}
+
+fn respond(method: &str, path: &str, state: &HttpState) -> Response {
+    if method != "GET" {
+        return Response::text("405 Method Not Allowed", "method not allowed");
+    }
+
+    match path {
+        "/healthz" => healthz(state),
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: state.metrics.render(),
+        },
+        _ => Response::text("404 Not Found", "not found"),
+    }
+}
+
+/// Healthy as long as the chat consumer is connected, a quiet chat is still a healthy bot
+fn healthz(state: &HttpState) -> Response {
+    let consumer = state.chat.as_ref().map(ConnectionHealth::state);
+    let healthy = consumer.is_none_or(|state| state == ConnectionState::Connected);
+
+    let body = json!({
+        "status": if healthy { "ok" } else { "unavailable" },
+        "consumer": consumer.map(|state| state.to_string()),
+        "last_message_secs": state.metrics.last_message_age().map(|age| age.as_secs_f64()),
+    });
+
+    Response {
+        status: if healthy {
+            "200 OK"
+        } else {
+            "503 Service Unavailable"
+        },
+        content_type: "application/json",
+        body: format!("{body}\n"),
+    }
+}
+
+/// Serves until the bot stops, a port that can't be bound is logged and otherwise ignored
+pub async fn serve(addr: String, state: HttpState) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(%addr, "unable to serve /healthz and /metrics: {}", e);
+            return;
+        }
+    };
+    info!(%addr, "serving /healthz and /metrics");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                debug!(error = %e, "unable to accept http connection");
+                continue;
+            }
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &state).await {
+                debug!(error = %e, "http connection failed");
+            }
+        });
+    }
+}
+
+/// One request per connection, only the request line is looked at
+async fn handle(mut stream: TcpStream, state: &HttpState) -> io::Result<()> {
+    let mut buf = [0; 4096];
+    let mut len = 0;
+
+    let read_head = async {
+        while len < buf.len() && !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf[len..]).await? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        Ok::<_, io::Error>(())
+    };
+    tokio::time::timeout(Duration::from_secs(5), read_head)
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    let head = String::from_utf8_lossy(&buf[..len]);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    let response = respond(method, path, state);
+    let head = format!(
+        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn routes() {
+        let state = HttpState {
+            metrics: Metrics::default(),
+            chat: None,
+        };
+
+        // no consumer to be disconnected from
+        let health = respond("GET", "/healthz", &state);
+        assert_eq!(health.status, "200 OK");
+        assert!(health.body.contains("\"consumer\":null"));
+
+        let metrics = respond("GET", "/metrics", &state);
+        assert!(metrics.body.contains("mostlybot_messages_total 0"));
+
+        assert_eq!(respond("GET", "/", &state).status, "404 Not Found");
+        assert_eq!(
+            respond("POST", "/metrics", &state).status,
+            "405 Method Not Allowed"
+        );
+
+        let connecting = HttpState {
+            chat: Some(ConnectionHealth::default()),
+            ..state
+        };
+        let health = respond("GET", "/healthz", &connecting);
+        assert_eq!(health.status, "503 Service Unavailable");
+        assert!(health.body.contains("\"consumer\":\"connecting\""));
+    }
+}
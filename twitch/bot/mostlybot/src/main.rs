@@ -1,10 +1,29 @@
 #![doc = include_str!("../../README.md")]
 
-use mostlybot_api::{handle_command_if_applicable, Spam, TwitchApiWrapper};
-use tokio::signal;
+mod http;
+
+use franz_client::{Consumer, Producer};
+use http::HttpState;
+use mostlybot_api::{
+    handle_command_if_applicable, ignore_path, load_and_apply, load_toggles, position_path,
+    toggles_path, AuditLog, Backoff, BotRng, ChatCursor, ChatEvent, CommandMap, Config,
+    ConnectionHealth, DryRun, EventBus, EventSource, FranzEvents, IgnoreList, LineSource, Metrics,
+    Moderation, Outbox, OutboxRequest, OutboxResult, ReloadHandle, ReloadTrigger, RuntimeStats,
+    Sanitizer, Services, Spam, SupervisedConsumer, TwitchApiWrapper, Usage,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    signal,
+    sync::mpsc::{self, Receiver},
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, instrument};
-use twitcheventsub::{MessageData, Subscription, TwitchEventSubApi, TwitchKeys};
+use tracing::{debug, error, info, instrument, warn};
+use twitcheventsub::{Subscription, TwitchEventSubApi, TwitchKeys};
 
 // ----------------------------------------------------------------------------
 
@@ -30,16 +49,420 @@ fn init_twitch_api() -> TwitchEventSubApi {
     twitch.build().expect("twitch api build")
 }
 
-#[instrument]
-async fn init_franz_consumer(topic: &str) -> franz_client::Consumer {
-    let broker = std::env::var("FRANZ_BROKER").expect("FRANZ_BROKER environment variable set");
-    franz_client::Consumer::new(&broker, topic, Some(0)).unwrap()
+/// Twitch, or with ```[dry_run]``` enabled a platform that only logs what it would have done
+fn init_platform(config: &Config) -> TwitchApiWrapper {
+    if !config.dry_run.enabled {
+        return TwitchApiWrapper::new(init_twitch_api());
+    }
+
+    warn!("dry run, nothing will be sent to twitch");
+    let topic = &config.dry_run.topic;
+    if topic.is_empty() {
+        return TwitchApiWrapper::new(DryRun::new(None));
+    }
+
+    match Producer::new(&config.franz.broker, topic) {
+        Ok(producer) => {
+            info!(%topic, "publishing dry run output");
+            TwitchApiWrapper::new(DryRun::new(Some(producer)))
+        }
+        Err(e) => {
+            error!(%topic, "unable to publish dry run output, only logging it: {}", e);
+            TwitchApiWrapper::new(DryRun::new(None))
+        }
+    }
+}
+
+/// Consumer that reconnects (right after the last message it got) when the broker goes away
+#[instrument(skip(config, health))]
+fn init_franz_consumer(
+    config: &Config,
+    topic: &str,
+    offset: u64,
+    health: ConnectionHealth,
+) -> SupervisedConsumer<Consumer, impl FnMut(u64) -> io::Result<Consumer>> {
+    let broker = config.franz.broker.clone();
+    let topic = topic.to_owned();
+    let connect = move |offset| Consumer::new(&broker, &topic, Some(offset));
+
+    SupervisedConsumer::new(
+        connect,
+        offset,
+        Backoff::new(config.franz.reconnect, BotRng::from_entropy()),
+        health,
+    )
+}
+
+/// Where chat comes from, franz unless ```--chat``` says otherwise
+#[derive(Debug)]
+enum ChatInput {
+    Franz,
+    /// recorded chat, one message per line
+    File(PathBuf),
+    Stdin,
+}
+
+impl ChatInput {
+    /// Only live chat has a position worth remembering and messages that can be too old
+    fn is_live(&self) -> bool {
+        matches!(self, ChatInput::Franz)
+    }
+
+    fn open(
+        self,
+        config: &Config,
+        offset: u64,
+        health: ConnectionHealth,
+    ) -> io::Result<Box<dyn EventSource>> {
+        Ok(match self {
+            ChatInput::Franz => Box::new(init_franz_consumer(
+                config,
+                &config.franz.topics.chat,
+                offset,
+                health,
+            )),
+            ChatInput::File(path) => Box::new(LineSource::open(&path)?),
+            ChatInput::Stdin => Box::new(LineSource::stdin()),
+        })
+    }
+}
+
+/// Reads a source on its own thread, sources block and the main loop has to keep listening
+/// for a shutdown. Messages still in the channel when the bot stops weren't handled and get
+/// read again after the restart
+fn spawn_reader(
+    what: &'static str,
+    open: impl FnOnce() -> io::Result<Box<dyn EventSource>> + Send + 'static,
+) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel(64);
+
+    std::thread::spawn(move || {
+        let mut source = match open() {
+            Ok(source) => source,
+            Err(e) => {
+                error!("unable to open {}: {}", what, e);
+                return;
+            }
+        };
+
+        loop {
+            match source.recv() {
+                Ok(Some(raw)) => {
+                    if tx.blocking_send(raw).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {
+                    info!("no more {}", what);
+                    return;
+                }
+                Err(e) => {
+                    error!("unable to read {}: {}", what, e);
+                    return;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+/// Loads where the last run stopped reading a topic, exiting if the saved position is
+/// unreadable rather than silently starting over from the beginning of the topic
+fn init_cursor(data_dir: &Path, name: &str) -> ChatCursor {
+    let path = position_path(data_dir, name);
+    match ChatCursor::load(path.clone()) {
+        Ok(cursor) => {
+            info!(offset = cursor.offset(), "resuming {}", name);
+            cursor
+        }
+        Err(e) => {
+            error!(
+                "{:#}, delete {} to start from the beginning of the topic",
+                e,
+                path.display()
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Requests other services put on the outbox topic, and where their results go
+struct OutboxTopic {
+    requests: Receiver<Vec<u8>>,
+    cursor: ChatCursor,
+    results: Option<Producer>,
+}
+
+/// Starts reading the outbox, unless ```franz.topics.outbox``` is empty
+fn init_outbox_topic(config: &Config) -> Option<OutboxTopic> {
+    let topic = config.franz.topics.outbox.clone();
+    if topic.is_empty() {
+        return None;
+    }
+
+    let cursor = init_cursor(&config.data_dir, "outbox");
+    let offset = cursor.offset();
+    let reader_config = config.clone();
+    let requests = spawn_reader("outbox", move || {
+        let consumer =
+            init_franz_consumer(&reader_config, &topic, offset, ConnectionHealth::default());
+        Ok(Box::new(consumer))
+    });
+
+    let results_topic = &config.franz.topics.outbox_results;
+    let results = if results_topic.is_empty() {
+        None
+    } else {
+        match Producer::new(&config.franz.broker, results_topic) {
+            Ok(producer) => Some(producer),
+            Err(e) => {
+                error!(topic = %results_topic, "unable to publish outbox results: {}", e);
+                None
+            }
+        }
+    };
+
+    Some(OutboxTopic {
+        requests,
+        cursor,
+        results,
+    })
+}
+
+/// Waits for the next outbox request, forever if there's no outbox
+async fn next_request(outbox: &mut Option<OutboxTopic>) -> Option<Vec<u8>> {
+    match outbox {
+        Some(outbox) => outbox.requests.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Carries out one outbox request and publishes how it went
+fn handle_outbox_request(
+    raw: Vec<u8>,
+    topic: &mut OutboxTopic,
+    outbox: &mut Outbox,
+    api: &mut TwitchApiWrapper,
+) {
+    let result = match OutboxRequest::parse(&raw) {
+        Ok(request) => {
+            // ids are only unique per service
+            let key = format!("{}/{}", request.service, request.id);
+            let replayed = topic.cursor.seen(&key);
+            if let Err(e) = topic.cursor.advance(Some(&key)) {
+                error!("unable to save outbox position: {:#}", e);
+            }
+            if replayed {
+                debug!(%key, "already handled before the restart");
+                return;
+            }
+
+            outbox.handle(&request, api)
+        }
+        Err(e) => {
+            if let Err(e) = topic.cursor.advance(None) {
+                error!("unable to save outbox position: {:#}", e);
+            }
+            OutboxResult::malformed(&raw, &e)
+        }
+    };
+
+    if let Some(results) = &mut topic.results {
+        if let Err(e) = results.send(result.to_json()) {
+            warn!("unable to publish outbox result: {}", e);
+        }
+    }
+}
+
+/// Loads the config and applies it to the commands and spam limits, exiting with
+/// every problem listed if it isn't usable
+fn init_config(path: &Path, commands: &mut CommandMap, spam: &mut Spam) -> Config {
+    let mut config = Config::default();
+    if let Err(problems) = load_and_apply(path, &mut config, commands, spam) {
+        for problem in problems {
+            error!("{}: {}", path.display(), problem);
+        }
+        std::process::exit(1);
+    }
+
+    config
+}
+
+fn enabled_commands(commands: &CommandMap) -> usize {
+    commands.commands().filter(|cmd| cmd.is_enabled()).count()
+}
+
+/// Reloads the config between messages, a bad config is reported and the old one kept
+fn reload_config(
+    trigger: ReloadTrigger,
+    path: &Path,
+    api: &mut TwitchApiWrapper,
+    config: &mut Config,
+    commands: &mut CommandMap,
+    spam: &mut Spam,
+    outbox: &mut Outbox,
+) {
+    let restart_only = |config: &Config| {
+        (
+            config.franz.broker.clone(),
+            config.franz.topics.clone(),
+            config.dry_run.clone(),
+            config.http.clone(),
+            config.audit.clone(),
+            config.moderation.log.clone(),
+        )
+    };
+    let before = restart_only(config);
+
+    let reply = match load_and_apply(path, config, commands, spam) {
+        Ok(()) => {
+            info!(?trigger, "config reloaded");
+            outbox.configure(&config.outbox);
+            api.set_sanitizer(Sanitizer::new(config));
+            if before != restart_only(config) {
+                warn!("franz, http, dry_run, audit or moderation.log settings changed, they only apply after a restart");
+            }
+            "config reloaded".to_owned()
+        }
+        Err(problems) => {
+            for problem in &problems {
+                error!(
+                    ?trigger,
+                    "config not reloaded, {}: {}",
+                    path.display(),
+                    problem
+                );
+            }
+            format!("config not reloaded: {}", problems.join("; "))
+        }
+    };
+
+    if let ReloadTrigger::Chat { message_id } = trigger {
+        let _ = api.send_chat_message_with_reply(reply, Some(message_id));
+    }
+}
+
+#[cfg(unix)]
+async fn reload_on_signal(reload: ReloadHandle) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            error!("unable to listen for SIGHUP: {}", err);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        reload.request(ReloadTrigger::Signal);
+    }
+}
+
+/// Polls the config file and asks for a reload whenever it's modified
+async fn reload_on_change(path: PathBuf, reload: ReloadHandle) {
+    let modified = |path: &Path| -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+
+    let mut last_modified = modified(&path);
+    let mut interval = tokio::time::interval(Duration::from_secs(2));
+
+    loop {
+        interval.tick().await;
+
+        let current = modified(&path);
+        if current != last_modified {
+            last_modified = current;
+            reload.request(ReloadTrigger::FileChanged);
+        }
+    }
+}
+
+/// ```mostlybot check-config [path]```, validates the config without starting the bot
+fn check_config(path: Option<String>) -> ExitCode {
+    let path = path.map(PathBuf::from).unwrap_or_else(Config::path);
+    if !path.exists() {
+        println!(
+            "{} doesn't exist, checking defaults and environment",
+            path.display()
+        );
+    }
+
+    let config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}: {:#}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let problems = config.validate(&mostlybot_commands::init());
+    if problems.is_empty() {
+        println!("{} is valid", path.display());
+        return ExitCode::SUCCESS;
+    }
+
+    for problem in problems {
+        println!("{}: {}", path.display(), problem);
+    }
+    ExitCode::FAILURE
+}
+
+/// Prints how often each command was used, from the counts in the data dir
+fn usage_report(format: Option<&str>) -> ExitCode {
+    let path = Config::path();
+    let usage = match Config::load(&path).and_then(|config| Usage::load(&config.data_dir)) {
+        Ok(usage) => usage,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match format {
+        None | Some("csv") => print!("{}", usage.report().to_csv()),
+        Some("json") => println!("{}", usage.report().to_json()),
+        Some(other) => {
+            eprintln!("unknown format {other:?}, csv or json");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Seeds the bot's rng from `MOSTLYBOT_RNG_SEED` if set, useful for replaying a simulation
+fn init_rng() -> BotRng {
+    match std::env::var("MOSTLYBOT_RNG_SEED") {
+        Ok(seed) => BotRng::seed_from_u64(seed.parse().expect("MOSTLYBOT_RNG_SEED is a u64")),
+        Err(_) => BotRng::from_entropy(),
+    }
+}
+
+/// Resolves on ```SIGTERM```, which is what fly and docker send on stop
+async fn terminated() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+                return;
+            }
+            Err(err) => error!("unable to listen for SIGTERM: {}", err),
+        }
+    }
+
+    std::future::pending().await
 }
 
 async fn cancel_on_signal(token: CancellationToken) {
-    match signal::ctrl_c().await {
-        Ok(()) => info!("caught signal. shutting down..."),
-        Err(err) => error!("unable to listen for shutdown signal: {}", err),
+    tokio::select! {
+        res = signal::ctrl_c() => match res {
+            Ok(()) => info!("caught signal. shutting down..."),
+            Err(err) => error!("unable to listen for shutdown signal: {}", err),
+        },
+        _ = terminated() => info!("caught SIGTERM. shutting down..."),
     }
 
     token.cancel();
@@ -48,27 +471,271 @@ async fn cancel_on_signal(token: CancellationToken) {
 // ----------------------------------------------------------------------------
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
 
-    let mut api = TwitchApiWrapper::Live(init_twitch_api());
-    let mut consumer = init_franz_consumer("chat").await;
+    const USAGE: &str =
+        "usage: mostlybot [--chat <file.jsonl|->] or mostlybot check-config [path] \
+        or mostlybot usage-report [csv|json]";
+
+    let mut args = std::env::args().skip(1);
+    let input = match args.next().as_deref() {
+        None => ChatInput::Franz,
+        Some("check-config") => return check_config(args.next()),
+        Some("usage-report") => return usage_report(args.next().as_deref()),
+        Some("--chat") => match args.next().as_deref() {
+            Some("-") => ChatInput::Stdin,
+            Some(path) => ChatInput::File(path.into()),
+            None => {
+                eprintln!("--chat needs a file or - for stdin, {USAGE}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(other) => {
+            eprintln!("unknown argument {other:?}, {USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let config_path = Config::path();
+    let mut commands = mostlybot_commands::init();
+    let mut spam = Spam::default();
+    let mut config = init_config(&config_path, &mut commands, &mut spam);
+
+    if let Err(e) = std::fs::create_dir_all(&config.data_dir) {
+        error!(
+            "unable to create data dir {}: {}",
+            config.data_dir.display(),
+            e
+        );
+    }
+
+    let mut api = init_platform(&config);
+    api.set_sanitizer(Sanitizer::new(&config));
+    let live = input.is_live();
+    let mut cursor = live.then(|| init_cursor(&config.data_dir, "chat"));
+    let offset = cursor.as_ref().map_or(0, ChatCursor::offset);
+    let reader_config = config.clone();
+    let chat_health = ConnectionHealth::default();
+    let reader_health = chat_health.clone();
+    let mut chat = spawn_reader("chat", move || {
+        input.open(&reader_config, offset, reader_health)
+    });
+
+    let metrics = Metrics::default();
+    if !config.http.addr.is_empty() {
+        tokio::spawn(http::serve(
+            config.http.addr.clone(),
+            HttpState {
+                metrics: metrics.clone(),
+                chat: live.then_some(chat_health),
+            },
+        ));
+    }
+
+    // other services only get to act through a live bot, a dry run would answer them for
+    // actions it never carried out
+    let mut outbox = Outbox::new(&config.outbox);
+    let mut outbox_topic = if live && !config.dry_run.enabled {
+        init_outbox_topic(&config)
+    } else {
+        None
+    };
 
     let cancel_token = CancellationToken::new();
     tokio::spawn(cancel_on_signal(cancel_token.clone()));
 
-    let mut commands = mostlybot_commands::init();
-    let bot_id = std::env::var("TWITCH_BOT_ID").expect("TWITCH_BOT_ID environment variable set");
+    // moderator !cmd toggles from before the restart
+    match load_toggles(&toggles_path(&config.data_dir)) {
+        Ok(toggles) => commands.apply_toggles(&toggles),
+        Err(e) => error!("unable to load command toggles: {:#}", e),
+    }
+    let ignore = IgnoreList::load(&ignore_path(&config.data_dir)).unwrap_or_else(|e| {
+        error!("unable to load the ignore list, ignoring nobody: {:#}", e);
+        IgnoreList::default()
+    });
 
-    let mut spam = Spam::default();
+    // a dry run only logs what commands publish, like everything else it does
+    let events = if config.dry_run.enabled {
+        EventBus::default()
+    } else {
+        EventBus::new(FranzEvents::new(&config.franz.broker))
+    };
+    info!(topics = ?commands.topics(), "commands publish to");
+
+    let audit = AuditLog::new(&config.audit, &config.data_dir, events.clone());
+    let usage = Usage::load(&config.data_dir).unwrap_or_else(|e| {
+        error!("unable to load command usage, counting from zero: {:#}", e);
+        Usage::default()
+    });
+    let moderation = Moderation::new(&config.moderation, &config.data_dir);
+    let services = Services {
+        data_dir: config.data_dir.clone(),
+        events,
+        audit,
+        usage,
+        ignore,
+        moderation,
+        metrics,
+        stats: RuntimeStats::new(env!("CARGO_PKG_VERSION")),
+        ..Services::new(init_rng())
+    };
+    commands.restore(&services);
+    services
+        .stats
+        .set_commands_loaded(enabled_commands(&commands));
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_signal(services.reload.clone()));
+    tokio::spawn(reload_on_change(
+        config_path.clone(),
+        services.reload.clone(),
+    ));
+
+    // handle chat commands and outbox requests
+    // a message is always handled to the end, the shutdown is only noticed in between
+    loop {
+        let raw = tokio::select! {
+            biased;
+            _ = cancel_token.cancelled() => break,
+            raw = chat.recv() => match raw {
+                Some(raw) => raw,
+                None => break,
+            },
+            // a quiet chat shouldn't leave a SIGHUP or a changed config waiting
+            _ = services.reload.requested() => {
+                if let Some(trigger) = services.reload.take() {
+                    reload_config(
+                        trigger,
+                        &config_path,
+                        &mut api,
+                        &mut config,
+                        &mut commands,
+                        &mut spam,
+                        &mut outbox,
+                    );
+                    services
+                        .stats
+                        .set_commands_loaded(enabled_commands(&commands));
+                }
+                continue;
+            },
+            request = next_request(&mut outbox_topic) => {
+                match (request, &mut outbox_topic) {
+                    (Some(raw), Some(topic)) => {
+                        services.metrics.set_inbound_queue_depth("outbox", topic.requests.len());
+                        if let Some(trigger) = services.reload.take() {
+                            reload_config(
+                                trigger,
+                                &config_path,
+                                &mut api,
+                                &mut config,
+                                &mut commands,
+                                &mut spam,
+                                &mut outbox,
+                            );
+                        }
+                        handle_outbox_request(raw, topic, &mut outbox, &mut api);
+                    }
+                    _ => {
+                        warn!("no more outbox requests");
+                        outbox_topic = None;
+                    }
+                }
+                continue;
+            },
+        };
+        let event = ChatEvent::parse(raw);
+        services.metrics.message_received();
+        services.metrics.set_inbound_queue_depth("chat", chat.len());
 
-    // handle chat commands
-    while let Ok(msg) = consumer.recv() {
-        let msg = String::from_utf8(msg).unwrap();
-        let Ok(chat_msg) = serde_json::from_str::<MessageData>(&msg) else {
+        let message_id = event.message.as_ref().map(|m| m.message_id.as_str());
+        let replayed = match &mut cursor {
+            Some(cursor) => {
+                let replayed = message_id.is_some_and(|id| cursor.seen(id));
+                // every message moves the cursor, even ones that aren't chat messages
+                if let Err(e) = cursor.advance(message_id) {
+                    error!("unable to save chat position: {:#}", e);
+                }
+                replayed
+            }
+            None => false,
+        };
+
+        let Some(chat_msg) = &event.message else {
             continue;
         };
+        if replayed {
+            debug!(message_id = %chat_msg.message_id, "already handled before the restart");
+            continue;
+        }
+
+        // reloads only happen here, between messages, so a command never sees half a config
+        if let Some(trigger) = services.reload.take() {
+            reload_config(
+                trigger,
+                &config_path,
+                &mut api,
+                &mut config,
+                &mut commands,
+                &mut spam,
+                &mut outbox,
+            );
+        }
+
+        // recorded chat is old on purpose, only live chat can be too late to answer
+        if live && event.is_stale(config.chat.max_age, SystemTime::now()) {
+            debug!(message_id = %chat_msg.message_id, "skipping old message");
+            continue;
+        }
+
+        handle_command_if_applicable(
+            chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
 
-        handle_command_if_applicable(&chat_msg, &mut api, &mut commands, &bot_id, &mut spam);
+        // answer !reload right away instead of on the next message
+        if let Some(trigger) = services.reload.take() {
+            reload_config(
+                trigger,
+                &config_path,
+                &mut api,
+                &mut config,
+                &mut commands,
+                &mut spam,
+                &mut outbox,
+            );
+        }
+
+        // !cmd and reloads can turn commands on and off
+        services
+            .stats
+            .set_commands_loaded(enabled_commands(&commands));
+    }
+
+    // the cursors only save every so often, this is where the last handled message is
+    if let Some(cursor) = &mut cursor {
+        if let Err(e) = cursor.save() {
+            error!("unable to save chat position: {:#}", e);
+        }
+    }
+    if let Some(topic) = &mut outbox_topic {
+        if let Err(e) = topic.cursor.save() {
+            error!("unable to save outbox position: {:#}", e);
+        }
     }
+
+    // replies are sent as commands make them, so with the last message handled all that's
+    // left is the commands' own state
+    info!("saving command state");
+    commands.shutdown(&services);
+    if let Err(e) = services.usage.save() {
+        error!("unable to save command usage: {:#}", e);
+    }
+
+    ExitCode::SUCCESS
 }
@@ -1,9 +1,16 @@
+use anyhow::anyhow;
 use mostlybot_api::{
-    handle_command_if_applicable, ChatCommand, CommandMap, MockTwitchEventSubApi, Spam,
-    TwitchApiWrapper,
+    handle_command_if_applicable, AuditConfig, AuditLog, BotPolicy, BotRng, ChatCommand,
+    ChatSource, CommandConfig, CommandContext, CommandError, CommandMap, CommandResult, Config,
+    EventBus, EventSource, FilterAction, LineSource, MemoryEvents, Metrics, MockTwitchEventSubApi,
+    ModAction, Outbox, OutboxRequest, OutboxResult, RateLimit, ReloadHandle, ReloadTrigger,
+    Sanitizer, Services, Spam, TwitchApiWrapper, Usage, UsageReport,
+};
+use mostlybot_commands::{
+    ban, cmd, help, lurk, modlog, permit, ping, reload, rewrite, train, undo,
 };
-use mostlybot_commands::ping;
 use serde_json::json;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use twitcheventsub::MessageData;
 
 /// Simulates a twitch chat message
@@ -49,15 +56,24 @@ fn create_chat_msg(cmd: &str, chatter_id: &str) -> serde_json::Value {
     })
 }
 
+/// Config with just enough set for the dispatcher
+fn test_config() -> Config {
+    let mut config = Config::default();
+    config.twitch.bot_id = "id_bot".to_owned();
+    config
+}
+
 /// Test handling of various command scenarios, including spam detection and invalid commands
 #[test]
 fn test_chat_command_handling() {
     let mut commands = CommandMap::new();
     commands.insert(ping::MostlyPing::new());
 
-    let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
 
     let mut spam = Spam::default();
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let config = test_config();
 
     const BOT_ID: &str = "id_bot";
 
@@ -88,12 +104,1024 @@ fn test_chat_command_handling() {
         create_chat_msg("!Ping", "id_spam"),
     ];
 
+    #[allow(clippy::unused_enumerate_index)]
     for (_i, chat_msg) in chat_messages.into_iter().enumerate() {
         // // Simulating time delay between commands
         // if i > 0 {
         //     std::thread::sleep(Duration::from_secs(1));
         // }
         let chat_msg: MessageData = serde_json::from_value(chat_msg).unwrap();
-        handle_command_if_applicable(&chat_msg, &mut api, &mut commands, BOT_ID, &mut spam);
+        handle_command_if_applicable(
+            &chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
+    }
+}
+
+/// Command that fails in whatever way its argument asks for
+struct MostlyFail {}
+
+impl ChatCommand for MostlyFail {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn names() -> Vec<String> {
+        vec!["fail".to_string()]
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit::new(0, Duration::ZERO)
+    }
+
+    fn handle(&mut self, _api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        match ctx.message.text.split_whitespace().nth(1) {
+            Some("usage") => Err(CommandError::Usage),
+            Some("user") => Err(CommandError::user_facing("nope")),
+            Some("permission") => Err(CommandError::PermissionDenied),
+            Some("transient") => Err(CommandError::Transient(anyhow!("twitch is down"))),
+            _ => Err(CommandError::Internal(anyhow!(
+                "EventSubError(secret stuff)"
+            ))),
+        }
+    }
+
+    fn help(&self) -> String {
+        "usage: !fail <kind>".to_string()
+    }
+}
+
+/// Test that command errors are turned into chat replies without leaking internals
+#[test]
+fn test_command_error_replies() {
+    let mut commands = CommandMap::new();
+    commands.insert(MostlyFail::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::default();
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let config = test_config();
+
+    let cases = [
+        ("usage", "usage: !fail <kind>"),
+        ("user", "nope"),
+        (
+            "permission",
+            "you don't have permission to do that with \"fail\"",
+        ),
+        (
+            "transient",
+            "\"fail\" is having trouble right now, try again later",
+        ),
+        ("internal", "\"fail\" ran into an internal error (id: "),
+    ];
+
+    for (kind, expected) in cases {
+        let chat_msg = create_chat_msg(&format!("!fail {kind}"), &format!("id_{kind}"));
+        let chat_msg: MessageData = serde_json::from_value(chat_msg).unwrap();
+        handle_command_if_applicable(
+            &chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
+
+        let reply = api.as_mock().unwrap().sent_messages().last().unwrap();
+        assert!(reply.contains(expected), "{kind}: {reply}");
+        assert!(!reply.contains("secret"), "{kind}: {reply}");
+    }
+}
+
+/// Test that the config can disable commands, override cooldowns and change prefixes
+#[test]
+fn test_command_config() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+    commands.insert(MostlyFail::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+
+    let mut config = test_config();
+    config.chat.prefixes = vec!['?'];
+    config.commands.insert(
+        "ping".to_owned(),
+        CommandConfig {
+            enabled: false,
+            cooldown: None,
+        },
+    );
+    config.commands.insert(
+        "fail".to_owned(),
+        CommandConfig {
+            enabled: true,
+            cooldown: Some(Duration::from_secs(60)),
+        },
+    );
+    commands.configure(&config.commands);
+
+    let mut send = |text: &str| {
+        let chat_msg: MessageData =
+            serde_json::from_value(create_chat_msg(text, "id_config")).unwrap();
+        handle_command_if_applicable(
+            &chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
+        api.as_mock().unwrap().sent_messages().last().cloned()
+    };
+
+    assert!(send("?ping")
+        .unwrap()
+        .contains("\"ping\" is disabled right now"));
+    assert!(send("?fail user").unwrap().contains("nope"));
+    assert!(send("?fail user")
+        .unwrap()
+        .contains("\"fail\" is on cooldown"));
+
+    // the old prefix is just chat now
+    assert!(send("!fail user")
+        .unwrap()
+        .contains("\"!fail user\" is not a command"));
+}
+
+/// Test that only the broadcaster can ask for a reload from chat
+#[test]
+fn test_reload_command() {
+    let mut commands = CommandMap::new();
+    commands.insert(reload::MostlyReload::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::default();
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let config = test_config();
+
+    let mut chatter = create_chat_msg("!reload", "id_chatter");
+    chatter["badges"] = json!([]);
+    let chatter: MessageData = serde_json::from_value(chatter).unwrap();
+    handle_command_if_applicable(
+        &chatter,
+        &mut api,
+        &mut commands,
+        &config,
+        &mut spam,
+        &services,
+    );
+
+    let reply = api.as_mock().unwrap().sent_messages().last().unwrap();
+    assert!(
+        reply.contains("\"reload\" can only be used by broadcasters"),
+        "{reply}"
+    );
+    assert_eq!(services.reload.take(), None);
+
+    let broadcaster: MessageData =
+        serde_json::from_value(create_chat_msg("!reload", "938429017")).unwrap();
+    handle_command_if_applicable(
+        &broadcaster,
+        &mut api,
+        &mut commands,
+        &config,
+        &mut spam,
+        &services,
+    );
+
+    assert_eq!(
+        services.reload.take(),
+        Some(ReloadTrigger::Chat {
+            message_id: broadcaster.message_id.clone()
+        })
+    );
+}
+
+/// Test that a reload request wakes the main loop up even when chat is quiet
+#[tokio::test]
+async fn test_reload_wakes_up() {
+    let reload = ReloadHandle::default();
+    let waiting = tokio::spawn({
+        let reload = reload.clone();
+        async move {
+            reload.requested().await;
+            reload.take()
+        }
+    });
+
+    reload.request(ReloadTrigger::Signal);
+    let trigger = tokio::time::timeout(Duration::from_secs(1), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(trigger, Some(ReloadTrigger::Signal));
+}
+
+/// Test that moderators can toggle commands from chat and everyone else can't
+#[test]
+fn test_cmd_toggles() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+    commands.insert(cmd::MostlyCmd::new());
+    commands.insert(help::MostlyHelp::new());
+    // like a reload adding a response after the commands were set up
+    commands.set_responses(&HashMap::from([(
+        "discord".to_owned(),
+        "join the discord!".to_owned(),
+    )]));
+
+    let data_dir = std::env::temp_dir().join(format!("mostlybot-toggles-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services {
+        data_dir: data_dir.clone(),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let mut config = test_config();
+    config.commands.insert(
+        "cmd".to_owned(),
+        CommandConfig {
+            enabled: true,
+            cooldown: Some(Duration::ZERO),
+        },
+    );
+    commands.configure(&config.commands);
+
+    let mut send = |text: &str, badges: serde_json::Value| {
+        let mut chat_msg = create_chat_msg(text, "id_toggler");
+        chat_msg["badges"] = badges;
+        let chat_msg: MessageData = serde_json::from_value(chat_msg).unwrap();
+        handle_command_if_applicable(
+            &chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
+        api.as_mock()
+            .unwrap()
+            .sent_messages()
+            .last()
+            .cloned()
+            .unwrap()
+    };
+    let moderator = || json!([{"set_id": "moderator", "id": "1", "info": ""}]);
+
+    let reply = send("!cmd disable ping", json!([]));
+    assert!(
+        reply.contains("\"cmd\" can only be used by moderators"),
+        "{reply}"
+    );
+
+    assert_eq!(send("!cmd disable ping", moderator()), "!ping disabled");
+    assert!(send("!ping", json!([])).contains("\"ping\" is disabled right now"));
+
+    assert_eq!(send("!cmd enable ping", moderator()), "!ping enabled");
+    assert_eq!(send("!ping", json!([])), "pong");
+
+    // the toggled cooldown wins over the ping's own 250ms, and the last pong was just now
+    assert_eq!(
+        send("!cmd cooldown ping 1m", moderator()),
+        "!ping cooldown set to 1m"
+    );
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(send("!ping", json!([])).contains("\"ping\" is on cooldown"));
+
+    assert_eq!(
+        send("!cmd disable discord", moderator()),
+        "!discord disabled"
+    );
+    assert_eq!(
+        send("!cmd list-disabled", moderator()),
+        "disabled: !discord"
+    );
+
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Test that command state saved on shutdown is there again after a restart
+#[test]
+fn test_command_state() {
+    let data_dir = std::env::temp_dir().join(format!("mostlybot-state-{}", std::process::id()));
+    let services = Services {
+        data_dir: data_dir.clone(),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let config = test_config();
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+
+    let mut send = |commands: &mut CommandMap, text: &str| {
+        let chat_msg: MessageData =
+            serde_json::from_value(create_chat_msg(text, "id_lurker")).unwrap();
+        handle_command_if_applicable(
+            &chat_msg,
+            &mut api,
+            commands,
+            &config,
+            &mut Spam::new(
+                RateLimit::new(0, Duration::ZERO),
+                RateLimit::new(0, Duration::ZERO),
+                RateLimit::new(0, Duration::ZERO),
+            ),
+            &services,
+        );
+        api.as_mock()
+            .unwrap()
+            .sent_messages()
+            .last()
+            .cloned()
+            .unwrap()
+    };
+
+    let mut before = CommandMap::new();
+    before.insert(lurk::Lurk::new());
+    before.restore(&services);
+    assert_eq!(send(&mut before, "!lurkwith coding"), "have a nice lurk!");
+    before.shutdown(&services);
+
+    let mut after = CommandMap::new();
+    after.insert(lurk::Lurk::new());
+    after.restore(&services);
+    assert_eq!(send(&mut after, "!lurker"), "@mostlymaxi is coding");
+    // extra words are just chatter
+    assert_eq!(
+        send(&mut after, "!lurker @mostlymaxi still?"),
+        "@mostlymaxi is coding"
+    );
+    assert_eq!(
+        send(&mut after, "!unlurk finally"),
+        "welcome back! hope you were productive coding"
+    );
+
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Test that recorded chat runs through the same dispatcher as live chat
+#[test]
+fn test_chat_source() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let mut config = test_config();
+    config.commands.insert(
+        "ping".to_owned(),
+        CommandConfig {
+            enabled: true,
+            cooldown: Some(Duration::ZERO),
+        },
+    );
+    commands.configure(&config.commands);
+
+    let recorded = [
+        create_chat_msg("!ping", "id_source").to_string(),
+        "{\"not\": \"chat\"}".to_owned(),
+        String::new(),
+        create_chat_msg("!ping", "id_source").to_string(),
+    ]
+    .join("\n");
+    let mut source = LineSource::new(recorded.as_bytes());
+
+    let mut events = 0;
+    while let Some(event) = source.next_chat().unwrap() {
+        events += 1;
+        let Some(chat_msg) = &event.message else {
+            continue;
+        };
+        handle_command_if_applicable(
+            chat_msg,
+            &mut api,
+            &mut commands,
+            &config,
+            &mut spam,
+            &services,
+        );
+    }
+
+    assert_eq!(events, 3);
+    assert_eq!(api.as_mock().unwrap().sent_messages(), ["pong", "pong"]);
+}
+
+/// Test that other services can only do what they're allowed to through the outbox
+#[test]
+fn test_outbox() {
+    let config = Config::from_toml(
+        r#"
+        [outbox.services.points]
+        actions = ["say", "timeout"]
+        "#,
+    )
+    .unwrap();
+    let mut outbox = Outbox::new(&config.outbox);
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+
+    let requests = [
+        r#"{"id": "1", "service": "points", "action": "say", "message": "hydrate!"}"#,
+        r#"{"id": "2", "service": "points", "action": "timeout", "user_id": "123", "duration": "10m", "reason": "redeemed"}"#,
+        r#"{"id": "3", "service": "points", "action": "announce", "message": "hi"}"#,
+        r#"{"id": "4", "service": "chess", "action": "say", "message": "hi"}"#,
+        r#"{"id": "5", "service": "points", "action": "ban"}"#,
+    ]
+    .join("\n");
+    let mut source = LineSource::new(requests.as_bytes());
+
+    let mut results = Vec::new();
+    while let Some(raw) = source.recv().unwrap() {
+        let result = match OutboxRequest::parse(&raw) {
+            Ok(request) => outbox.handle(&request, &mut api),
+            Err(e) => OutboxResult::malformed(&raw, &e),
+        };
+        results.push((result.id.unwrap(), result.ok));
     }
+
+    assert_eq!(
+        results,
+        [
+            ("1".to_owned(), true),
+            ("2".to_owned(), true),
+            ("3".to_owned(), false),
+            ("4".to_owned(), false),
+            ("5".to_owned(), false),
+        ]
+    );
+
+    let mock = api.as_mock().unwrap();
+    assert_eq!(mock.sent_messages(), ["hydrate!"]);
+    assert!(mock.announcements().is_empty());
+    assert_eq!(
+        mock.mod_actions(),
+        [ModAction::Timeout {
+            user_id: "123".to_owned(),
+            duration: Duration::from_secs(600),
+            reason: "redeemed".to_owned(),
+        }]
+    );
+}
+
+/// Test that commands can publish to the topics they declared, and only those
+#[test]
+fn test_command_events() {
+    /// Publishes to a topic it never declared
+    struct Sneaky;
+
+    impl ChatCommand for Sneaky {
+        fn new() -> Self {
+            Self
+        }
+
+        fn names() -> Vec<String> {
+            vec!["sneaky".to_owned()]
+        }
+
+        fn handle(&mut self, _api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+            ctx.publish("points-term", &json!({ "action": "train" }))
+        }
+
+        fn help(&self) -> String {
+            "usage: !sneaky".to_owned()
+        }
+    }
+
+    let mut commands = CommandMap::new();
+    commands.insert(train::MostlyTrain::new());
+    commands.insert(Sneaky::new());
+    assert_eq!(
+        commands.topics().into_iter().collect::<Vec<_>>(),
+        ["points-term"]
+    );
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let events = MemoryEvents::default();
+    let services = Services {
+        events: EventBus::new(events.clone()),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let config = test_config();
+
+    for text in ["!train", "!sneaky"] {
+        let msg: MessageData = serde_json::from_value(create_chat_msg(text, "id_events")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    assert_eq!(
+        events.events(),
+        [(
+            "points-term".to_owned(),
+            r#"{"action":"train","by":"mostlymaxi"}"#.to_owned()
+        )]
+    );
+    let sent = api.as_mock().unwrap().sent_messages();
+    assert_eq!(sent[0], "choo choo");
+    assert!(sent[1].contains("internal error"), "{}", sent[1]);
+}
+
+/// Test that the dispatcher counts handled commands and cooldowns for /metrics
+#[test]
+fn test_metrics() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(1, Duration::from_secs(60)),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let metrics = Metrics::default();
+    let services = Services {
+        metrics: metrics.clone(),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let config = test_config();
+
+    for _ in 0..2 {
+        let msg: MessageData =
+            serde_json::from_value(create_chat_msg("!ping", "id_metrics")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    let text = metrics.render();
+    for line in [
+        "mostlybot_commands_handled_total{command=\"ping\",result=\"ok\"} 1",
+        "mostlybot_command_duration_seconds_count{command=\"ping\"} 1",
+        "mostlybot_chat_errors_total{kind=\"command_cooldown\"} 1",
+        "mostlybot_cooldown_rejections_total{scope=\"command\"} 1",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "missing {line:?} in\n{text}"
+        );
+    }
+}
+
+/// Test that every dispatch ends up in the audit log, rejected ones included
+#[test]
+fn test_audit_log() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(1, Duration::from_secs(60)),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let data_dir = std::env::temp_dir().join(format!("mostlybot-audit-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let events = MemoryEvents::default();
+    let audit_config = AuditConfig {
+        file: PathBuf::new(),
+        topic: "bot-audit".to_owned(),
+        ..AuditConfig::default()
+    };
+    let services = Services {
+        audit: AuditLog::new(&audit_config, &data_dir, EventBus::new(events.clone())),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let config = test_config();
+
+    for text in ["!ping now", "!ping", "!nope", "hello chat"] {
+        let msg: MessageData = serde_json::from_value(create_chat_msg(text, "id_audit")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    let audit: Vec<serde_json::Value> = events
+        .events()
+        .iter()
+        .map(|(topic, event)| {
+            assert_eq!(topic, "bot-audit");
+            serde_json::from_str(event).unwrap()
+        })
+        .collect();
+    assert_eq!(audit.len(), 3, "chat that isn't a command isn't audited");
+
+    assert_eq!(audit[0]["command"], "ping");
+    assert_eq!(audit[0]["args"], json!(["now"]));
+    assert_eq!(audit[0]["user_id"], "id_audit");
+    assert_eq!(audit[0]["role"], "broadcaster");
+    assert_eq!(audit[0]["outcome"], "ok");
+    assert_eq!(audit[0]["reply_ids"], json!(["mock-1"]));
+
+    assert_eq!(audit[1]["outcome"], "command_cooldown");
+    assert_eq!(audit[1]["reply_ids"], json!(["mock-2"]));
+    assert_eq!(audit[2]["command"], "nope");
+    assert_eq!(audit[2]["outcome"], "command_does_not_exist");
+
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Test that the dispatcher counts every command it runs, under the command's own name
+#[test]
+fn test_usage_counts() {
+    let mut commands = CommandMap::new();
+    commands.insert(lurk::Lurk::new());
+    commands.insert(ping::MostlyPing::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(1, Duration::from_secs(60)),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let usage = Usage::default();
+    let services = Services {
+        usage: usage.clone(),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let config = test_config();
+
+    // the second !ping is on cooldown and never runs
+    for text in ["!lurk", "!unlurk", "!ping", "!ping", "!nope"] {
+        let msg: MessageData = serde_json::from_value(create_chat_msg(text, "id_usage")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    let report = usage.report();
+    assert_eq!(
+        UsageReport::top(&report.commands, 5),
+        [("lurk", 2), ("ping", 1)]
+    );
+    assert_eq!(report.users["id_usage"].total(), 3);
+    assert_eq!(report.streams.len(), 1);
+}
+
+/// Test that a panicking command is caught, and disabled once it keeps panicking
+#[test]
+fn test_command_panics() {
+    /// Panics whenever it's run, like an unfinished ```todo!()```
+    struct Panicky;
+
+    impl ChatCommand for Panicky {
+        fn new() -> Self {
+            Self
+        }
+
+        fn names() -> Vec<String> {
+            vec!["panicky".to_owned()]
+        }
+
+        fn handle(&mut self, _api: &mut TwitchApiWrapper, _ctx: &CommandContext) -> CommandResult {
+            todo!()
+        }
+
+        fn help(&self) -> String {
+            "usage: !panicky".to_owned()
+        }
+
+        fn rate_limit(&self) -> RateLimit {
+            RateLimit::new(0, Duration::ZERO)
+        }
+    }
+
+    /// Takes longer than the test's time budget
+    struct Slow;
+
+    impl ChatCommand for Slow {
+        fn new() -> Self {
+            Self
+        }
+
+        fn names() -> Vec<String> {
+            vec!["slow".to_owned()]
+        }
+
+        fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+            std::thread::sleep(Duration::from_millis(20));
+            api.reply(ctx, "done")
+        }
+
+        fn help(&self) -> String {
+            "usage: !slow".to_owned()
+        }
+
+        fn rate_limit(&self) -> RateLimit {
+            RateLimit::new(0, Duration::ZERO)
+        }
+    }
+
+    let mut commands = CommandMap::new();
+    commands.insert(Panicky::new());
+    commands.insert(Slow::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let metrics = Metrics::default();
+    let services = Services {
+        metrics: metrics.clone(),
+        ..Services::new(BotRng::seed_from_u64(0))
+    };
+    let mut config = test_config();
+    config.handlers.max_panics = 2;
+    config.handlers.budget = Duration::from_millis(10);
+
+    for text in [
+        "!panicky", "!panicky", "!panicky", "!slow", "!slow", "!slow",
+    ] {
+        let msg: MessageData = serde_json::from_value(create_chat_msg(text, "id_panic")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    let sent = api.as_mock().unwrap().sent_messages();
+    assert!(sent[0].contains("\"panicky\" ran into an internal error"));
+    assert!(sent[1].contains("\"panicky\" ran into an internal error"));
+    assert!(sent[2].contains("\"panicky\" is disabled right now"));
+    assert_eq!(sent[3], "done");
+    assert_eq!(sent[4], "done");
+    assert!(
+        sent[5].contains("\"slow\" is disabled right now"),
+        "running over budget counts like a panic"
+    );
+
+    let panicky = commands.get("panicky").unwrap();
+    assert!(!panicky.is_enabled());
+    assert_eq!(
+        panicky.panics(),
+        0,
+        "counting starts over once it's disabled"
+    );
+
+    let text = metrics.render();
+    assert!(text.contains("mostlybot_command_panics_total{command=\"panicky\"} 2"));
+    assert!(text.contains("mostlybot_command_over_budget_total{command=\"slow\"} 2"));
+}
+
+/// Test that ignored chatters and other bots never reach a command, and that quiet bots
+/// aren't told off when they get something wrong
+#[test]
+fn test_ignored_chatters() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    services.ignore.ignore("troll").unwrap();
+    let mut config = test_config();
+
+    let mut send = |config: &Config, text: &str, login: &str| {
+        let mut msg = create_chat_msg(text, &format!("id_{login}"));
+        msg["chatter_user_login"] = json!(login);
+        msg["badges"] = json!([]);
+        let msg: MessageData = serde_json::from_value(msg).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, config, &mut spam, &services);
+    };
+
+    send(&config, "!ping", "troll");
+    send(&config, "!ping", "nightbot");
+
+    config.ignore.bot_messages = BotPolicy::Quiet;
+    send(&config, "!nope", "nightbot");
+    send(&config, "!ping", "nightbot");
+
+    config.ignore.bot_messages = BotPolicy::Allow;
+    send(&config, "!nope", "nightbot");
+
+    let sent = api.as_mock().unwrap().sent_messages();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0], "pong");
+    assert!(sent[1].contains("\"nope\" does not exist"));
+}
+
+/// Test that chat filters act on every message, escalate with repeat offences and leave
+/// moderators, permitted chatters and other bots alone
+#[test]
+fn test_moderation_filters() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+    commands.insert(permit::MostlyPermit::new());
+    commands.insert(lurk::Lurk::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let mut config = test_config();
+    config.moderation.enabled = true;
+    config.moderation.actions = vec![
+        FilterAction::Delete,
+        FilterAction::Timeout {
+            duration: Duration::from_secs(60),
+        },
+    ];
+
+    let mut send = |text: &str, login: &str, badges: serde_json::Value| {
+        let mut msg = create_chat_msg(text, &format!("id_{login}"));
+        msg["chatter_user_login"] = json!(login);
+        msg["badges"] = badges;
+        let msg: MessageData = serde_json::from_value(msg).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    };
+    let moderator = json!([{ "set_id": "moderator", "id": "1", "info": "" }]);
+
+    send("!ping https://evil.com", "chatter", json!([]));
+    send("FREE FOLLOWERS AT EVIL DOT COM", "chatter", json!([]));
+    send("!lurk https://evil.com", "modperson", moderator.clone());
+    send("go to evil.com", "nightbot", json!([]));
+    send("!permit @chatter", "modperson", moderator);
+    send("!ping evil.com", "chatter", json!([]));
+
+    let mock = api.as_mock().unwrap();
+    assert_eq!(
+        mock.mod_actions(),
+        [
+            ModAction::Delete {
+                message_id: "3104f083-2bdb-4d6a-bb5d-30b407876ea4".to_owned()
+            },
+            ModAction::Timeout {
+                user_id: "id_chatter".to_owned(),
+                duration: Duration::from_secs(60),
+                reason: "please don't shout (caps filter)".to_owned(),
+            },
+        ]
+    );
+    assert_eq!(
+        mock.sent_messages(),
+        [
+            "@mostlymaxi, no links please, ask a moderator for a !permit",
+            "@mostlymaxi, please don't shout",
+            "have a nice lurk!",
+            "@chatter can post links for the next 2m",
+            "pong",
+        ]
+    );
+}
+
+/// Test that a copypasta wave across accounts is caught once enough chatters join in, and
+/// that moderators can see it with !modlog
+#[test]
+fn test_flood_filters() {
+    let mut commands = CommandMap::new();
+    commands.insert(modlog::MostlyModLog::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let mut config = test_config();
+    config.moderation.enabled = true;
+    config.moderation.flood.copypasta = 3;
+
+    let mut send = |text: &str, login: &str, badges: serde_json::Value| {
+        let mut msg = create_chat_msg(text, &format!("id_{login}"));
+        msg["chatter_user_login"] = json!(login);
+        msg["chatter_user_name"] = json!(login);
+        msg["badges"] = badges;
+        let msg: MessageData = serde_json::from_value(msg).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    };
+
+    let pasta = "what if we kissed in the rust borrow checker";
+    for login in ["one", "two", "three", "four"] {
+        send(pasta, login, json!([]));
+    }
+    send(
+        "!modlog",
+        "modperson",
+        json!([{ "set_id": "moderator", "id": "1", "info": "" }]),
+    );
+
+    let sent: Vec<_> = api
+        .as_mock()
+        .unwrap()
+        .sent_messages()
+        .iter()
+        .filter(|msg| !msg.contains("is not a command"))
+        .cloned()
+        .collect();
+    assert_eq!(
+        sent,
+        [
+            "@three, no copypasta please",
+            "@four, no copypasta please",
+            "last filtered: four (copypasta, warn), three (copypasta, warn)",
+        ]
+    );
+}
+
+/// Test that commands repeating what chat said can't make the bot post links, run chat
+/// commands or say blocked words
+#[test]
+fn test_output_sanitizer() {
+    let mut commands = CommandMap::new();
+    commands.insert(ban::MostlyBan::new());
+    commands.insert(rewrite::MostlyRewrite::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let mut config = test_config();
+    config.output.blocked = vec!["heck".to_owned()];
+    api.set_sanitizer(Sanitizer::new(&config));
+
+    for text in ["!ban /ban evil.com", "!rewrite heck"] {
+        let msg: MessageData = serde_json::from_value(create_chat_msg(text, "id_chatter")).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    }
+
+    assert_eq!(
+        api.as_mock().unwrap().sent_messages(),
+        [
+            "ban <link removed> has been banned",
+            "sorry, I'd rather not say that",
+        ]
+    );
+}
+
+/// Test that the bot remembers which chatter made it say what, so moderators can take it back
+#[test]
+fn test_undo() {
+    let mut commands = CommandMap::new();
+    commands.insert(ping::MostlyPing::new());
+    commands.insert(lurk::Lurk::new());
+    commands.insert(undo::MostlyUndo::new());
+
+    let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+    let mut spam = Spam::new(
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+        RateLimit::new(0, Duration::ZERO),
+    );
+    let services = Services::new(BotRng::seed_from_u64(0));
+    let config = test_config();
+
+    let mut send = |text: &str, login: &str, badges: serde_json::Value| {
+        let mut msg = create_chat_msg(text, &format!("id_{login}"));
+        msg["chatter_user_login"] = json!(login);
+        msg["badges"] = badges;
+        let msg: MessageData = serde_json::from_value(msg).unwrap();
+        handle_command_if_applicable(&msg, &mut api, &mut commands, &config, &mut spam, &services);
+    };
+    let moderator = json!([{ "set_id": "moderator", "id": "1", "info": "" }]);
+
+    send("!ping", "chatter", json!([]));
+    send("!lurk", "lurker", json!([]));
+    send("!undo", "modperson", moderator.clone());
+    send("!undo @chatter", "modperson", moderator.clone());
+    // undo's own replies are never taken back
+    send("!undo", "modperson", moderator);
+
+    let mock = api.as_mock().unwrap();
+    assert_eq!(
+        mock.mod_actions(),
+        [
+            ModAction::Delete {
+                message_id: "mock-2".to_owned()
+            },
+            ModAction::Delete {
+                message_id: "mock-1".to_owned()
+            },
+        ]
+    );
+    assert_eq!(
+        mock.sent_messages()[2..],
+        ["deleted 1 message for @chatter", "nothing to undo"]
+    );
+    assert_eq!(services.sent.len(), 0);
 }
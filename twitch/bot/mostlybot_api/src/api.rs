@@ -1,52 +1,392 @@
-use std::time::Duration;
-use twitcheventsub::{EventSubError, TwitchEventSubApi};
+use crate::{error::CommandResult, sanitize::Sanitizer};
+use std::{any::Any, fmt, time::Duration};
+use tracing::{debug, error};
+use twitcheventsub::{EventSubError, MessageData, TwitchEventSubApi};
 
-pub struct MockTwitchEventSubApi {}
+/// Something a moderator (or the bot, as one) can do to a message or chatter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModAction {
+    Delete {
+        message_id: String,
+    },
+    Timeout {
+        user_id: String,
+        duration: Duration,
+        reason: String,
+    },
+}
+
+/// What the bot is acting on, see [`ChatPlatform::handling`]
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger<'a> {
+    Chat(&'a MessageData),
+    /// A request another service put on the outbox
+    Outbox {
+        service: &'a str,
+        id: &'a str,
+    },
+}
+
+#[derive(Debug)]
+pub enum PlatformError {
+    /// The platform can't do that at all, like whispers in a terminal
+    Unsupported(&'static str),
+    /// Talking to the platform failed
+    Failed(String),
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(what) => write!(f, "{what} isn't supported here"),
+            Self::Failed(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PlatformError {}
+
+impl From<EventSubError> for PlatformError {
+    fn from(err: EventSubError) -> Self {
+        Self::Failed(format!("{err:?}"))
+    }
+}
+
+/// Where the bot's messages and moderation end up. Twitch and the test mock implement
+/// this, anything else (a terminal chat, a dry-run logger, ...) only needs to implement
+/// it too and can then be handed to [`TwitchApiWrapper::new`]
+pub trait ChatPlatform: Any {
+    /// Sends a message to chat and returns its id
+    fn send(&mut self, message: &str) -> Result<String, PlatformError>;
+
+    /// Sends a message as a reply to ```parent_id``` and returns its id
+    fn reply(&mut self, message: &str, parent_id: &str) -> Result<String, PlatformError>;
+
+    /// Sends a message that stands out from the rest of chat
+    fn announce(&mut self, message: &str) -> Result<(), PlatformError>;
+
+    fn moderate(&mut self, action: &ModAction) -> Result<(), PlatformError>;
+
+    fn whisper(&mut self, user_id: &str, message: &str) -> Result<(), PlatformError>;
+
+    /// Called with every chat message and outbox request before the bot acts on it, for
+    /// platforms that want to know what caused whatever comes next
+    fn handling(&mut self, _trigger: Trigger) {}
+}
+
+// Twitch ---------------------------------------------------------------------
+
+impl ChatPlatform for TwitchEventSubApi {
+    fn send(&mut self, message: &str) -> Result<String, PlatformError> {
+        let res = self.send_chat_message(message);
+        // apparently this is more than enough
+        std::thread::sleep(Duration::from_millis(100));
+        Ok(res?)
+    }
+
+    fn reply(&mut self, message: &str, parent_id: &str) -> Result<String, PlatformError> {
+        let res = self.send_chat_message_with_reply(message, Some(parent_id.to_owned()));
+        // apparently this is more than enough
+        std::thread::sleep(Duration::from_millis(100));
+        Ok(res?)
+    }
+
+    fn announce(&mut self, message: &str) -> Result<(), PlatformError> {
+        self.send_announcement(message, None::<String>)?;
+        Ok(())
+    }
+
+    fn moderate(&mut self, action: &ModAction) -> Result<(), PlatformError> {
+        match action {
+            ModAction::Delete { message_id } => self.delete_message(message_id.as_str())?,
+            ModAction::Timeout {
+                user_id,
+                duration,
+                reason,
+            } => self.timeout_user(
+                user_id.as_str(),
+                duration.as_secs().clamp(1, u32::MAX.into()) as u32,
+                reason.as_str(),
+            )?,
+        };
+        Ok(())
+    }
+
+    fn whisper(&mut self, _user_id: &str, _message: &str) -> Result<(), PlatformError> {
+        Err(PlatformError::Unsupported("whispering"))
+    }
+}
+
+// Mock -----------------------------------------------------------------------
+
+/// Remembers everything instead of sending it, for tests
+#[derive(Default)]
+pub struct MockTwitchEventSubApi {
+    sent: Vec<String>,
+    announcements: Vec<String>,
+    actions: Vec<ModAction>,
+    whispers: Vec<(String, String)>,
+}
 
 impl MockTwitchEventSubApi {
     pub fn init_twitch_api() -> MockTwitchEventSubApi {
-        MockTwitchEventSubApi {}
+        MockTwitchEventSubApi::default()
+    }
+
+    /// Every message the bot "sent" so far, in order
+    pub fn sent_messages(&self) -> &[String] {
+        &self.sent
+    }
+
+    pub fn announcements(&self) -> &[String] {
+        &self.announcements
+    }
+
+    pub fn mod_actions(&self) -> &[ModAction] {
+        &self.actions
+    }
+
+    /// ```(user_id, message)``` for every whisper
+    pub fn whispers(&self) -> &[(String, String)] {
+        &self.whispers
     }
 }
 
-pub enum TwitchApiWrapper {
-    Live(TwitchEventSubApi),
-    Test(MockTwitchEventSubApi),
+impl ChatPlatform for MockTwitchEventSubApi {
+    fn send(&mut self, message: &str) -> Result<String, PlatformError> {
+        println!("{}", message);
+        self.sent.push(message.to_owned());
+        Ok(format!("mock-{}", self.sent.len()))
+    }
+
+    fn reply(&mut self, message: &str, _parent_id: &str) -> Result<String, PlatformError> {
+        self.send(message)
+    }
+
+    fn announce(&mut self, message: &str) -> Result<(), PlatformError> {
+        self.announcements.push(message.to_owned());
+        Ok(())
+    }
+
+    fn moderate(&mut self, action: &ModAction) -> Result<(), PlatformError> {
+        self.actions.push(action.clone());
+        Ok(())
+    }
+
+    fn whisper(&mut self, user_id: &str, message: &str) -> Result<(), PlatformError> {
+        self.whispers.push((user_id.to_owned(), message.to_owned()));
+        Ok(())
+    }
+}
+
+// Wrapper --------------------------------------------------------------------
+
+/// What commands get to talk to chat with, whichever [`ChatPlatform`] is behind it
+pub struct TwitchApiWrapper {
+    platform: Box<dyn ChatPlatform>,
+    /// Ids of the messages sent since the last [`TwitchApiWrapper::handling`]
+    sent_ids: Vec<String>,
+    /// Every message goes through it before it's sent
+    sanitizer: Sanitizer,
+    /// The chat message being handled, for the sanitizer to know what's being repeated
+    echo: String,
 }
 
 impl TwitchApiWrapper {
+    pub fn new(platform: impl ChatPlatform) -> Self {
+        Self {
+            platform: Box::new(platform),
+            sent_ids: Vec::new(),
+            sanitizer: Sanitizer::default(),
+            echo: String::new(),
+        }
+    }
+
+    /// Replaces the default sanitizer, e.g. with one from a reloaded config
+    pub fn set_sanitizer(&mut self, sanitizer: Sanitizer) {
+        self.sanitizer = sanitizer;
+    }
+
+    /// What is actually sent instead of ```message```, see [`Sanitizer::sanitize`]
+    fn sanitized(&self, message: String) -> String {
+        match self.sanitizer.sanitize(&message, &self.echo) {
+            Ok(sanitized) => sanitized,
+            Err(refusal) => {
+                tracing::warn!(target: "sanitizer", %message, refusal, "refusing to send");
+                refusal.to_owned()
+            }
+        }
+    }
+
+    /// The platform behind the wrapper, if it's a ```P```
+    pub fn platform<P: ChatPlatform>(&self) -> Option<&P> {
+        let platform: &dyn Any = &*self.platform;
+        platform.downcast_ref()
+    }
+
+    /// Returns the mock api when running in test mode, useful for asserting on replies
+    pub fn as_mock(&self) -> Option<&MockTwitchEventSubApi> {
+        self.platform()
+    }
+
     pub fn send_chat_message<S: Into<String>>(
         &mut self,
         message: S,
-    ) -> Result<String, EventSubError> {
-        let res = match self {
-            Self::Live(api) => api.send_chat_message(message),
-            Self::Test(_mock) => todo!(),
-        };
-
-        // apparently this is more than enough
-        std::thread::sleep(Duration::from_millis(100));
-        res
+    ) -> Result<String, PlatformError> {
+        let message = self.sanitized(message.into());
+        let id = self.platform.send(&message)?;
+        self.sent(&id);
+        Ok(id)
     }
 
     pub fn send_chat_message_with_reply<S: Into<String>>(
         &mut self,
         message: S,
         reply_message_parent_id: Option<S>,
-    ) -> Result<String, EventSubError> {
-        let res = match self {
-            Self::Live(api) => {
-                api.send_chat_message_with_reply(message, reply_message_parent_id.map(S::into))
+    ) -> Result<String, PlatformError> {
+        let message = self.sanitized(message.into());
+        let id = match reply_message_parent_id {
+            Some(parent_id) => self.platform.reply(&message, &parent_id.into()),
+            None => self.platform.send(&message),
+        }?;
+        self.sent(&id);
+        Ok(id)
+    }
+
+    /// Replies to the message that triggered a command, logging the outcome.
+    ///
+    /// ```ignore
+    /// api.reply(ctx, "pong")
+    /// ```
+    pub fn reply<S: Into<String>>(&mut self, ctx: &MessageData, message: S) -> CommandResult {
+        let message = self.sanitized(message.into());
+        match self.platform.reply(&message, &ctx.message_id) {
+            Ok(s) => {
+                debug!(reply = %s);
+                self.sent(&s);
+                Ok(())
             }
-            Self::Test(_mock) => {
-                println!("{}", message.into());
-                Ok(String::new())
+            Err(e) => {
+                error!(error = ?e);
+                Err(e.into())
             }
+        }
+    }
+
+    pub fn announce<S: Into<String>>(&mut self, message: S) -> Result<(), PlatformError> {
+        let message = self.sanitized(message.into());
+        self.platform.announce(&message)
+    }
+
+    pub fn moderate(&mut self, action: ModAction) -> Result<(), PlatformError> {
+        self.platform.moderate(&action)
+    }
+
+    /// Tells the platform what everything after this is about
+    pub fn handling(&mut self, trigger: Trigger) {
+        self.sent_ids.clear();
+        self.echo = match trigger {
+            Trigger::Chat(msg) => msg.message.text.clone(),
+            Trigger::Outbox { .. } => String::new(),
         };
+        self.platform.handling(trigger);
+    }
 
-        // apparently this is more than enough
-        std::thread::sleep(Duration::from_millis(100));
+    fn sent(&mut self, id: &str) {
+        if !id.is_empty() {
+            self.sent_ids.push(id.to_owned());
+        }
+    }
+
+    /// Ids of the messages sent since the last [`TwitchApiWrapper::handling`], for the audit log
+    pub fn sent_ids(&self) -> &[String] {
+        &self.sent_ids
+    }
+
+    pub fn whisper<S: Into<String>>(
+        &mut self,
+        user_id: &str,
+        message: S,
+    ) -> Result<(), PlatformError> {
+        let message = self.sanitized(message.into());
+        self.platform.whisper(user_id, &message)
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A platform the crate knows nothing about
+    #[derive(Default)]
+    struct Terminal(Vec<String>);
+
+    impl ChatPlatform for Terminal {
+        fn send(&mut self, message: &str) -> Result<String, PlatformError> {
+            self.0.push(message.to_owned());
+            Ok(self.0.len().to_string())
+        }
+
+        fn reply(&mut self, message: &str, parent_id: &str) -> Result<String, PlatformError> {
+            self.send(&format!("> {parent_id}: {message}"))
+        }
+
+        fn announce(&mut self, message: &str) -> Result<(), PlatformError> {
+            self.send(&message.to_uppercase()).map(|_| ())
+        }
+
+        fn moderate(&mut self, _action: &ModAction) -> Result<(), PlatformError> {
+            Err(PlatformError::Unsupported("moderation"))
+        }
+
+        fn whisper(&mut self, _user_id: &str, _message: &str) -> Result<(), PlatformError> {
+            Err(PlatformError::Unsupported("whispering"))
+        }
+    }
+
+    #[test]
+    fn custom_platform() {
+        let mut api = TwitchApiWrapper::new(Terminal::default());
+        assert!(api.as_mock().is_none());
+
+        api.send_chat_message("hi").unwrap();
+        api.send_chat_message_with_reply("pong", Some("abc"))
+            .unwrap();
+        api.announce("stream starting").unwrap();
+        assert!(matches!(
+            api.moderate(ModAction::Delete {
+                message_id: "abc".to_owned()
+            }),
+            Err(PlatformError::Unsupported(_))
+        ));
+
+        assert_eq!(
+            api.platform::<Terminal>().unwrap().0,
+            ["hi", "> abc: pong", "STREAM STARTING"]
+        );
+    }
+
+    #[test]
+    fn mock_records_everything() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+
+        api.send_chat_message("hi").unwrap();
+        api.announce("hello chat").unwrap();
+        api.whisper("123", "psst").unwrap();
+        let timeout = ModAction::Timeout {
+            user_id: "123".to_owned(),
+            duration: Duration::from_secs(60),
+            reason: "spam".to_owned(),
+        };
+        api.moderate(timeout.clone()).unwrap();
 
-        res
+        let mock = api.as_mock().unwrap();
+        assert_eq!(mock.sent_messages(), ["hi"]);
+        assert_eq!(mock.announcements(), ["hello chat"]);
+        assert_eq!(mock.whispers(), [("123".to_owned(), "psst".to_owned())]);
+        assert_eq!(mock.mod_actions(), [timeout]);
+        assert_eq!(api.sent_ids(), ["mock-1"]);
     }
 }
@@ -0,0 +1,485 @@
+use crate::error::CommandError;
+use std::{fmt, time::Duration};
+
+// Typed Values ---------------------------------------------------------------
+
+/// A value that can be parsed out of a single chat word
+pub trait FromArg: Sized {
+    /// What the value looks like, used in error messages e.g. "a number"
+    const EXPECTED: &'static str;
+
+    fn from_arg(word: &str) -> Option<Self>;
+}
+
+impl FromArg for String {
+    const EXPECTED: &'static str = "a word";
+
+    fn from_arg(word: &str) -> Option<Self> {
+        Some(word.to_owned())
+    }
+}
+
+macro_rules! impl_from_arg_int {
+    ($($t:ty),*) => {
+        $(
+            impl FromArg for $t {
+                const EXPECTED: &'static str = "a number";
+
+                fn from_arg(word: &str) -> Option<Self> {
+                    word.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_arg_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromArg for Duration {
+    const EXPECTED: &'static str = "a duration like 30s, 5m or 250ms";
+
+    fn from_arg(word: &str) -> Option<Self> {
+        parse_duration(word)
+    }
+}
+
+/// A chatter mentioned by name, with or without the leading ```@```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mention(pub String);
+
+impl FromArg for Mention {
+    const EXPECTED: &'static str = "an @username";
+
+    fn from_arg(word: &str) -> Option<Self> {
+        let name = word.strip_prefix('@').unwrap_or(word);
+
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        Some(Self(name.to_owned()))
+    }
+}
+
+/// Parses durations like ```250ms```, ```30s```, ```5m```, ```1h``` or ```1m30s```.
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+
+    if let Ok(secs) = s.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        if digits == 0 {
+            return None;
+        }
+        let (value, tail) = rest.split_at(digits);
+        let value: u64 = value.parse().ok()?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let part = match unit {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => Duration::from_secs(value.checked_mul(60)?),
+            "h" => Duration::from_secs(value.checked_mul(60 * 60)?),
+            _ => return None,
+        };
+        total = total.checked_add(part)?;
+        rest = tail;
+    }
+
+    Some(total)
+}
+
+/// Short durations for chat, the other way around from [`parse_duration`]: ```2h5m```,
+/// ```3m12s```, ```2m```, ```40s``` and ```0.3s``` below ten seconds
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=9 => format!("{:.1}s", duration.as_secs_f32()),
+        10..=59 => format!("{secs}s"),
+        60..=3599 if secs.is_multiple_of(60) => format!("{}m", secs / 60),
+        60..=3599 => format!("{}m{}s", secs / 60, secs % 60),
+        _ if (secs / 60).is_multiple_of(60) => format!("{}h", secs / 3600),
+        _ => format!("{}h{}m", secs / 3600, secs / 60 % 60),
+    }
+}
+
+// Argument Cursor ------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    Missing(String),
+    Invalid {
+        name: String,
+        value: String,
+        expected: &'static str,
+    },
+    Unexpected(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(name) => write!(f, "missing <{name}>"),
+            Self::Invalid {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "\"{value}\" is not a valid <{name}> (expected {expected})"
+            ),
+            Self::Unexpected(value) => write!(f, "didn't expect \"{value}\""),
+        }
+    }
+}
+
+/// The words of a chat message that still need to be parsed
+#[derive(Debug, Clone)]
+pub struct Args<'a> {
+    words: Vec<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    /// Every word of the message, including the command name
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            words: text.split_whitespace().collect(),
+        }
+    }
+
+    pub fn peek(&self) -> Option<&'a str> {
+        self.words.first().copied()
+    }
+
+    pub fn next_word(&mut self) -> Option<&'a str> {
+        if self.words.is_empty() {
+            return None;
+        }
+        Some(self.words.remove(0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// A required value
+    pub fn positional<T: FromArg>(&mut self, name: &str) -> Result<T, ArgError> {
+        match self.optional(name)? {
+            Some(value) => Ok(value),
+            None => Err(ArgError::Missing(name.to_owned())),
+        }
+    }
+
+    /// A value that may be left out, but has to be valid if it's there
+    pub fn optional<T: FromArg>(&mut self, name: &str) -> Result<Option<T>, ArgError> {
+        let Some(word) = self.next_word() else {
+            return Ok(None);
+        };
+
+        T::from_arg(word)
+            .map(Some)
+            .ok_or_else(|| ArgError::Invalid {
+                name: name.to_owned(),
+                value: word.to_owned(),
+                expected: T::EXPECTED,
+            })
+    }
+
+    /// All remaining words joined by spaces, at least one is required
+    pub fn rest(&mut self, name: &str) -> Result<String, ArgError> {
+        self.optional_rest()
+            .ok_or_else(|| ArgError::Missing(name.to_owned()))
+    }
+
+    pub fn optional_rest(&mut self) -> Option<String> {
+        if self.words.is_empty() {
+            return None;
+        }
+        Some(self.words.drain(..).collect::<Vec<_>>().join(" "))
+    }
+
+    /// Takes ```--name``` out of the message wherever it is
+    pub fn flag(&mut self, name: &str) -> bool {
+        let flag = format!("--{name}");
+        let len = self.words.len();
+        self.words.retain(|w| *w != flag);
+        self.words.len() != len
+    }
+
+    /// Errors if there are words nobody asked for
+    pub fn finish(&self) -> Result<(), ArgError> {
+        match self.peek() {
+            Some(word) => Err(ArgError::Unexpected(word.to_owned())),
+            None => Ok(()),
+        }
+    }
+}
+
+// Command Arguments ----------------------------------------------------------
+
+/// Arguments of a chat command, usually derived:
+///
+/// ```ignore
+/// #[derive(CommandArgs)]
+/// enum TicTacToeArgs {
+///     #[arg(subcommand)]
+///     Print,
+///     #[arg(subcommand)]
+///     Reset,
+///     Place { square: u8 },
+/// }
+/// ```
+///
+/// Structs parse their fields in order. ```Option<T>``` fields are optional,
+/// ```#[arg(rest)]``` takes every remaining word and ```#[arg(flag)]``` bools
+/// are set by ```--name``` anywhere in the message. ```#[arg(rest, ignore)]``` takes
+/// the remaining words and throws them away, the field is left at its default.
+///
+/// Enums try each variant and pick the first one that uses up the whole message.
+/// Variants marked ```#[arg(subcommand)]``` (or ```#[arg(subcommand = "name")]```)
+/// must start with their keyword and are tried before the others.
+pub trait CommandArgs: Sized {
+    /// Parses the words after the command name
+    fn parse_args(args: &mut Args) -> Result<Self, ArgError>;
+
+    /// One line per way of using the command, without the command name
+    fn usage_lines() -> Vec<String>;
+
+    /// Parses a chat message, skipping the command name
+    fn parse(text: &str) -> Result<Self, CommandError> {
+        let mut args = Args::new(text);
+        let cmd = args
+            .next_word()
+            .unwrap_or_default()
+            .trim_start_matches(is_prefix);
+
+        Self::parse_args(&mut args)
+            .and_then(|parsed| args.finish().map(|_| parsed))
+            .map_err(|err| CommandError::UserFacing(format!("{err}, {}", Self::usage(cmd))))
+    }
+
+    /// Parses a chat message where the command name itself is the subcommand
+    /// (```!lurk``` vs ```!unlurk```)
+    fn parse_invocation(text: &str) -> Result<Self, CommandError> {
+        let text = text.trim_start().trim_start_matches(is_prefix);
+        let mut args = Args::new(text);
+
+        Self::parse_args(&mut args)
+            .and_then(|parsed| args.finish().map(|_| parsed))
+            .map_err(|err| CommandError::UserFacing(format!("{err}, {}", Self::invocation_usage())))
+    }
+
+    /// e.g. ```usage: !mostlypasta <gnu> <linux>```
+    fn usage(cmd: &str) -> String {
+        let lines: Vec<_> = Self::usage_lines()
+            .iter()
+            .map(|line| format!("!{cmd} {line}").trim_end().to_owned())
+            .collect();
+
+        format!("usage: {}", lines.join(" or "))
+    }
+
+    /// e.g. ```usage: !lurk or !unlurk```
+    fn invocation_usage() -> String {
+        let lines: Vec<_> = Self::usage_lines()
+            .iter()
+            .map(|line| format!("!{line}"))
+            .collect();
+
+        format!("usage: {}", lines.join(" or "))
+    }
+}
+
+/// Command prefixes are configurable, but they're never part of a command name
+fn is_prefix(c: char) -> bool {
+    !c.is_alphanumeric() && c != '_'
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CommandArgs;
+
+    #[derive(Debug, PartialEq, CommandArgs)]
+    struct Pasta {
+        gnu: String,
+        linux: String,
+        count: Option<u32>,
+        #[arg(flag)]
+        loud: bool,
+    }
+
+    #[derive(Debug, PartialEq, CommandArgs)]
+    enum Kaomoji {
+        Random,
+        Index {
+            index: isize,
+        },
+        Add {
+            #[arg(rest)]
+            kaomoji: String,
+        },
+        #[arg(subcommand = "remove")]
+        RemoveLast,
+        #[arg(subcommand = "remove")]
+        RemoveIndex {
+            index: isize,
+        },
+        #[arg(subcommand)]
+        Timeout {
+            user: Mention,
+            duration: Duration,
+        },
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("18446744073709551615s1s"), None);
+        assert_eq!(parse_duration("99999999999999999h"), None);
+    }
+
+    #[test]
+    fn formatted_durations() {
+        assert_eq!(format_duration(Duration::from_millis(300)), "0.3s");
+        assert_eq!(format_duration(Duration::from_secs(40)), "40s");
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(192)), "3m12s");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(7500)), "2h5m");
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(
+            Pasta::parse("!mostlypasta --loud gnu linux").unwrap(),
+            Pasta {
+                gnu: "gnu".into(),
+                linux: "linux".into(),
+                count: None,
+                loud: true,
+            }
+        );
+        assert_eq!(Pasta::parse("!mostlypasta a b 3").unwrap().count, Some(3));
+
+        let err = Pasta::parse("!mostlypasta gnu").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "missing <linux>, usage: !mostlypasta <gnu> <linux> [count] [--loud]"
+        );
+
+        let err = Pasta::parse("!mostlypasta a b c").unwrap_err().to_string();
+        assert!(err.starts_with("\"c\" is not a valid <count> (expected a number)"));
+
+        let err = Pasta::parse("!mostlypasta a b 1 2")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("didn't expect \"2\""));
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(Kaomoji::parse("!uwu").unwrap(), Kaomoji::Random);
+        assert_eq!(
+            Kaomoji::parse("!uwu -1").unwrap(),
+            Kaomoji::Index { index: -1 }
+        );
+        assert_eq!(
+            Kaomoji::parse("!uwu (^_^) hi").unwrap(),
+            Kaomoji::Add {
+                kaomoji: "(^_^) hi".into()
+            }
+        );
+        assert_eq!(Kaomoji::parse("!uwu remove").unwrap(), Kaomoji::RemoveLast);
+        assert_eq!(
+            Kaomoji::parse("!uwu REMOVE 2").unwrap(),
+            Kaomoji::RemoveIndex { index: 2 }
+        );
+        assert_eq!(
+            Kaomoji::parse("!uwu timeout @someone 5m").unwrap(),
+            Kaomoji::Timeout {
+                user: Mention("someone".into()),
+                duration: Duration::from_secs(300)
+            }
+        );
+
+        let err = Kaomoji::parse("!uwu remove UwU").unwrap_err().to_string();
+        assert!(err.starts_with("didn't expect \"UwU\""), "{err}");
+
+        assert_eq!(
+            Kaomoji::usage("uwu"),
+            "usage: !uwu or !uwu <index> or !uwu <kaomoji...> or !uwu remove or \
+            !uwu remove <index> or !uwu timeout <user> <duration>"
+        );
+    }
+
+    #[derive(Debug, PartialEq, CommandArgs)]
+    enum LurkArgs {
+        #[arg(subcommand)]
+        Lurk,
+        #[arg(subcommand)]
+        Lurkwith {
+            #[arg(rest)]
+            status: String,
+        },
+        #[arg(subcommand)]
+        Lurker { user: Option<Mention> },
+        #[arg(subcommand)]
+        Unlurk {
+            #[arg(rest, ignore)]
+            chatter: (),
+        },
+    }
+
+    #[test]
+    fn invocations() {
+        assert_eq!(LurkArgs::parse_invocation("!lurk").unwrap(), LurkArgs::Lurk);
+        assert_eq!(
+            LurkArgs::parse_invocation("!lurkwith  touching grass ").unwrap(),
+            LurkArgs::Lurkwith {
+                status: "touching grass".into()
+            }
+        );
+        assert_eq!(
+            LurkArgs::parse_invocation("!lurker @mostlymaxi").unwrap(),
+            LurkArgs::Lurker {
+                user: Some(Mention("mostlymaxi".into()))
+            }
+        );
+        assert_eq!(
+            LurkArgs::parse_invocation("!unlurk finally done").unwrap(),
+            LurkArgs::Unlurk { chatter: () }
+        );
+        assert_eq!(
+            LurkArgs::invocation_usage(),
+            "usage: !lurk or !lurkwith <status...> or !lurker [user] or !unlurk"
+        );
+
+        let err = LurkArgs::parse_invocation("!lurkwith")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("missing <status>"), "{err}");
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{config::AuditConfig, events::EventBus};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Events ---------------------------------------------------------------------
+
+/// One command dispatch, whatever came of it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEvent {
+    /// Unix millis, like the ```received_at``` of chat messages
+    pub at: u64,
+    pub message_id: String,
+    pub user_id: String,
+    pub user_name: String,
+    /// The chatter's [`Role`](crate::Role) at the time
+    pub role: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// ```ok```, or what chat was told went wrong like ```command_cooldown```
+    pub outcome: String,
+    pub latency_ms: f64,
+    /// Every message the bot sent because of it, error replies included
+    pub reply_ids: Vec<String>,
+}
+
+impl AuditEvent {
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64)
+    }
+}
+
+// File -----------------------------------------------------------------------
+
+/// Appends lines to ```path```, moving it to ```path.1``` (and ```path.1``` to ```path.2```,
+/// ...) once it's grown past ```max_bytes```. Only ```keep``` old files are kept around
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn new(path: PathBuf, max_bytes: u64, keep: usize) -> Self {
+        Self {
+            path,
+            max_bytes,
+            keep,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.file.is_some() && self.size + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                self.size = file.metadata()?.len();
+                self.file.insert(file)
+            }
+        };
+
+        writeln!(file, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+// Log ------------------------------------------------------------------------
+
+/// Where [`AuditEvent`]s go: a rotating JSONL file in the data dir and, optionally, a
+/// franz topic. The default records nothing. Cheap to clone, clones share the file
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    file: Option<Rc<RefCell<RotatingFile>>>,
+    topic: Option<(EventBus, String)>,
+}
+
+impl AuditLog {
+    /// ```config.file``` is relative to ```data_dir```, the topic is published to through ```events```
+    pub fn new(config: &AuditConfig, data_dir: &Path, events: EventBus) -> Self {
+        let file = (!config.file.as_os_str().is_empty()).then(|| {
+            let path = data_dir.join(&config.file);
+            Rc::new(RefCell::new(RotatingFile::new(
+                path,
+                config.max_bytes,
+                config.keep,
+            )))
+        });
+        let topic = (!config.topic.is_empty()).then(|| (events, config.topic.clone()));
+
+        Self { file, topic }
+    }
+
+    /// Failing to record is logged, it never gets in the way of the command
+    pub fn record(&self, event: &AuditEvent) {
+        let line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!(target: "audit", error = %e, "unable to serialize audit event");
+                return;
+            }
+        };
+
+        if let Some(file) = &self.file {
+            if let Err(e) = file.borrow_mut().write_line(&line) {
+                tracing::error!(target: "audit", error = %e, "unable to write audit log");
+            }
+        }
+        if let Some((events, topic)) = &self.topic {
+            if let Err(e) = events.publish(topic, &line) {
+                tracing::warn!(target: "audit", %topic, error = %e, "unable to publish audit event");
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field(
+                "file",
+                &self.file.as_ref().map(|file| file.borrow().path.clone()),
+            )
+            .field("topic", &self.topic.as_ref().map(|(_, topic)| topic))
+            .finish()
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::MemoryEvents;
+
+    fn event(command: &str) -> AuditEvent {
+        AuditEvent {
+            at: 1_700_000_000_000,
+            message_id: "abc".to_owned(),
+            user_id: "123".to_owned(),
+            user_name: "chatter".to_owned(),
+            role: "everyone".to_owned(),
+            command: command.to_owned(),
+            args: vec!["a".to_owned()],
+            outcome: "ok".to_owned(),
+            latency_ms: 1.5,
+            reply_ids: vec!["r1".to_owned()],
+        }
+    }
+
+    #[test]
+    fn rotates() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("audit.jsonl");
+        let mut file = RotatingFile::new(path.clone(), 10, 2);
+        for line in ["one", "two", "three", "four"] {
+            file.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "four\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("audit.jsonl.1")).unwrap(),
+            "three\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("audit.jsonl.2")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(!dir.join("audit.jsonl.3").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_and_topic() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-audit-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let events = MemoryEvents::default();
+        let config = AuditConfig {
+            topic: "bot-audit".to_owned(),
+            ..AuditConfig::default()
+        };
+        let audit = AuditLog::new(&config, &dir, EventBus::new(events.clone()));
+        audit.record(&event("ping"));
+        audit.clone().record(&event("dice"));
+
+        let line = r#"{"at":1700000000000,"message_id":"abc","user_id":"123","user_name":"chatter","role":"everyone","command":"ping","args":["a"],"outcome":"ok","latency_ms":1.5,"reply_ids":["r1"]}"#;
+        let written = fs::read_to_string(dir.join("audit.jsonl")).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        assert_eq!(written.lines().next(), Some(line));
+        assert_eq!(
+            events.events()[0],
+            ("bot-audit".to_owned(), line.to_owned())
+        );
+
+        // nothing configured, nothing recorded
+        AuditLog::default().record(&event("ping"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
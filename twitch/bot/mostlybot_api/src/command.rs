@@ -1,12 +1,24 @@
 use crate::{
-    api::TwitchApiWrapper,
+    api::{Trigger, TwitchApiWrapper},
+    audit::AuditEvent,
+    config::{CommandConfig, Config},
+    context::{CommandContext, Services},
+    error::{CommandError, CommandResult},
+    ignore::{BotPolicy, Sender},
+    role::Role,
+    sent::SentMessage,
     spam::{RateLimit, Spam},
+    toggles::CommandToggle,
 };
+use rand::Rng;
 use std::{
-    cell::{Ref, RefCell, RefMut},
-    collections::HashMap,
+    any::Any,
+    cell::{Cell, Ref, RefCell, RefMut},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    panic::{self, AssertUnwindSafe},
     rc::Rc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tracing::instrument;
 use twitcheventsub::MessageData;
@@ -20,13 +32,43 @@ pub trait ChatCommand: 'static {
     where
         Self: Sized;
 
+    /// Franz topics the command publishes events to, anything else is refused by
+    /// [`CommandContext::publish`]
+    fn topics() -> Vec<String>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
     fn rate_limit(&self) -> RateLimit {
         RateLimit::new(1, Duration::from_millis(250))
     }
 
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()>;
+    /// The lowest role allowed to run the command
+    fn role(&self) -> Role {
+        Role::Everyone
+    }
+
+    /// Whether ```!undo``` may take back what the command said
+    fn undoable(&self) -> bool {
+        true
+    }
+
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult;
 
     fn help(&self) -> String;
+
+    /// Called once at startup, before any messages, to load whatever ```shutdown``` saved
+    fn restore(&mut self, _services: &Services) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called once when the bot stops, after the last message was handled, to save state
+    /// that should survive a restart
+    fn shutdown(&mut self, _services: &Services) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -37,31 +79,96 @@ pub enum CommandParseResult {
     ValidCommand(String, Vec<String>),
 }
 
+/// A command in the [`CommandMap`], clones (one per name) share everything
 #[derive(Clone)]
 pub struct Command {
+    /// the first of the command's names
+    name: Rc<str>,
     inner: Rc<RefCell<dyn ChatCommand>>,
+    /// what [`ChatCommand::topics`] declared
+    topics: Rc<[String]>,
+    /// from the ```[commands.<name>]``` section of the config
+    config: Rc<Cell<CommandConfig>>,
+    /// set by moderators in chat, wins over the config
+    toggle: Rc<Cell<CommandToggle>>,
+    /// how often the handler panicked or ran over budget since it was last disabled for it
+    panics: Rc<Cell<u32>>,
 }
 
 impl Command {
     const PREFIX: char = '!';
 
-    fn new(cmd: Rc<RefCell<dyn ChatCommand>>) -> Self {
-        Self { inner: cmd }
+    fn new(name: &str, cmd: Rc<RefCell<dyn ChatCommand>>, topics: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            inner: cmd,
+            topics: topics.into(),
+            config: Rc::default(),
+            toggle: Rc::default(),
+            panics: Rc::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn borrow(&self) -> Ref<dyn ChatCommand> {
+    /// The franz topics the command may publish to
+    pub fn topics(&self) -> &[String] {
+        &self.topics
+    }
+
+    pub fn borrow(&self) -> Ref<'_, dyn ChatCommand> {
         self.inner.borrow()
     }
 
-    pub fn borrow_mut(&self) -> RefMut<dyn ChatCommand> {
+    pub fn borrow_mut(&self) -> RefMut<'_, dyn ChatCommand> {
         self.inner.borrow_mut()
     }
 
+    /// Overrides from the ```[commands.<name>]``` section of the config
+    pub fn config(&self) -> CommandConfig {
+        self.config.get()
+    }
+
+    /// Overrides set by moderators with ```!cmd```
+    pub fn toggle(&self) -> CommandToggle {
+        self.toggle.get()
+    }
+
+    pub fn set_toggle(&self, toggle: CommandToggle) {
+        self.toggle.set(toggle);
+    }
+
+    /// How often the handler panicked or ran over budget, see
+    /// [`HandlersConfig::max_panics`](crate::HandlersConfig::max_panics)
+    pub fn panics(&self) -> u32 {
+        self.panics.get()
+    }
+
+    /// Disabled by either the config or a moderator
+    pub fn is_enabled(&self) -> bool {
+        self.config().enabled && !self.toggle().disabled
+    }
+
+    /// The command's own rate limit, unless a moderator or the config overrides its cooldown
+    pub fn rate_limit(&self) -> RateLimit {
+        match self.toggle().cooldown.or(self.config().cooldown) {
+            Some(cooldown) => RateLimit::new(1, cooldown),
+            None => self.borrow().rate_limit(),
+        }
+    }
+
     /// Parses the message to check if it's a command
     pub fn parse(message: &str) -> CommandParseResult {
+        Self::parse_with_prefixes(message, &[Self::PREFIX])
+    }
+
+    /// Same as [`Command::parse`] for when commands can start with any of ```prefixes```
+    pub fn parse_with_prefixes(message: &str, prefixes: &[char]) -> CommandParseResult {
         let trimmed_message = message.trim();
 
-        if trimmed_message.is_empty() || !trimmed_message.starts_with(Self::PREFIX) {
+        if trimmed_message.is_empty() || !trimmed_message.starts_with(prefixes) {
             return CommandParseResult::NotACommand;
         }
 
@@ -69,7 +176,7 @@ impl Command {
 
         if let Some(first_word) = words.next() {
             // Strip the prefix
-            let command_name = first_word.trim_start_matches(Self::PREFIX);
+            let command_name = first_word.trim_start_matches(prefixes);
 
             if command_name.is_empty() {
                 return CommandParseResult::InvalidCommand;
@@ -93,16 +200,16 @@ impl Command {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct CommandMap {
     inner: HashMap<String, Command>,
+    /// names that came from the ```[responses]``` section of the config
+    responses: HashSet<String>,
 }
 
-impl Default for CommandMap {
-    fn default() -> Self {
-        Self {
-            inner: HashMap::new(),
-        }
+impl fmt::Debug for CommandMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.inner.keys()).finish()
     }
 }
 
@@ -112,9 +219,108 @@ impl CommandMap {
     }
 
     pub fn insert<C: ChatCommand>(&mut self, cmd: C) {
-        let cmd = Rc::new(RefCell::new(cmd));
-        for name in C::names() {
-            self.inner.insert(name, Command::new(Rc::clone(&cmd) as _));
+        let names = C::names();
+        let Some(first) = names.first() else {
+            return;
+        };
+
+        let cmd = Command::new(first, Rc::new(RefCell::new(cmd)), C::topics());
+        for name in names {
+            self.inner.insert(name, cmd.clone());
+        }
+    }
+
+    /// Replaces the commands from the ```[responses]``` section of the config,
+    /// real commands with the same name are left alone
+    pub fn set_responses(&mut self, responses: &HashMap<String, String>) {
+        for name in self.responses.drain() {
+            self.inner.remove(&name);
+        }
+
+        for (name, text) in responses {
+            if self.inner.contains_key(name) {
+                tracing::warn!(command = %name, "response has the same name as a command");
+                continue;
+            }
+
+            let response = Rc::new(RefCell::new(Response { text: text.clone() }));
+            let cmd = Command::new(name, response, Vec::new());
+            self.inner.insert(name.clone(), cmd);
+            self.responses.insert(name.clone());
+        }
+    }
+
+    /// Whether the command is one of the ```[responses]``` from the config
+    pub fn is_response(&self, name: &str) -> bool {
+        self.responses.contains(name)
+    }
+
+    /// Every command once, under its first name
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.inner
+            .iter()
+            .filter(|(name, cmd)| name.as_str() == cmd.name())
+            .map(|(_, cmd)| cmd)
+    }
+
+    /// Every topic any command publishes to
+    pub fn topics(&self) -> BTreeSet<&str> {
+        self.commands()
+            .flat_map(|cmd| cmd.topics())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Runs every command's [`ChatCommand::restore`], a command that fails starts fresh
+    pub fn restore(&self, services: &Services) {
+        for cmd in self.commands() {
+            if let Err(e) = cmd.borrow_mut().restore(services) {
+                tracing::error!(command = %cmd.name(), "unable to restore state: {:#}", e);
+            }
+        }
+    }
+
+    /// Runs every command's [`ChatCommand::shutdown`], one failing doesn't stop the rest
+    pub fn shutdown(&self, services: &Services) {
+        for cmd in self.commands() {
+            if let Err(e) = cmd.borrow_mut().shutdown(services) {
+                tracing::error!(command = %cmd.name(), "unable to save state: {:#}", e);
+            }
+        }
+    }
+
+    /// Moderator overrides that differ from the defaults, by command name
+    pub fn toggles(&self) -> BTreeMap<String, CommandToggle> {
+        self.commands()
+            .filter(|cmd| cmd.toggle() != CommandToggle::default())
+            .map(|cmd| (cmd.name().to_owned(), cmd.toggle()))
+            .collect()
+    }
+
+    /// Restores moderator overrides, usually the ones saved with [`save_toggles`]
+    ///
+    /// [`save_toggles`]: crate::save_toggles
+    pub fn apply_toggles(&self, toggles: &BTreeMap<String, CommandToggle>) {
+        for (name, toggle) in toggles {
+            match self.get(name) {
+                Some(cmd) => cmd.set_toggle(*toggle),
+                None => tracing::warn!(command = %name, "toggle for a command that doesn't exist"),
+            }
+        }
+    }
+
+    /// Applies the ```[commands]``` section of the config, commands that aren't
+    /// mentioned go back to their defaults
+    pub fn configure(&self, configs: &HashMap<String, CommandConfig>) {
+        for cmd in self.inner.values() {
+            cmd.config.set(CommandConfig::default());
+        }
+
+        for (name, config) in configs {
+            match self.get(name) {
+                Some(cmd) => cmd.config.set(*config),
+                None => tracing::warn!(command = %name, "config for a command that doesn't exist"),
+            }
         }
     }
 
@@ -125,6 +331,73 @@ impl CommandMap {
     pub fn get_mut(&mut self, key: &str) -> Option<&mut Command> {
         self.inner.get_mut(key)
     }
+
+    /// Every command registered with ```#[command]``` or ```register_command!```
+    pub fn registered() -> Self {
+        let mut map = Self::new();
+        for registration in inventory::iter::<CommandRegistration> {
+            (registration.insert)(&mut map);
+        }
+        map
+    }
+}
+
+/// A command from the ```[responses]``` section of the config, it's named by the
+/// config so it's never inserted with [`CommandMap::insert`]
+struct Response {
+    text: String,
+}
+
+impl ChatCommand for Response {
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+        }
+    }
+
+    fn names() -> Vec<String> {
+        Vec::new()
+    }
+
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        api.reply(ctx, self.text.clone())
+    }
+
+    fn help(&self) -> String {
+        format!("replies with \"{}\"", self.text)
+    }
+}
+
+// Registry -------------------------------------------------------------------
+
+/// A command that gets inserted into [`CommandMap::registered`], you shouldn't need
+/// to build these yourself: ```#[command]``` and ```register_command!``` do it for you.
+pub struct CommandRegistration {
+    insert: fn(&mut CommandMap),
+}
+
+impl CommandRegistration {
+    pub const fn new<C: ChatCommand>() -> Self {
+        Self {
+            insert: |map| map.insert(C::new()),
+        }
+    }
+}
+
+inventory::collect!(CommandRegistration);
+
+/// Registers a command that implements ```ChatCommand``` by hand
+///
+/// ```ignore
+/// register_command!(MostlyStatus);
+/// ```
+#[macro_export]
+macro_rules! register_command {
+    ($cmd:ty) => {
+        $crate::inventory::submit! {
+            $crate::CommandRegistration::new::<$cmd>()
+        }
+    };
 }
 
 // ----------------------------------------------------------------------------
@@ -136,16 +409,83 @@ enum ChatErrorKind {
     CommandCooldown(String, Duration),
     // CommandSentByBot(String),
     CommandDoesNotExist(String),
-    HandleError(String, String),
+    CommandDisabled(String),
+    CommandUsage(String),
+    CommandFailed(String),
+    PermissionDenied(String),
+    RoleRequired(String, Role),
+    CommandUnavailable(String),
+    InternalError(String, String),
+}
+
+impl ChatErrorKind {
+    /// Name for metrics and logs
+    fn label(&self) -> &'static str {
+        match self {
+            Self::NotACommand => "not_a_command",
+            Self::InvalidCommand => "invalid_command",
+            Self::SpamDetected => "spam_detected",
+            Self::CommandCooldown(..) => "command_cooldown",
+            Self::CommandDoesNotExist(_) => "command_does_not_exist",
+            Self::CommandDisabled(_) => "command_disabled",
+            Self::CommandUsage(_) => "command_usage",
+            Self::CommandFailed(_) => "command_failed",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::RoleRequired(..) => "role_required",
+            Self::CommandUnavailable(_) => "command_unavailable",
+            Self::InternalError(..) => "internal_error",
+        }
+    }
+
+    /// Turns a command's error into what chat gets to see, logging anything
+    /// that shouldn't be shown to chat
+    fn from_command_error(
+        cmd_name: String,
+        help: String,
+        err: CommandError,
+        services: &Services,
+    ) -> Self {
+        match err {
+            CommandError::Usage => Self::CommandUsage(help),
+            CommandError::UserFacing(msg) => Self::CommandFailed(msg),
+            CommandError::PermissionDenied => Self::PermissionDenied(cmd_name),
+            CommandError::Transient(err) => {
+                services.stats.error();
+                tracing::warn!(command = %cmd_name, error = %err, "transient command error");
+                Self::CommandUnavailable(cmd_name)
+            }
+            CommandError::Internal(err) => {
+                services.stats.error();
+                // not from ```services.rng```, so a seeded run isn't thrown off by errors and
+                // ids stay unique across runs
+                let error_id = format!("{:08x}", rand::thread_rng().gen::<u32>());
+                tracing::error!(
+                    command = %cmd_name,
+                    %error_id,
+                    error = ?err,
+                    "internal command error"
+                );
+                Self::InternalError(cmd_name, error_id)
+            }
+        }
+    }
 }
 
 /// Sends a message to the chat on command error
 fn send_chat_err_msg(
     api: &mut TwitchApiWrapper,
     spam: &mut Spam,
+    services: &Services,
     ctx: &MessageData,
     error: ChatErrorKind,
 ) {
+    services.metrics.chat_error(error.label());
+    match error {
+        ChatErrorKind::SpamDetected => services.metrics.cooldown_rejected("user"),
+        ChatErrorKind::CommandCooldown(..) => services.metrics.cooldown_rejected("command"),
+        _ => {}
+    }
+
     // Comment this to disable failed command spam handling
     if let Some(cooldown) = spam.check_failed_command_cooldown(&ctx.chatter.id) {
         tracing::warn!(
@@ -176,13 +516,33 @@ fn send_chat_err_msg(
         ChatErrorKind::CommandDoesNotExist(cmd_name) => {
             format!("\"{}\" does not exist", cmd_name)
         }
-        ChatErrorKind::HandleError(cmd_name, err) => {
-            format!("\"{}\" command handle error: {}", cmd_name, err)
+        ChatErrorKind::CommandDisabled(cmd_name) => {
+            format!("\"{}\" is disabled right now", cmd_name)
+        }
+        ChatErrorKind::CommandUsage(help) => help,
+        ChatErrorKind::CommandFailed(msg) => msg,
+        ChatErrorKind::PermissionDenied(cmd_name) => {
+            format!("you don't have permission to do that with \"{}\"", cmd_name)
+        }
+        ChatErrorKind::RoleRequired(cmd_name, role) => {
+            format!("\"{}\" can only be used by {}s", cmd_name, role)
+        }
+        ChatErrorKind::CommandUnavailable(cmd_name) => {
+            format!(
+                "\"{}\" is having trouble right now, try again later",
+                cmd_name
+            )
+        }
+        ChatErrorKind::InternalError(cmd_name, error_id) => {
+            format!(
+                "\"{}\" ran into an internal error (id: {})",
+                cmd_name, error_id
+            )
         }
     };
 
     // additional logging for test mode
-    let msg = if let TwitchApiWrapper::Test(_) = api {
+    let msg = if api.as_mock().is_some() {
         format!(
             "@{}, id: {}, msg: {msg}, raw: \"{}\"",
             ctx.chatter.name, ctx.chatter.id, ctx.message.text
@@ -195,70 +555,221 @@ fn send_chat_err_msg(
 }
 
 /// Handles incoming chat commands if applicable (validity checks, etc...)
-#[instrument(skip(api, ctx, cmds, spam))]
+#[instrument(skip(api, ctx, cmds, config, spam, services))]
 pub fn handle_command_if_applicable(
     ctx: &MessageData,
     api: &mut TwitchApiWrapper,
     cmds: &mut CommandMap,
-    bot_id: &str,
+    config: &Config,
     spam: &mut Spam,
+    services: &Services,
 ) {
     // Ignore commands sent by the bot itself
-    if ctx.chatter.id == bot_id {
+    if ctx.chatter.id == config.twitch.bot_id {
         return;
     }
 
-    // Parse the command from the message
-    let (cmd_name, _args) = match Command::parse(&ctx.message.text) {
-        CommandParseResult::NotACommand => {
-            if let TwitchApiWrapper::Test(_) = api {
-                send_chat_err_msg(api, spam, ctx, ChatErrorKind::NotACommand);
-            }
+    let sender = services.ignore.sender(ctx, &config.ignore);
+    api.handling(Trigger::Chat(ctx));
+
+    // Every message but the ones from other bots goes through the chat filters, whether or
+    // not it's a command
+    if sender != Sender::Bot
+        && services
+            .moderation
+            .moderate(ctx, &config.moderation, api, &services.metrics)
+    {
+        return;
+    }
+
+    // Ignored chatters and other bots never get to run a command
+    let policy = config.ignore.bot_messages;
+    let quiet = match sender {
+        Sender::Ignored => {
+            tracing::debug!(chatter = %ctx.chatter.login, "ignored chatter");
             return;
         }
-        CommandParseResult::InvalidCommand => {
-            send_chat_err_msg(api, spam, ctx, ChatErrorKind::InvalidCommand);
+        Sender::Bot if policy == BotPolicy::Ignore => {
+            tracing::debug!(chatter = %ctx.chatter.login, "ignored bot");
             return;
         }
-        CommandParseResult::ValidCommand(cmd_name, args) => (cmd_name, args),
+        Sender::Bot => policy == BotPolicy::Quiet,
+        Sender::Chatter => false,
+    };
+
+    // Parse the command from the message
+    let (cmd_name, args) =
+        match Command::parse_with_prefixes(&ctx.message.text, &config.chat.prefixes) {
+            CommandParseResult::NotACommand => {
+                if api.as_mock().is_some() && !quiet {
+                    send_chat_err_msg(api, spam, services, ctx, ChatErrorKind::NotACommand);
+                }
+                return;
+            }
+            CommandParseResult::InvalidCommand => {
+                if !quiet {
+                    send_chat_err_msg(api, spam, services, ctx, ChatErrorKind::InvalidCommand);
+                }
+                return;
+            }
+            CommandParseResult::ValidCommand(cmd_name, args) => (cmd_name, args),
+        };
+
+    let started = Instant::now();
+    let outcome = dispatch(ctx, api, cmds, config, spam, services, cmd_name.clone());
+    let took = started.elapsed();
+
+    let outcome_label = match &outcome {
+        Ok(()) => "ok",
+        Err(error) => error.label(),
     };
+    if let Err(error) = outcome {
+        if !quiet {
+            send_chat_err_msg(api, spam, services, ctx, error);
+        }
+    }
+
+    let undoable = cmds
+        .get(&cmd_name)
+        .is_none_or(|cmd| cmd.borrow().undoable());
+    for id in api.sent_ids().iter().filter(|_| undoable) {
+        services.sent.record(SentMessage {
+            id: id.clone(),
+            user_id: ctx.chatter.id.clone(),
+            user_login: ctx.chatter.login.clone(),
+            command: cmd_name.clone(),
+        });
+    }
+
+    services.audit.record(&AuditEvent {
+        at: AuditEvent::now(),
+        message_id: ctx.message_id.clone(),
+        user_id: ctx.chatter.id.clone(),
+        user_name: ctx.chatter.name.clone(),
+        role: Role::of(ctx).to_string(),
+        command: cmd_name,
+        args,
+        outcome: outcome_label.to_owned(),
+        latency_ms: took.as_secs_f64() * 1000.0,
+        reply_ids: api.sent_ids().to_vec(),
+    });
+}
+
+/// Counts the panic and disables the command once it's panicked too often, the panic itself
+/// becomes an internal error for chat
+fn panicked(
+    command: &Command,
+    panic: Box<dyn Any + Send>,
+    config: &Config,
+    services: &Services,
+) -> CommandError {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no message".to_owned());
+
+    services.metrics.command_panicked(command.name());
+    strike(command, config);
+
+    CommandError::Internal(anyhow::anyhow!("panicked: {message}"))
+}
+
+/// Counts a panic or an over-budget run and disables the command once it's had too many
+fn strike(command: &Command, config: &Config) {
+    let panics = command.panics.get() + 1;
+    command.panics.set(panics);
+
+    let max_panics = config.handlers.max_panics;
+    if max_panics > 0 && panics >= max_panics {
+        command.set_toggle(CommandToggle {
+            disabled: true,
+            ..command.toggle()
+        });
+        command.panics.set(0);
+        tracing::error!(
+            command = %command.name(),
+            panics,
+            "command disabled after panicking or running over budget too often, !cmd enable turns it back on"
+        );
+    }
+}
+
+/// Runs the command unless something stands in its way, in which case that's what chat
+/// should be told
+fn dispatch(
+    ctx: &MessageData,
+    api: &mut TwitchApiWrapper,
+    cmds: &CommandMap,
+    config: &Config,
+    spam: &mut Spam,
+    services: &Services,
+    cmd_name: String,
+) -> Result<(), ChatErrorKind> {
+    // Disabled commands are turned away before they count towards any cooldown
+    if cmds.get(&cmd_name).is_some_and(|cmd| !cmd.is_enabled()) {
+        return Err(ChatErrorKind::CommandDisabled(cmd_name));
+    }
 
     // Check if the user is sending commands too quickly
-    if let Some(_) = spam.check_user_command_cooldown(&ctx.chatter.id) {
-        send_chat_err_msg(api, spam, ctx, ChatErrorKind::SpamDetected);
-        return;
+    if spam.check_user_command_cooldown(&ctx.chatter.id).is_some() {
+        return Err(ChatErrorKind::SpamDetected);
     }
 
     // Check if the command exists and handle it
-    let Some(cmd) = cmds.get_mut(&cmd_name) else {
-        send_chat_err_msg(
-            api,
-            spam,
-            ctx,
-            ChatErrorKind::CommandDoesNotExist(cmd_name.clone()),
-        );
-        return;
+    let Some(command) = cmds.get(&cmd_name) else {
+        return Err(ChatErrorKind::CommandDoesNotExist(cmd_name));
     };
 
-    let mut cmd = cmd.borrow_mut();
+    let rate_limit = command.rate_limit();
+    let topics = command.topics.clone();
+    let name = command.name().to_owned();
+    let mut cmd = command.borrow_mut();
+
+    // Check if the chatter is allowed to use the command
+    let role = cmd.role();
+    if Role::of(ctx) < role {
+        return Err(ChatErrorKind::RoleRequired(cmd_name, role));
+    }
 
     // Check if the command is under cooldown
-    if let Some(duration) = spam.check_global_command_cooldown(&cmd_name, Some(&cmd.rate_limit())) {
-        send_chat_err_msg(
-            api,
-            spam,
-            ctx,
-            ChatErrorKind::CommandCooldown(cmd_name, duration),
-        );
-        return;
+    if let Some(duration) = spam.check_global_command_cooldown(&cmd_name, Some(&rate_limit)) {
+        return Err(ChatErrorKind::CommandCooldown(cmd_name, duration));
     }
 
-    if let Err(err) = cmd.handle(api, ctx) {
-        send_chat_err_msg(
-            api,
-            spam,
-            ctx,
-            ChatErrorKind::HandleError(cmd_name.clone(), err.to_string()),
+    services
+        .usage
+        .record(&name, &ctx.chatter.id, &ctx.chatter.name, AuditEvent::now());
+
+    // the map as it is now, reloads add and remove responses
+    let cmd_ctx = CommandContext::with_services(ctx, services)
+        .with_commands(cmds)
+        .publishing_to(topics);
+
+    // a panicking command only takes itself down, not the bot
+    let started = Instant::now();
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| cmd.handle(api, &cmd_ctx)));
+    let took = started.elapsed();
+    let did_panic = outcome.is_err();
+    let result = outcome.unwrap_or_else(|panic| Err(panicked(command, panic, config, services)));
+    services
+        .metrics
+        .command_handled(&cmd_name, result.is_ok(), took);
+
+    let budget = config.handlers.budget;
+    if !budget.is_zero() && took > budget {
+        services.metrics.command_over_budget(&name);
+        tracing::warn!(
+            command = %name,
+            took_ms = took.as_millis() as u64,
+            budget_ms = budget.as_millis() as u64,
+            "command took longer than its time budget"
         );
+        // a slow panic is only one strike
+        if !did_panic {
+            strike(command, config);
+        }
     }
+
+    result.map_err(|err| ChatErrorKind::from_command_error(cmd_name, cmd.help(), err, services))
 }
@@ -0,0 +1,988 @@
+use crate::{
+    args::parse_duration, command::CommandMap, ignore::BotPolicy, moderation::FilterAction,
+    outbox::OutboxKind, role::Role, spam::RateLimit,
+};
+use anyhow::Context;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Everything that can be tuned without touching code, loaded from ```mostlybot.toml```.
+///
+/// Every field has a default so the file only needs what differs, and the usual
+/// environment variables (```FRANZ_BROKER```, ```TWITCH_BOT_ID```, ...) override the file.
+///
+/// ```toml
+/// data_dir = "data"
+///
+/// [franz]
+/// broker = "tits.franz.mostlymaxi.com:8085"
+/// topics = { chat = "chat", outbox = "bot-outbox", outbox_results = "bot-outbox-results" }
+/// reconnect = { min = "500ms", max = "30s" }
+///
+/// [twitch]
+/// bot_id = "1124612654"
+///
+/// [chat]
+/// prefixes = ["!"]
+/// max_age = "2m"
+///
+/// [spam]
+/// user = { max = 1, per = "5s" }
+///
+/// [ignore]
+/// users = ["some_troll"]
+/// bots = ["our_other_bot"]
+/// bot_messages = "quiet"
+///
+/// [moderation]
+/// enabled = true
+/// actions = [{ action = "delete" }, { action = "timeout", duration = "10m" }]
+/// links = { allowed = ["twitch.tv", "github.com"], role = "subscriber" }
+/// words = { banned = ["heck"], patterns = ["(?i)free\\s+followers"] }
+/// flood = { window = "30s", repeats = 3, copypasta = 5 }
+///
+/// [output]
+/// blocked = ["heck"]
+/// max_echo = 100
+///
+/// [commands.help]
+/// cooldown = "3s"
+///
+/// [commands.status]
+/// enabled = false
+///
+/// [handlers]
+/// budget = "2s"
+/// max_panics = 3
+///
+/// [responses]
+/// discord = "join the SPARCL discord: https://discord.gg/aMAAbZy4QD"
+///
+/// [dry_run]
+/// enabled = true
+/// topic = "mostlybot-dry-run"
+///
+/// [http]
+/// addr = "0.0.0.0:8080"
+///
+/// [outbox]
+/// rate = { max = 20, per = "1m" }
+///
+/// [outbox.services.points]
+/// actions = ["say", "timeout"]
+///
+/// [audit]
+/// file = "audit.jsonl"
+/// topic = "bot-audit"
+/// ```
+///
+/// Everything but ```data_dir```, ```[franz]```, ```[http]```, ```[dry_run]```, ```[audit]``` and
+/// ```moderation.log``` can be reloaded while the bot is running.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where commands and the bot keep files between restarts (the working directory by default)
+    pub data_dir: PathBuf,
+    pub franz: FranzConfig,
+    pub twitch: TwitchConfig,
+    pub chat: ChatConfig,
+    pub spam: SpamConfig,
+    pub ignore: IgnoreConfig,
+    pub moderation: ModerationConfig,
+    pub output: OutputConfig,
+    /// Per-command overrides, keyed by any of the command's names
+    pub commands: HashMap<String, CommandConfig>,
+    pub handlers: HandlersConfig,
+    /// Commands that just reply with some text, ```!name``` -> ```text```
+    pub responses: HashMap<String, String>,
+    pub dry_run: DryRunConfig,
+    pub http: HttpConfig,
+    pub outbox: OutboxConfig,
+    pub audit: AuditConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FranzConfig {
+    pub broker: String,
+    pub topics: TopicsConfig,
+    pub reconnect: ReconnectConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopicsConfig {
+    /// Twitch chat messages to handle commands from
+    pub chat: String,
+    /// Actions other services want the bot to take, see [`OutboxConfig`]. Empty to not read it
+    pub outbox: String,
+    /// Where the outcome of every outbox action goes, empty to not publish them
+    pub outbox_results: String,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            chat: "chat".to_owned(),
+            outbox: "bot-outbox".to_owned(),
+            outbox_results: "bot-outbox-results".to_owned(),
+        }
+    }
+}
+
+/// How long to wait between attempts when the broker goes away, doubling from ```min``` up to
+/// ```max``` with some jitter so a restarted broker isn't hit by everyone at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectConfig {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min: Duration,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max: Duration,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The broadcaster isn't set here, every chat message says whose channel it's from
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TwitchConfig {
+    /// Messages from this chatter are ignored so the bot doesn't talk to itself
+    pub bot_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// Characters that start a command, ```!``` in ```!ping```
+    pub prefixes: Vec<char>,
+    /// Messages older than this are skipped instead of handled, so catching up after a
+    /// restart doesn't answer a backlog of commands. ```"0s"``` handles everything
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_age: Duration,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            prefixes: vec!['!'],
+            max_age: Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpamConfig {
+    /// Any command, per chatter
+    pub user: RateLimit,
+    /// Any single command, by everyone, when the command doesn't set its own limit
+    pub command: RateLimit,
+    /// Error replies, per chatter
+    pub failed_command: RateLimit,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            // max any 1 bot command per-user every 5 seconds
+            user: RateLimit::new(1, Duration::from_secs(5)),
+            // max any 1 bot command by any user every 5 seconds
+            // use any sensible defaults, will be overridden by the user provided value
+            command: RateLimit::new(1, Duration::from_secs(5)),
+            // max any 2 failed command messages in chat, per-user, every 30 seconds
+            failed_command: RateLimit::new(2, Duration::from_secs(30)),
+        }
+    }
+}
+
+/// Chatters whose messages never reach a command. Moderators can add more with ```!ignore```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IgnoreConfig {
+    /// Logins to ignore, on top of the ones moderators added
+    pub users: Vec<String>,
+    /// Logins of other bots in the channel
+    pub bots: Vec<String>,
+    /// Also count well known bots like Nightbot and StreamElements as bots, see
+    /// [`KNOWN_BOTS`](crate::KNOWN_BOTS)
+    pub known_bots: bool,
+    /// What to do with messages from bots
+    pub bot_messages: BotPolicy,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        Self {
+            users: Vec::new(),
+            bots: Vec::new(),
+            known_bots: true,
+            bot_messages: BotPolicy::Ignore,
+        }
+    }
+}
+
+/// The filters every chat message goes through, see [`Moderation`](crate::Moderation).
+/// Moderators and the broadcaster are never filtered
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    pub enabled: bool,
+    /// Chatters with this role or higher aren't filtered either
+    pub exempt: Role,
+    /// What happens on a chatter's first offence, their second, ... the last one repeats
+    pub actions: Vec<FilterAction>,
+    /// Offences older than this don't count towards the next action
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub forget: Duration,
+    pub links: LinkFilterConfig,
+    pub words: WordFilterConfig,
+    pub zalgo: ZalgoFilterConfig,
+    /// Uppercase letters out of all letters
+    pub caps: RatioFilterConfig,
+    /// Anything that isn't a letter or digit out of everything but spaces
+    pub symbols: RatioFilterConfig,
+    pub flood: FloodConfig,
+    /// Every filtered message is written to this JSONL file in ```data_dir```, empty to not
+    /// write one
+    pub log: PathBuf,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            exempt: Role::Moderator,
+            actions: vec![
+                FilterAction::Warn,
+                FilterAction::Delete,
+                FilterAction::Timeout {
+                    duration: Duration::from_secs(10 * 60),
+                },
+            ],
+            forget: Duration::from_secs(60 * 60),
+            links: LinkFilterConfig::default(),
+            words: WordFilterConfig::default(),
+            zalgo: ZalgoFilterConfig::default(),
+            caps: RatioFilterConfig::default(),
+            symbols: RatioFilterConfig::default(),
+            flood: FloodConfig::default(),
+            log: PathBuf::from("moderation.jsonl"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkFilterConfig {
+    pub enabled: bool,
+    /// Domains anyone may link to, subdomains included
+    pub allowed: Vec<String>,
+    /// Chatters with this role or higher may post any link
+    pub role: Role,
+    /// How long a ```!permit``` lasts
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub permit: Duration,
+}
+
+impl Default for LinkFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed: vec!["twitch.tv".to_owned()],
+            role: Role::Vip,
+            permit: Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WordFilterConfig {
+    /// Whole words, in any case
+    pub banned: Vec<String>,
+    /// Regexes matched against the whole message, ```(?i)``` makes them ignore case
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZalgoFilterConfig {
+    pub enabled: bool,
+    /// Combining marks stacked on one character, and invisible characters in one message
+    pub max_marks: usize,
+}
+
+impl Default for ZalgoFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_marks: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RatioFilterConfig {
+    pub enabled: bool,
+    /// Messages with fewer characters that count are left alone, so ```LUL``` is fine
+    pub min_length: usize,
+    /// Between 0 and 1, more than this is too much
+    pub max_ratio: f64,
+}
+
+impl Default for RatioFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_length: 12,
+            max_ratio: 0.7,
+        }
+    }
+}
+
+/// Spam that's only spam because of the messages around it
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FloodConfig {
+    pub enabled: bool,
+    /// How far back messages are compared
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub window: Duration,
+    /// Shorter messages (```W```, ```LUL```, ...) are never repeats or copypasta
+    pub min_length: usize,
+    /// How alike two messages have to be to count as the same, between 0 and 1
+    pub similarity: f64,
+    /// The same message this many times from one chatter, 0 to allow any
+    pub repeats: usize,
+    /// The same message from this many chatters, 0 to allow any
+    pub copypasta: usize,
+    /// Messages per chatter
+    pub burst: RateLimit,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window: Duration::from_secs(30),
+            min_length: 10,
+            similarity: 0.8,
+            repeats: 3,
+            copypasta: 5,
+            burst: RateLimit::new(6, Duration::from_secs(10)),
+        }
+    }
+}
+
+/// What the bot won't say, whoever asked. Every message it sends is checked, see
+/// [`Sanitizer`](crate::Sanitizer)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Whole words or phrases, in any case and ignoring punctuation (```"shut up"``` also
+    /// catches ```"Shut-up!"```). Chat gets a polite no instead of a message with any of them
+    pub blocked: Vec<String>,
+    /// Removes links a chatter got the bot to repeat, unless they're to a
+    /// ```moderation.links.allowed``` domain. The bot's own links are left alone
+    pub strip_links: bool,
+    /// Drops a leading ```/``` or ```.``` so chat can't make the bot run ```/ban``` and friends
+    pub neutralize_commands: bool,
+    /// The most characters of a chatter's message the bot repeats in one go, 0 for no limit
+    pub max_echo: usize,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            blocked: Vec::new(),
+            strip_links: true,
+            neutralize_commands: true,
+            max_echo: 200,
+        }
+    }
+}
+
+/// Runs the bot without posting anything, what it would have done is logged instead
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DryRunConfig {
+    pub enabled: bool,
+    /// Also publish everything to this franz topic, empty to only log it
+    pub topic: String,
+}
+
+/// Where ```/healthz``` and ```/metrics``` are served
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Empty to not serve them at all
+    pub addr: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            addr: "0.0.0.0:8080".to_owned(),
+        }
+    }
+}
+
+/// Which services may act in chat through the bot's outbox topic, and how often. A request
+/// from a service that isn't listed here is rejected
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    /// Per service, unless the service sets its own
+    pub rate: RateLimit,
+    /// Keyed by the name services put in their requests. Nothing checks that a request really
+    /// comes from the service it names, only who can write to the topic limits that
+    pub services: HashMap<String, OutboxServiceConfig>,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            rate: RateLimit::new(20, Duration::from_secs(60)),
+            services: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutboxServiceConfig {
+    /// What the service may do: ```say```, ```reply```, ```announce```, ```timeout```, ```delete```
+    pub actions: Vec<OutboxKind>,
+    #[serde(default)]
+    pub rate: Option<RateLimit>,
+}
+
+/// Where every command dispatch is recorded, see [`AuditLog`](crate::AuditLog)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// JSONL file in ```data_dir```, empty to not write one
+    pub file: PathBuf,
+    /// The file is rotated to ```file.1```, ```file.2```, ... once it's this big
+    pub max_bytes: u64,
+    /// How many rotated files to keep
+    pub keep: usize,
+    /// Also publish every event to this franz topic, empty to not publish them
+    pub topic: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: PathBuf::from("audit.jsonl"),
+            max_bytes: 10 * 1024 * 1024,
+            keep: 5,
+            topic: String::new(),
+        }
+    }
+}
+
+/// What every command handler is held to, see the rules in ```ping.rs```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HandlersConfig {
+    /// Handlers that take longer are logged and counted like a panic towards
+    /// ```max_panics```, ```"0s"``` for no budget. It's not enforced: a handler can't be stopped
+    /// halfway, so this is only checked once it returns and one that hangs is never caught
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub budget: Duration,
+    /// A command that panics or runs over budget this many times is disabled until a moderator
+    /// turns it back on with ```!cmd enable```, 0 to never disable it
+    pub max_panics: u32,
+}
+
+impl Default for HandlersConfig {
+    fn default() -> Self {
+        Self {
+            budget: Duration::from_secs(2),
+            max_panics: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandConfig {
+    pub enabled: bool,
+    /// Replaces the command's own rate limit with one use per ```cooldown```
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub cooldown: Option<Duration>,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cooldown: None,
+        }
+    }
+}
+
+impl Config {
+    /// Where the config is read from unless ```MOSTLYBOT_CONFIG``` says otherwise
+    pub const DEFAULT_PATH: &'static str = "mostlybot.toml";
+
+    /// Path from ```MOSTLYBOT_CONFIG```, or [`Config::DEFAULT_PATH`]
+    pub fn path() -> PathBuf {
+        std::env::var_os("MOSTLYBOT_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(Self::DEFAULT_PATH))
+    }
+
+    /// Reads the config file (if there is one) and applies environment overrides
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut config = if path.exists() {
+            let toml = std::fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            Self::from_toml(&toml).with_context(|| format!("parsing {}", path.display()))?
+        } else {
+            tracing::warn!("no config at {}, using defaults", path.display());
+            Self::default()
+        };
+
+        config.apply_env(|key| std::env::var(key).ok());
+        Ok(config)
+    }
+
+    pub fn from_toml(toml: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Overrides values with whatever ```var``` returns for the matching environment variable
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(data_dir) = var("MOSTLYBOT_DATA_DIR") {
+            self.data_dir = data_dir.into();
+        }
+        if let Some(broker) = var("FRANZ_BROKER") {
+            self.franz.broker = broker;
+        }
+        if let Some(topic) = var("MOSTLYBOT_CHAT_TOPIC") {
+            self.franz.topics.chat = topic;
+        }
+        if let Some(bot_id) = var("TWITCH_BOT_ID") {
+            self.twitch.bot_id = bot_id;
+        }
+        if let Some(prefixes) = var("MOSTLYBOT_PREFIXES") {
+            self.chat.prefixes = prefixes.chars().collect();
+        }
+        // fly and most other hosts say which port to listen on
+        if let Some(port) = var("PORT") {
+            self.http.addr = format!("0.0.0.0:{port}");
+        }
+        if let Some(dry_run) = var("MOSTLYBOT_DRY_RUN") {
+            self.dry_run.enabled = !matches!(dry_run.as_str(), "" | "0" | "false");
+        }
+    }
+
+    /// Everything wrong with the config, empty if it's good to go
+    pub fn validate(&self, cmds: &CommandMap) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.franz.broker.is_empty() {
+            problems.push("franz.broker is not set (or FRANZ_BROKER)".to_owned());
+        }
+        if self.franz.topics.chat.is_empty() {
+            problems.push("franz.topics.chat is empty".to_owned());
+        }
+        let reconnect = self.franz.reconnect;
+        if reconnect.min.is_zero() || reconnect.min > reconnect.max {
+            problems.push(format!(
+                "franz.reconnect: min ({:?}) has to be more than zero and at most max ({:?})",
+                reconnect.min, reconnect.max
+            ));
+        }
+        if !self.franz.topics.outbox.is_empty()
+            && self.franz.topics.outbox == self.franz.topics.chat
+        {
+            problems.push("franz.topics.outbox can't be the chat topic".to_owned());
+        }
+        if self.audit.max_bytes == 0 {
+            problems.push("audit.max_bytes has to be more than zero".to_owned());
+        }
+        if !self.audit.topic.is_empty() && self.audit.topic == self.franz.topics.chat {
+            problems.push("audit.topic can't be the chat topic".to_owned());
+        }
+        if !self.http.addr.is_empty() && self.http.addr.parse::<std::net::SocketAddr>().is_err() {
+            problems.push(format!(
+                "http.addr: {:?} is not an address like \"0.0.0.0:8080\"",
+                self.http.addr
+            ));
+        }
+        if self.twitch.bot_id.is_empty() {
+            problems.push("twitch.bot_id is not set (or TWITCH_BOT_ID)".to_owned());
+        }
+
+        if self.chat.prefixes.is_empty() {
+            problems.push("chat.prefixes is empty, nothing would be a command".to_owned());
+        }
+        for prefix in &self.chat.prefixes {
+            if prefix.is_alphanumeric() || prefix.is_whitespace() || *prefix == '_' {
+                problems.push(format!("chat.prefixes: {prefix:?} can't start a command"));
+            }
+        }
+
+        let mut names: Vec<_> = self.commands.keys().collect();
+        names.sort();
+        for name in names {
+            let is_command = cmds.get(name).is_some() && !cmds.is_response(name);
+            if !is_command && !self.responses.contains_key(name) {
+                problems.push(format!("commands.{name}: no such command"));
+            }
+        }
+
+        let mut responses: Vec<_> = self.responses.iter().collect();
+        responses.sort();
+        for (name, text) in responses {
+            if cmds.get(name).is_some() && !cmds.is_response(name) {
+                problems.push(format!(
+                    "responses.{name}: there's already a !{name} command"
+                ));
+            }
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                problems.push(format!("responses.{name}: not a valid command name"));
+            }
+            if text.trim().is_empty() {
+                problems.push(format!("responses.{name}: empty response"));
+            }
+        }
+
+        let moderation = &self.moderation;
+        if moderation.enabled && moderation.actions.is_empty() {
+            problems.push("moderation.actions is empty, nothing would be done".to_owned());
+        }
+        for (name, filter) in [("caps", moderation.caps), ("symbols", moderation.symbols)] {
+            if !(0.0..=1.0).contains(&filter.max_ratio) {
+                problems.push(format!(
+                    "moderation.{name}.max_ratio: {} has to be between 0 and 1",
+                    filter.max_ratio
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&moderation.flood.similarity) {
+            problems.push(format!(
+                "moderation.flood.similarity: {} has to be between 0 and 1",
+                moderation.flood.similarity
+            ));
+        }
+        for pattern in &moderation.words.patterns {
+            if regex::Regex::new(pattern).is_err() {
+                problems.push(format!(
+                    "moderation.words.patterns: {pattern:?} is not a valid regex"
+                ));
+            }
+        }
+
+        for entry in &self.output.blocked {
+            if !entry.chars().any(char::is_alphanumeric) {
+                problems.push(format!(
+                    "output.blocked: {entry:?} has no letters or digits, it would never match"
+                ));
+            }
+        }
+
+        let mut services: Vec<_> = self.outbox.services.iter().collect();
+        services.sort_by_key(|(name, _)| *name);
+        for (name, service) in services {
+            if service.actions.is_empty() {
+                problems.push(format!("outbox.services.{name}: no actions allowed"));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Durations are written like ```"250ms"``` or ```"1m30s"```, see [`parse_duration`]
+pub(crate) fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_duration(&s)
+        .ok_or_else(|| D::Error::custom(format!("{s:?} is not a duration like \"250ms\"")))
+}
+
+fn deserialize_optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::TwitchApiWrapper, command::ChatCommand, context::CommandContext, error::CommandResult,
+    };
+
+    struct Ping;
+
+    impl ChatCommand for Ping {
+        fn new() -> Self {
+            Self
+        }
+
+        fn names() -> Vec<String> {
+            vec!["ping".to_owned()]
+        }
+
+        fn handle(&mut self, _: &mut TwitchApiWrapper, _: &CommandContext) -> CommandResult {
+            Ok(())
+        }
+
+        fn help(&self) -> String {
+            "usage: !ping".to_owned()
+        }
+    }
+
+    const FULL: &str = r#"
+        data_dir = "/data"
+
+        [franz]
+        broker = "localhost:8085"
+        topics = { chat = "test-chat" }
+        reconnect = { min = "1s", max = "1m" }
+
+        [twitch]
+        bot_id = "1124612654"
+
+        [chat]
+        prefixes = ["!", "?"]
+        max_age = "30s"
+
+        [spam]
+        user = { max = 2, per = "10s" }
+
+        [ignore]
+        bots = ["channelbot"]
+        bot_messages = "quiet"
+
+        [moderation]
+        enabled = true
+        actions = [{ action = "delete" }, { action = "timeout", duration = "5m" }]
+        links = { allowed = ["github.com"], role = "subscriber" }
+        words = { banned = ["heck"] }
+        flood = { repeats = 2, burst = { max = 10, per = "5s" } }
+
+        [output]
+        blocked = ["heck"]
+        strip_links = false
+
+        [commands.ping]
+        enabled = false
+        cooldown = "1m30s"
+
+        [handlers]
+        budget = "500ms"
+
+        [outbox.services.points]
+        actions = ["say", "timeout"]
+        rate = { max = 5, per = "10s" }
+
+        [audit]
+        max_bytes = 1024
+        topic = "bot-audit"
+    "#;
+
+    #[test]
+    fn parse_full() {
+        let config = Config::from_toml(FULL).unwrap();
+
+        assert_eq!(config.data_dir, PathBuf::from("/data"));
+        assert_eq!(config.franz.broker, "localhost:8085");
+        assert_eq!(config.franz.topics.chat, "test-chat");
+        assert_eq!(
+            config.franz.reconnect,
+            ReconnectConfig {
+                min: Duration::from_secs(1),
+                max: Duration::from_secs(60),
+            }
+        );
+        assert_eq!(config.twitch.bot_id, "1124612654");
+        assert_eq!(config.chat.prefixes, ['!', '?']);
+        assert_eq!(config.chat.max_age, Duration::from_secs(30));
+        assert_eq!(config.spam.user, RateLimit::new(2, Duration::from_secs(10)));
+        // untouched limits keep their defaults
+        assert_eq!(
+            config.spam.failed_command,
+            SpamConfig::default().failed_command
+        );
+        assert_eq!(
+            config.commands["ping"],
+            CommandConfig {
+                enabled: false,
+                cooldown: Some(Duration::from_secs(90)),
+            }
+        );
+        assert_eq!(
+            config.outbox.services["points"],
+            OutboxServiceConfig {
+                actions: vec![OutboxKind::Say, OutboxKind::Timeout],
+                rate: Some(RateLimit::new(5, Duration::from_secs(10))),
+            }
+        );
+        assert_eq!(
+            config.ignore,
+            IgnoreConfig {
+                bots: vec!["channelbot".to_owned()],
+                bot_messages: BotPolicy::Quiet,
+                ..IgnoreConfig::default()
+            }
+        );
+        assert_eq!(
+            config.moderation.actions,
+            [
+                FilterAction::Delete,
+                FilterAction::Timeout {
+                    duration: Duration::from_secs(300)
+                }
+            ]
+        );
+        assert_eq!(
+            config.moderation.links,
+            LinkFilterConfig {
+                allowed: vec!["github.com".to_owned()],
+                role: Role::Subscriber,
+                ..LinkFilterConfig::default()
+            }
+        );
+        assert_eq!(config.moderation.words.banned, ["heck"]);
+        assert_eq!(config.moderation.caps, RatioFilterConfig::default());
+        assert_eq!(
+            config.moderation.flood,
+            FloodConfig {
+                repeats: 2,
+                burst: RateLimit::new(10, Duration::from_secs(5)),
+                ..FloodConfig::default()
+            }
+        );
+        assert_eq!(config.moderation.log, PathBuf::from("moderation.jsonl"));
+        assert_eq!(
+            config.output,
+            OutputConfig {
+                blocked: vec!["heck".to_owned()],
+                strip_links: false,
+                ..OutputConfig::default()
+            }
+        );
+        assert_eq!(
+            config.handlers,
+            HandlersConfig {
+                budget: Duration::from_millis(500),
+                max_panics: 3,
+            }
+        );
+        assert_eq!(
+            config.audit,
+            AuditConfig {
+                max_bytes: 1024,
+                topic: "bot-audit".to_owned(),
+                ..AuditConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        let config = Config::from_toml("").unwrap();
+
+        assert_eq!(config.franz.topics.chat, "chat");
+        assert_eq!(config.chat.prefixes, ['!']);
+        assert!(config.commands.is_empty());
+        assert!(config.responses.is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Config::from_toml("[spam]\nuser = { max = 1, per = \"soon\" }").is_err());
+        assert!(Config::from_toml("[commands.ping]\ncooldown = 5").is_err());
+        assert!(Config::from_toml("typo = true").is_err());
+        assert!(Config::from_toml("[outbox.services.points]\nactions = [\"ban\"]").is_err());
+        assert!(Config::from_toml("[ignore]\nbot_messages = \"reply\"").is_err());
+        assert!(Config::from_toml("[moderation]\nactions = [{ action = \"ban\" }]").is_err());
+    }
+
+    #[test]
+    fn env_overrides() {
+        let mut config = Config::from_toml(FULL).unwrap();
+        config.apply_env(|key| match key {
+            "FRANZ_BROKER" => Some("franz:1234".to_owned()),
+            "MOSTLYBOT_PREFIXES" => Some("$".to_owned()),
+            "MOSTLYBOT_DRY_RUN" => Some("1".to_owned()),
+            "PORT" => Some("9090".to_owned()),
+            _ => None,
+        });
+
+        assert_eq!(config.franz.broker, "franz:1234");
+        assert_eq!(config.chat.prefixes, ['$']);
+        assert!(config.dry_run.enabled);
+        assert_eq!(config.http.addr, "0.0.0.0:9090");
+        assert_eq!(config.twitch.bot_id, "1124612654");
+    }
+
+    #[test]
+    fn validation() {
+        let mut cmds = CommandMap::new();
+        cmds.insert(Ping::new());
+
+        assert!(Config::from_toml(FULL).unwrap().validate(&cmds).is_empty());
+
+        let config = Config::from_toml(
+            "[chat]\nprefixes = [\"a\"]\n[commands.pong]\n[responses]\nping = \"pong\"\n\"d-c\" = \" \"\n[outbox.services.points]\nactions = []",
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate(&cmds),
+            [
+                "franz.broker is not set (or FRANZ_BROKER)",
+                "twitch.bot_id is not set (or TWITCH_BOT_ID)",
+                "chat.prefixes: 'a' can't start a command",
+                "commands.pong: no such command",
+                "responses.d-c: not a valid command name",
+                "responses.d-c: empty response",
+                "responses.ping: there's already a !ping command",
+                "outbox.services.points: no actions allowed",
+            ]
+        );
+
+        let mut config = Config::from_toml(FULL).unwrap();
+        config.franz.reconnect.min = Duration::from_secs(120);
+        config.http.addr = "8080".to_owned();
+        config.audit.max_bytes = 0;
+        config.moderation.actions.clear();
+        config.moderation.caps.max_ratio = 1.5;
+        config.moderation.words.patterns = vec!["(unclosed".to_owned()];
+        config.output.blocked.push("?!".to_owned());
+        assert_eq!(
+            config.validate(&cmds),
+            [
+                "franz.reconnect: min (120s) has to be more than zero and at most max (60s)",
+                "audit.max_bytes has to be more than zero",
+                "http.addr: \"8080\" is not an address like \"0.0.0.0:8080\"",
+                "moderation.actions is empty, nothing would be done",
+                "moderation.caps.max_ratio: 1.5 has to be between 0 and 1",
+                "moderation.words.patterns: \"(unclosed\" is not a valid regex",
+                "output.blocked: \"?!\" has no letters or digits, it would never match",
+            ]
+        );
+    }
+}
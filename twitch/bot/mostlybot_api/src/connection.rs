@@ -0,0 +1,296 @@
+use crate::{config::ReconnectConfig, rng::BotRng, source::EventSource};
+use rand::Rng;
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// Health ---------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Hasn't connected yet, or is trying again after losing the connection
+    Connecting,
+    Connected,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => f.write_str("connecting"),
+            ConnectionState::Connected => f.write_str("connected"),
+        }
+    }
+}
+
+/// A snapshot of [`ConnectionHealth`]
+#[derive(Debug, Clone)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// When ```state``` last changed
+    pub since: Instant,
+    /// How many times an established connection was lost
+    pub disconnects: u64,
+    pub last_error: Option<String>,
+}
+
+/// How the consumer's connection to the broker is doing, shared with whatever reports
+/// health. Cheap to clone and safe to read from other threads
+#[derive(Debug, Clone)]
+pub struct ConnectionHealth {
+    status: Arc<Mutex<ConnectionStatus>>,
+}
+
+impl Default for ConnectionHealth {
+    fn default() -> Self {
+        Self {
+            status: Arc::new(Mutex::new(ConnectionStatus {
+                state: ConnectionState::Connecting,
+                since: Instant::now(),
+                disconnects: 0,
+                last_error: None,
+            })),
+        }
+    }
+}
+
+impl ConnectionHealth {
+    pub fn status(&self) -> ConnectionStatus {
+        self.status.lock().expect("connection health lock").clone()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.status().state
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        let mut status = self.status.lock().expect("connection health lock");
+        if status.state != state {
+            status.state = state;
+            status.since = Instant::now();
+        }
+    }
+
+    fn connected(&self) {
+        self.set_state(ConnectionState::Connected);
+    }
+
+    /// An established connection went away
+    fn disconnected(&self, error: &io::Error) {
+        self.set_state(ConnectionState::Connecting);
+
+        let mut status = self.status.lock().expect("connection health lock");
+        status.disconnects += 1;
+        status.last_error = Some(error.to_string());
+    }
+
+    /// Couldn't connect in the first place
+    fn failed(&self, error: &io::Error) {
+        self.status
+            .lock()
+            .expect("connection health lock")
+            .last_error = Some(error.to_string());
+    }
+}
+
+// Backoff --------------------------------------------------------------------
+
+/// Exponential backoff with jitter, every delay is somewhere between half and all of
+/// ```min * 2^attempt```, capped at ```max```
+#[derive(Debug)]
+pub struct Backoff {
+    config: ReconnectConfig,
+    attempt: u32,
+    rng: BotRng,
+}
+
+impl Backoff {
+    pub fn new(config: ReconnectConfig, rng: BotRng) -> Self {
+        Self {
+            config,
+            attempt: 0,
+            rng,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .config
+            .min
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.config.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = delay / 2;
+        half + half.mul_f64(self.rng.gen::<f64>())
+    }
+
+    /// Back to ```min```, called once things work again
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+// Supervisor -----------------------------------------------------------------
+
+/// Keeps a consumer alive. Whenever connecting or receiving fails it waits out the
+/// backoff, reconnects right after the last message it got and carries on, so the bot
+/// never stops just because the broker went away for a bit
+pub struct SupervisedConsumer<C, F> {
+    connect: F,
+    consumer: Option<C>,
+    /// Offset of the next message
+    offset: u64,
+    backoff: Backoff,
+    health: ConnectionHealth,
+}
+
+impl<C, F> SupervisedConsumer<C, F>
+where
+    C: EventSource,
+    F: FnMut(u64) -> io::Result<C>,
+{
+    /// ```connect``` opens a consumer starting at the given offset, the first one being ```offset```
+    pub fn new(connect: F, offset: u64, backoff: Backoff, health: ConnectionHealth) -> Self {
+        Self {
+            connect,
+            consumer: None,
+            offset,
+            backoff,
+            health,
+        }
+    }
+
+    pub fn health(&self) -> &ConnectionHealth {
+        &self.health
+    }
+
+    /// Blocks until the next message, reconnecting if needed
+    pub fn next_message(&mut self) -> Vec<u8> {
+        loop {
+            let consumer = match self.consumer.take() {
+                Some(consumer) => consumer,
+                None => match (self.connect)(self.offset) {
+                    Ok(consumer) => {
+                        tracing::info!(offset = self.offset, "connected to franz");
+                        self.health.connected();
+                        consumer
+                    }
+                    Err(e) => {
+                        self.health.failed(&e);
+                        self.wait(&e);
+                        continue;
+                    }
+                },
+            };
+            let consumer = self.consumer.insert(consumer);
+
+            match consumer
+                .recv()
+                .and_then(|msg| msg.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof)))
+            {
+                Ok(msg) => {
+                    self.offset += 1;
+                    self.backoff.reset();
+                    return msg;
+                }
+                Err(e) => {
+                    self.consumer = None;
+                    self.health.disconnected(&e);
+                    self.wait(&e);
+                }
+            }
+        }
+    }
+
+    fn wait(&mut self, error: &io::Error) {
+        let delay = self.backoff.next_delay();
+        tracing::warn!(error = %error, ?delay, "lost franz, reconnecting");
+        std::thread::sleep(delay);
+    }
+}
+
+/// Never runs dry and never fails, it just keeps reconnecting
+impl<C, F> EventSource for SupervisedConsumer<C, F>
+where
+    C: EventSource,
+    F: FnMut(u64) -> io::Result<C>,
+{
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(Some(self.next_message()))
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn config(min_ms: u64, max_ms: u64) -> ReconnectConfig {
+        ReconnectConfig {
+            min: Duration::from_millis(min_ms),
+            max: Duration::from_millis(max_ms),
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let mut backoff = Backoff::new(config(100, 1000), BotRng::seed_from_u64(0));
+
+        for expected in [100, 200, 400, 800, 1000, 1000] {
+            let delay = backoff.next_delay();
+            let expected = Duration::from_millis(expected);
+            assert!(delay >= expected / 2 && delay <= expected, "{delay:?}");
+        }
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= Duration::from_millis(100));
+    }
+
+    struct Fake(VecDeque<io::Result<Vec<u8>>>);
+
+    impl EventSource for Fake {
+        fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+            self.0.pop_front().transpose()
+        }
+    }
+
+    #[test]
+    fn reconnects() {
+        let mut offsets = Vec::new();
+        let mut connections = VecDeque::from([
+            Ok(Fake(VecDeque::from([Ok(b"a".to_vec())]))),
+            Err(io::ErrorKind::ConnectionRefused.into()),
+            Ok(Fake(VecDeque::from([Ok(b"b".to_vec())]))),
+        ]);
+
+        let health = ConnectionHealth::default();
+        assert_eq!(health.state(), ConnectionState::Connecting);
+
+        let mut consumer = SupervisedConsumer::new(
+            |offset| {
+                offsets.push(offset);
+                connections.pop_front().unwrap()
+            },
+            5,
+            Backoff::new(config(1, 5), BotRng::seed_from_u64(0)),
+            health.clone(),
+        );
+
+        assert_eq!(consumer.next_message(), b"a");
+        assert_eq!(health.state(), ConnectionState::Connected);
+
+        // the first connection runs dry, the second attempt is refused, the third works
+        assert_eq!(consumer.next_message(), b"b");
+        drop(consumer);
+        assert_eq!(offsets, [5, 6, 6]);
+
+        let status = health.status();
+        assert_eq!(status.state, ConnectionState::Connected);
+        assert_eq!(status.disconnects, 1);
+        assert!(status.last_error.is_some());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    audit::AuditLog,
+    command::CommandMap,
+    error::{CommandError, CommandResult},
+    events::EventBus,
+    ignore::IgnoreList,
+    metrics::Metrics,
+    moderation::Moderation,
+    reload::ReloadHandle,
+    rng::BotRng,
+    role::Role,
+    sent::SentMessages,
+    stats::RuntimeStats,
+    usage::Usage,
+};
+use anyhow::anyhow;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use twitcheventsub::MessageData;
+
+/// Handles to the bot services commands are allowed to use, cheap to clone
+#[derive(Debug, Clone, Default)]
+pub struct Services {
+    pub rng: BotRng,
+    pub reload: ReloadHandle,
+    /// ```data_dir``` from the config
+    pub data_dir: PathBuf,
+    /// Where [`CommandContext::publish`] sends events, only logged by default
+    pub events: EventBus,
+    /// What ```/metrics``` reports
+    pub metrics: Metrics,
+    /// Uptime, version and the like, see [`CommandContext::stats`]
+    pub stats: RuntimeStats,
+    /// Where the dispatcher records who ran what, records nothing by default
+    pub audit: AuditLog,
+    /// Command counts, see [`CommandContext::usage`]
+    pub usage: Usage,
+    /// Who moderators told the bot to ignore
+    pub ignore: IgnoreList,
+    /// The chat filters, see [`CommandContext::moderation`]
+    pub moderation: Moderation,
+    /// What the bot said lately, see [`CommandContext::sent_messages`]
+    pub sent: SentMessages,
+}
+
+impl Services {
+    pub fn new(rng: BotRng) -> Self {
+        Self {
+            rng,
+            ..Self::default()
+        }
+    }
+}
+
+// the dispatcher lends its services to every command, tests can hand theirs over
+
+impl From<Services> for Cow<'_, Services> {
+    fn from(services: Services) -> Self {
+        Cow::Owned(services)
+    }
+}
+
+impl<'a> From<&'a Services> for Cow<'a, Services> {
+    fn from(services: &'a Services) -> Self {
+        Cow::Borrowed(services)
+    }
+}
+
+/// Everything a command gets to know about the chat message that triggered it,
+/// plus handles to the bot services it is allowed to use.
+///
+/// Derefs to [`MessageData`] so the usual ```ctx.chatter.id``` and
+/// ```ctx.message_id``` still work.
+#[derive(Debug)]
+pub struct CommandContext<'a> {
+    msg: &'a MessageData,
+    services: Cow<'a, Services>,
+    /// Every command, set by the dispatcher so reloaded responses are in it
+    commands: Cow<'a, CommandMap>,
+    /// The topics the command declared, see [`ChatCommand::topics`](crate::ChatCommand::topics)
+    topics: Rc<[String]>,
+}
+
+impl<'a> CommandContext<'a> {
+    /// Context with only an rng set up, handy for tests
+    pub fn new(msg: &'a MessageData, rng: BotRng) -> Self {
+        Self::with_services(msg, Services::new(rng))
+    }
+
+    pub fn with_services(msg: &'a MessageData, services: impl Into<Cow<'a, Services>>) -> Self {
+        Self {
+            msg,
+            services: services.into(),
+            commands: Cow::Owned(CommandMap::new()),
+            topics: Rc::new([]),
+        }
+    }
+
+    /// Lets the command see every other command, the dispatcher passes the map it's
+    /// dispatching from
+    pub fn with_commands(self, commands: &'a CommandMap) -> Self {
+        Self {
+            commands: Cow::Borrowed(commands),
+            ..self
+        }
+    }
+
+    /// Lets the command publish to these topics, the dispatcher passes what the command
+    /// declared
+    pub fn publishing_to(self, topics: impl Into<Rc<[String]>>) -> Self {
+        Self {
+            topics: topics.into(),
+            ..self
+        }
+    }
+
+    /// The raw twitch chat message
+    pub fn message_data(&self) -> &'a MessageData {
+        self.msg
+    }
+
+    /// Random number generator, seeded in tests so output is reproducible
+    pub fn rng(&self) -> BotRng {
+        self.services.rng.clone()
+    }
+
+    /// Where to keep files that should survive a restart
+    pub fn data_dir(&self) -> &Path {
+        &self.services.data_dir
+    }
+
+    /// Facts about the running bot: uptime, version, the last error, ...
+    pub fn stats(&self) -> &RuntimeStats {
+        &self.services.stats
+    }
+
+    /// The counters behind ```/metrics```
+    pub fn metrics(&self) -> &Metrics {
+        &self.services.metrics
+    }
+
+    /// How often each command was run, by whom and when
+    pub fn usage(&self) -> &Usage {
+        &self.services.usage
+    }
+
+    /// The chatters moderators told the bot to ignore
+    pub fn ignore_list(&self) -> &IgnoreList {
+        &self.services.ignore
+    }
+
+    /// The chat filters, for handing out ```!permit```s
+    pub fn moderation(&self) -> &Moderation {
+        &self.services.moderation
+    }
+
+    /// The bot's last messages and who made it send them, for ```!undo```
+    pub fn sent_messages(&self) -> &SentMessages {
+        &self.services.sent
+    }
+
+    /// Every command the bot has right now, for ```!cmd``` and ```!help```. The command being
+    /// handled is in there too, but it's already borrowed
+    pub fn commands(&self) -> &CommandMap {
+        &self.commands
+    }
+
+    /// Asks the bot to reload its config
+    pub fn reload(&self) -> &ReloadHandle {
+        &self.services.reload
+    }
+
+    /// Publishes ```event``` as json to one of the franz topics the command declared, for
+    /// other services to act on.
+    ///
+    /// ```ignore
+    /// ctx.publish("points-term", &json!({ "action": "train", "by": ctx.chatter.name }))?;
+    /// ```
+    pub fn publish<E: Serialize>(&self, topic: &str, event: &E) -> CommandResult {
+        if !self.topics.iter().any(|declared| declared == topic) {
+            return Err(CommandError::Internal(anyhow!(
+                "publishing to {topic:?}, which the command didn't declare in topics()"
+            )));
+        }
+
+        let event = serde_json::to_string(event).map_err(anyhow::Error::from)?;
+        self.services
+            .events
+            .publish(topic, &event)
+            .map_err(|e| CommandError::Transient(anyhow!("publishing to {topic:?}: {e}")))
+    }
+
+    /// The highest role of the chatter who sent the message
+    pub fn role(&self) -> Role {
+        Role::of(self.msg)
+    }
+}
+
+impl Deref for CommandContext<'_> {
+    type Target = MessageData;
+
+    fn deref(&self) -> &Self::Target {
+        self.msg
+    }
+}
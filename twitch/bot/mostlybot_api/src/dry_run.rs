@@ -0,0 +1,160 @@
+use crate::api::{ChatPlatform, ModAction, PlatformError, Trigger};
+use serde_json::{json, Value};
+
+/// A [`ChatPlatform`] that never posts. Everything the bot would have done is logged, and
+/// published to a franz topic if there is one, along with the chat message (or outbox
+/// request) that caused it.
+/// Meant for running a new version next to the live bot and comparing what they do
+#[derive(Default)]
+pub struct DryRun {
+    producer: Option<franz_client::Producer>,
+    /// What's being handled, see [`ChatPlatform::handling`]
+    trigger: Option<Value>,
+    /// Fake ids for the messages that weren't sent
+    sent: u64,
+}
+
+impl DryRun {
+    pub fn new(producer: Option<franz_client::Producer>) -> Self {
+        Self {
+            producer,
+            ..Self::default()
+        }
+    }
+
+    /// Logs (and publishes) one would-be action, returning what was recorded
+    fn record(&mut self, action: &str, fields: Value) -> Value {
+        let mut record = json!({
+            "action": action,
+            "trigger": self.trigger,
+        });
+        if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+            record.extend(fields);
+        }
+
+        tracing::info!(target: "dry_run", "{}", record);
+        if let Some(producer) = &mut self.producer {
+            if let Err(e) = producer.send(record.to_string()) {
+                tracing::warn!(error = %e, "unable to publish dry run output");
+            }
+        }
+
+        record
+    }
+
+    fn next_id(&mut self) -> String {
+        self.sent += 1;
+        format!("dry-run-{}", self.sent)
+    }
+}
+
+impl ChatPlatform for DryRun {
+    fn send(&mut self, message: &str) -> Result<String, PlatformError> {
+        self.record("send", json!({ "message": message }));
+        Ok(self.next_id())
+    }
+
+    fn reply(&mut self, message: &str, parent_id: &str) -> Result<String, PlatformError> {
+        self.record(
+            "reply",
+            json!({ "message": message, "parent_id": parent_id }),
+        );
+        Ok(self.next_id())
+    }
+
+    fn announce(&mut self, message: &str) -> Result<(), PlatformError> {
+        self.record("announce", json!({ "message": message }));
+        Ok(())
+    }
+
+    fn moderate(&mut self, action: &ModAction) -> Result<(), PlatformError> {
+        match action {
+            ModAction::Delete { message_id } => {
+                self.record("delete", json!({ "message_id": message_id }))
+            }
+            ModAction::Timeout {
+                user_id,
+                duration,
+                reason,
+            } => self.record(
+                "timeout",
+                json!({
+                    "user_id": user_id,
+                    "duration_secs": duration.as_secs(),
+                    "reason": reason,
+                }),
+            ),
+        };
+        Ok(())
+    }
+
+    fn whisper(&mut self, user_id: &str, message: &str) -> Result<(), PlatformError> {
+        self.record("whisper", json!({ "user_id": user_id, "message": message }));
+        Ok(())
+    }
+
+    fn handling(&mut self, trigger: Trigger) {
+        self.trigger = Some(match trigger {
+            Trigger::Chat(message) => json!({
+                "message_id": message.message_id,
+                "chatter_id": message.chatter.id,
+                "chatter": message.chatter.name,
+                "text": message.message.text,
+            }),
+            Trigger::Outbox { service, id } => json!({ "service": service, "request_id": id }),
+        });
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{chat_message, MESSAGE_ID};
+
+    #[test]
+    fn records_with_trigger() {
+        let mut dry_run = DryRun::new(None);
+
+        // nothing triggered it yet
+        assert_eq!(
+            dry_run.record("send", json!({ "message": "hi" })),
+            json!({ "action": "send", "trigger": null, "message": "hi" })
+        );
+
+        let msg = chat_message("chatter", "!ping", &[]);
+        dry_run.handling(Trigger::Chat(&msg));
+
+        assert_eq!(
+            dry_run.record("reply", json!({ "message": "pong", "parent_id": "abc" })),
+            json!({
+                "action": "reply",
+                "trigger": {
+                    "message_id": MESSAGE_ID,
+                    "chatter_id": "chatter",
+                    "chatter": "chatter",
+                    "text": "!ping",
+                },
+                "message": "pong",
+                "parent_id": "abc",
+            })
+        );
+
+        dry_run.handling(Trigger::Outbox {
+            service: "points",
+            id: "1",
+        });
+        assert_eq!(
+            dry_run.record("announce", json!({ "message": "hi" })),
+            json!({
+                "action": "announce",
+                "trigger": { "service": "points", "request_id": "1" },
+                "message": "hi",
+            })
+        );
+
+        assert_eq!(dry_run.send("hi").unwrap(), "dry-run-1");
+        assert_eq!(dry_run.reply("hi", "abc").unwrap(), "dry-run-2");
+    }
+}
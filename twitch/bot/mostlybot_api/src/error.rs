@@ -0,0 +1,59 @@
+use crate::api::PlatformError;
+use std::fmt;
+
+pub type CommandResult<T = ()> = Result<T, CommandError>;
+
+/// Errors a command can return from ```handle```.
+///
+/// The variant decides what chat gets to see, so pick the one that matches who
+/// is at fault:
+/// - [`CommandError::Usage`]: the chatter used the command wrong, replies with the command's ```help()```
+/// - [`CommandError::UserFacing`]: replies with the given message as is
+/// - [`CommandError::PermissionDenied`]: the chatter isn't allowed to do that
+/// - [`CommandError::Transient`]: something outside our control failed (twitch api, network), try again later
+/// - [`CommandError::Internal`]: a bug, logged in full but chat only sees a generic message with an error id
+#[derive(Debug)]
+pub enum CommandError {
+    Usage,
+    UserFacing(String),
+    PermissionDenied,
+    Transient(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl CommandError {
+    pub fn user_facing<S: Into<String>>(msg: S) -> Self {
+        Self::UserFacing(msg.into())
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage => write!(f, "invalid usage"),
+            Self::UserFacing(msg) => write!(f, "{msg}"),
+            Self::PermissionDenied => write!(f, "permission denied"),
+            Self::Transient(err) => write!(f, "transient error: {err:#}"),
+            Self::Internal(err) => write!(f, "internal error: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Anything bubbled up with ```?``` is a bug until proven otherwise
+impl From<anyhow::Error> for CommandError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+/// Failing to talk to twitch is usually temporary, asking it for something it can't do is a bug
+impl From<PlatformError> for CommandError {
+    fn from(err: PlatformError) -> Self {
+        match err {
+            PlatformError::Unsupported(_) => Self::Internal(err.into()),
+            PlatformError::Failed(_) => Self::Transient(err.into()),
+        }
+    }
+}
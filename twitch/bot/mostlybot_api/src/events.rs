@@ -0,0 +1,138 @@
+use std::{cell::RefCell, collections::HashMap, fmt, io, rc::Rc};
+
+/// Where the events commands publish end up
+pub trait EventSink {
+    fn publish(&mut self, topic: &str, event: &str) -> io::Result<()>;
+}
+
+/// Publishes to franz, opening a producer per topic the first time it's used. A producer
+/// that fails is dropped, so the next event tries a fresh connection
+pub struct FranzEvents {
+    broker: String,
+    producers: HashMap<String, franz_client::Producer>,
+}
+
+impl FranzEvents {
+    pub fn new(broker: &str) -> Self {
+        Self {
+            broker: broker.to_owned(),
+            producers: HashMap::new(),
+        }
+    }
+}
+
+impl EventSink for FranzEvents {
+    fn publish(&mut self, topic: &str, event: &str) -> io::Result<()> {
+        if !self.producers.contains_key(topic) {
+            let producer = franz_client::Producer::new(&self.broker, topic)?;
+            self.producers.insert(topic.to_owned(), producer);
+        }
+
+        let producer = self.producers.get_mut(topic).expect("opened above");
+        if let Err(e) = producer.send(event) {
+            self.producers.remove(topic);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Only logs events, for dry runs and tests that don't care
+#[derive(Debug, Default)]
+pub struct LogEvents;
+
+impl EventSink for LogEvents {
+    fn publish(&mut self, topic: &str, event: &str) -> io::Result<()> {
+        tracing::info!(target: "events", %topic, "{}", event);
+        Ok(())
+    }
+}
+
+/// Remembers every event as ```(topic, event)```, clones share them. For tests
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEvents {
+    events: Rc<RefCell<Vec<(String, String)>>>,
+}
+
+impl MemoryEvents {
+    pub fn events(&self) -> Vec<(String, String)> {
+        self.events.borrow().clone()
+    }
+}
+
+impl EventSink for MemoryEvents {
+    fn publish(&mut self, topic: &str, event: &str) -> io::Result<()> {
+        self.events
+            .borrow_mut()
+            .push((topic.to_owned(), event.to_owned()));
+        Ok(())
+    }
+}
+
+/// The handle commands publish through, see
+/// [`CommandContext::publish`](crate::CommandContext::publish). Cheap to clone, clones
+/// share the sink
+#[derive(Clone)]
+pub struct EventBus {
+    sink: Rc<RefCell<dyn EventSink>>,
+}
+
+impl EventBus {
+    pub fn new(sink: impl EventSink + 'static) -> Self {
+        Self {
+            sink: Rc::new(RefCell::new(sink)),
+        }
+    }
+
+    pub fn publish(&self, topic: &str, event: &str) -> io::Result<()> {
+        self.sink.borrow_mut().publish(topic, event)
+    }
+}
+
+/// Logs events, nothing leaves the bot unless it's given a real sink
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(LogEvents)
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus").finish_non_exhaustive()
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        context::CommandContext, error::CommandError, rng::BotRng, test_util::chat_message,
+        Services,
+    };
+
+    #[test]
+    fn only_declared_topics() {
+        let msg = chat_message("chatter", "!train", &[]);
+        let events = MemoryEvents::default();
+        let services = Services {
+            events: EventBus::new(events.clone()),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        let ctx =
+            CommandContext::with_services(&msg, services).publishing_to(vec!["trains".to_owned()]);
+
+        ctx.publish("trains", &serde_json::json!({ "action": "train" }))
+            .unwrap();
+        assert!(matches!(
+            ctx.publish("chat", &"hi"),
+            Err(CommandError::Internal(_))
+        ));
+
+        assert_eq!(
+            events.events(),
+            [("trains".to_owned(), r#"{"action":"train"}"#.to_owned())]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{config::FloodConfig, moderation::Filter, spam::RateLimiter};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
+};
+use twitcheventsub::MessageData;
+
+/// Messages kept around to compare new ones against, however long the window
+const MAX_RECENT: usize = 500;
+
+/// Lowercase words, so ```Hello,   WORLD!``` and ```hello world``` are the same message
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How alike two messages are, from 0 (nothing in common) to 1 (the same). Counts the pairs of
+/// characters they share, so a changed word or two still comes out close
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+
+    let pairs = |s: &str| {
+        let chars: Vec<char> = s.chars().collect();
+        let mut pairs: HashMap<(char, char), usize> = HashMap::new();
+        for pair in chars.windows(2) {
+            *pairs.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        pairs
+    };
+    let (a, b) = (pairs(a), pairs(b));
+    let total: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 0.0;
+    }
+
+    let shared: usize = a
+        .iter()
+        .map(|(pair, count)| (*count).min(b.get(pair).copied().unwrap_or_default()))
+        .sum();
+    2.0 * shared as f64 / total as f64
+}
+
+/// Chat from the last ```flood.window```, for the filters that look at more than one message
+#[derive(Debug)]
+pub(crate) struct Flood {
+    /// Oldest first, ```(when, user id, normalized text)```
+    recent: VecDeque<(Instant, String, String)>,
+    /// Messages per chatter, for ```flood.burst```
+    burst: RateLimiter<String>,
+}
+
+impl Default for Flood {
+    fn default() -> Self {
+        Self {
+            recent: VecDeque::new(),
+            burst: RateLimiter::new(FloodConfig::default().burst),
+        }
+    }
+}
+
+impl Flood {
+    /// Remembers ```msg``` and returns the first flood filter it trips: the same chatter saying
+    /// (nearly) the same thing over and over, many chatters pasting the same thing, or one
+    /// chatter sending too much too fast
+    pub(crate) fn check(
+        &mut self,
+        msg: &MessageData,
+        config: &FloodConfig,
+        now: Instant,
+    ) -> Option<Filter> {
+        if !config.enabled {
+            return None;
+        }
+
+        while let Some((at, ..)) = self.recent.front() {
+            if now.saturating_duration_since(*at) < config.window && self.recent.len() < MAX_RECENT
+            {
+                break;
+            }
+            self.recent.pop_front();
+        }
+
+        let user_id = &msg.chatter.id;
+        let text = normalize(&msg.message.text);
+        let mut filter = None;
+
+        if text.chars().count() >= config.min_length {
+            let similar: Vec<_> = self
+                .recent
+                .iter()
+                .filter(|(_, _, other)| similarity(&text, other) >= config.similarity)
+                .map(|(_, user, _)| user)
+                .collect();
+            let repeats = similar.iter().filter(|user| **user == user_id).count() + 1;
+            let chatters = similar
+                .iter()
+                .filter(|user| **user != user_id)
+                .collect::<HashSet<_>>()
+                .len()
+                + 1;
+
+            if config.repeats > 0 && repeats >= config.repeats {
+                filter = Some(Filter::Repeat);
+            } else if config.copypasta > 0 && chatters >= config.copypasta {
+                filter = Some(Filter::Copypasta);
+            }
+        }
+
+        let bursting = self
+            .burst
+            .enforce_limit(user_id.clone(), Some(&config.burst))
+            .is_some();
+        if filter.is_none() && bursting {
+            filter = Some(Filter::Burst);
+        }
+
+        self.recent.push_back((now, user_id.clone(), text));
+        filter
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{spam::RateLimit, test_util::chat_message};
+    use std::time::Duration;
+
+    #[test]
+    fn similar_messages() {
+        assert_eq!(normalize("Hello,   WORLD!"), "hello world");
+        assert_eq!(similarity("same thing", "same thing"), 1.0);
+        assert!(similarity("buy cheap followers now", "buy cheap followers now!!1") > 0.9);
+        assert!(similarity("buy cheap followers now", "what a nice stream today") < 0.3);
+        assert_eq!(similarity("a", "b"), 0.0);
+    }
+
+    #[test]
+    fn repeats_and_copypasta() {
+        let mut flood = Flood::default();
+        let config = FloodConfig {
+            burst: RateLimit::new(0, Duration::ZERO),
+            ..FloodConfig::default()
+        };
+        let now = Instant::now();
+        let mut check = |user, text, secs| {
+            flood.check(
+                &chat_message(user, text, &[]),
+                &config,
+                now + Duration::from_secs(secs),
+            )
+        };
+
+        assert_eq!(check("1", "anyone here playing elden ring", 0), None);
+        assert_eq!(check("1", "anyone here playing elden ring?", 1), None);
+        assert_eq!(
+            check("1", "ANYONE HERE PLAYING ELDEN RING", 2),
+            Some(Filter::Repeat)
+        );
+        // long enough ago to be forgotten
+        assert_eq!(check("1", "anyone here playing elden ring", 100), None);
+
+        let pasta = "i'm not saying it was aliens but it was aliens";
+        for user in ["2", "3", "4", "5"] {
+            assert_eq!(check(user, pasta, 101), None);
+        }
+        assert_eq!(check("6", pasta, 102), Some(Filter::Copypasta));
+        // short messages like "W" or "LUL" are fine
+        for user in ["2", "3", "4", "5", "6", "7"] {
+            assert_eq!(check(user, "LUL", 103), None);
+        }
+    }
+
+    #[test]
+    fn bursts() {
+        let mut flood = Flood::default();
+        let config = FloodConfig {
+            burst: RateLimit::new(2, Duration::from_secs(10)),
+            ..FloodConfig::default()
+        };
+        let now = Instant::now();
+
+        let results: Vec<_> = ["one", "two", "three"]
+            .into_iter()
+            .map(|text| flood.check(&chat_message("1", text, &[]), &config, now))
+            .collect();
+        assert_eq!(results, [None, None, Some(Filter::Burst)]);
+    }
+}
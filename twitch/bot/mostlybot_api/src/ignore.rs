@@ -0,0 +1,188 @@
+use crate::{config::IgnoreConfig, state::write_atomic};
+use anyhow::Context;
+use serde::Deserialize;
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use twitcheventsub::MessageData;
+
+/// Logins of bots that are in a lot of channels, see [`IgnoreConfig::known_bots`]
+pub const KNOWN_BOTS: [&str; 14] = [
+    "blerp",
+    "botrixoficial",
+    "commanderroot",
+    "fossabot",
+    "kofistreambot",
+    "moobot",
+    "nightbot",
+    "pokemoncommunitygame",
+    "sery_bot",
+    "soundalerts",
+    "streamelements",
+    "streamlabs",
+    "tangiabot",
+    "wizebot",
+];
+
+/// What to do with messages from other bots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BotPolicy {
+    /// Drop them before they're even parsed
+    #[default]
+    Ignore,
+    /// Run their commands, but never answer them with an error so two bots can't get stuck
+    /// telling each other off
+    Quiet,
+    /// Treat them like any other chatter
+    Allow,
+}
+
+/// Who sent a message, as far as the ignore list goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sender {
+    Chatter,
+    Bot,
+    /// On the ignore list
+    Ignored,
+}
+
+/// Where the ignore list lives inside the data dir
+pub fn ignore_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("ignored.json")
+}
+
+/// The chatters moderators told the bot to ignore with ```!ignore```, kept across restarts.
+/// The default is empty and never saved. Cheap to clone, clones share the list
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    logins: Rc<RefCell<BTreeSet<String>>>,
+    path: Option<PathBuf>,
+}
+
+impl IgnoreList {
+    /// Reads the saved list, no file just means nobody was ignored yet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let logins = if path.exists() {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            serde_json::from_str(&json).with_context(|| format!("parsing {}", path.display()))?
+        } else {
+            BTreeSet::new()
+        };
+
+        Ok(Self {
+            logins: Rc::new(RefCell::new(logins)),
+            path: Some(path.to_owned()),
+        })
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&*self.logins.borrow())?;
+        write_atomic(path, json)
+    }
+
+    /// False if they already were, the list is saved either way
+    pub fn ignore(&self, login: &str) -> anyhow::Result<bool> {
+        let added = self.logins.borrow_mut().insert(login.to_lowercase());
+        self.save()?;
+        Ok(added)
+    }
+
+    /// False if they weren't ignored, the list is saved either way
+    pub fn unignore(&self, login: &str) -> anyhow::Result<bool> {
+        let removed = self.logins.borrow_mut().remove(&login.to_lowercase());
+        self.save()?;
+        Ok(removed)
+    }
+
+    pub fn contains(&self, login: &str) -> bool {
+        self.logins.borrow().contains(&login.to_lowercase())
+    }
+
+    pub fn logins(&self) -> Vec<String> {
+        self.logins.borrow().iter().cloned().collect()
+    }
+
+    /// Ignored chatters first, then bots. The broadcaster is never either
+    pub fn sender(&self, msg: &MessageData, config: &IgnoreConfig) -> Sender {
+        let login = msg.chatter.login.to_lowercase();
+        let is = |logins: &[String]| logins.iter().any(|l| l.eq_ignore_ascii_case(&login));
+
+        if msg.chatter.id == msg.broadcaster.id {
+            Sender::Chatter
+        } else if self.contains(&login) || is(&config.users) {
+            Sender::Ignored
+        } else if is(&config.bots) || (config.known_bots && KNOWN_BOTS.contains(&login.as_str())) {
+            Sender::Bot
+        } else {
+            Sender::Chatter
+        }
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{chat_message, BROADCASTER_ID};
+
+    fn message(chatter_id: &str, login: &str) -> MessageData {
+        let mut msg = chat_message(login, "!ping", &[]);
+        msg.chatter.id = chatter_id.to_owned();
+        msg
+    }
+
+    #[test]
+    fn senders() {
+        let list = IgnoreList::default();
+        list.ignore("Troll").unwrap();
+        let config = IgnoreConfig {
+            users: vec!["spammer".to_owned()],
+            bots: vec!["channelbot".to_owned()],
+            ..IgnoreConfig::default()
+        };
+
+        let sender = |id, login| list.sender(&message(id, login), &config);
+        assert_eq!(sender("1", "troll"), Sender::Ignored);
+        assert_eq!(sender("2", "Spammer"), Sender::Ignored);
+        assert_eq!(sender("3", "nightbot"), Sender::Bot);
+        assert_eq!(sender("4", "channelbot"), Sender::Bot);
+        assert_eq!(sender("5", "chatter"), Sender::Chatter);
+        assert_eq!(sender(BROADCASTER_ID, "mostlymaxi"), Sender::Chatter);
+
+        let config = IgnoreConfig {
+            known_bots: false,
+            ..config
+        };
+        assert_eq!(
+            list.sender(&message("3", "nightbot"), &config),
+            Sender::Chatter
+        );
+
+        assert!(list.unignore("troll").unwrap());
+        assert!(!list.unignore("troll").unwrap());
+    }
+
+    #[test]
+    fn saved() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-ignore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = ignore_path(&dir);
+
+        let list = IgnoreList::load(&path).unwrap();
+        assert!(list.ignore("troll").unwrap());
+        assert!(!list.ignore("TROLL").unwrap());
+
+        assert_eq!(IgnoreList::load(&path).unwrap().logins(), ["troll"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
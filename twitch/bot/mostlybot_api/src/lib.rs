@@ -1,9 +1,80 @@
+// lets the derive macros refer to `::mostlybot_api` from inside this crate too
+extern crate self as mostlybot_api;
+
 mod api;
+mod args;
+mod audit;
 mod command;
+mod config;
+mod connection;
+mod context;
+mod dry_run;
+mod error;
+mod events;
+mod flood;
+mod ignore;
+mod metrics;
+mod moderation;
+mod outbox;
+mod reload;
+mod resume;
+mod rng;
+mod role;
+mod sanitize;
+mod sent;
+mod source;
 mod spam;
+mod state;
+mod stats;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod toggles;
+mod usage;
 
-pub use api::{MockTwitchEventSubApi, TwitchApiWrapper};
+pub use api::{
+    ChatPlatform, MockTwitchEventSubApi, ModAction, PlatformError, Trigger, TwitchApiWrapper,
+};
+pub use args::{format_duration, parse_duration, ArgError, Args, CommandArgs, FromArg, Mention};
+pub use audit::{AuditEvent, AuditLog, RotatingFile};
 pub use command::{
     handle_command_if_applicable, ChatCommand, Command, CommandMap, CommandParseResult,
+    CommandRegistration,
+};
+pub use config::{
+    AuditConfig, ChatConfig, CommandConfig, Config, DryRunConfig, FloodConfig, FranzConfig,
+    HandlersConfig, HttpConfig, IgnoreConfig, LinkFilterConfig, ModerationConfig, OutboxConfig,
+    OutboxServiceConfig, OutputConfig, RatioFilterConfig, ReconnectConfig, SpamConfig,
+    TopicsConfig, TwitchConfig, WordFilterConfig, ZalgoFilterConfig,
 };
+pub use connection::{
+    Backoff, ConnectionHealth, ConnectionState, ConnectionStatus, SupervisedConsumer,
+};
+pub use context::{CommandContext, Services};
+pub use dry_run::DryRun;
+pub use error::{CommandError, CommandResult};
+pub use events::{EventBus, EventSink, FranzEvents, LogEvents, MemoryEvents};
+pub use ignore::{ignore_path, BotPolicy, IgnoreList, Sender, KNOWN_BOTS};
+pub use metrics::Metrics;
+pub use moderation::{Filter, FilterAction, Moderation, ModerationEvent};
+pub use mostlybot_macros::{command, CommandArgs};
+pub use outbox::{Outbox, OutboxAction, OutboxError, OutboxKind, OutboxRequest, OutboxResult};
+pub use reload::{apply_config, load_and_apply, ReloadHandle, ReloadTrigger};
+pub use resume::{is_stale, message_age, position_path, ChatCursor, ChatPosition};
+pub use rng::BotRng;
+pub use role::Role;
+pub use sanitize::Sanitizer;
+pub use sent::{SentMessage, SentMessages};
+pub use source::{ChannelSource, ChatEvent, ChatSource, EventSource, LineSource};
 pub use spam::{RateLimit, Spam};
+pub use state::{load_state, save_state, state_path};
+pub use stats::{check_storage, RuntimeStats};
+pub use toggles::{load_toggles, save_toggles, toggles_path, CommandToggle};
+pub use usage::{StreamUsage, Usage, UsageReport, UserUsage};
+
+// used by `#[command]` and `register_command!` to collect commands
+#[doc(hidden)]
+pub use inventory;
+
+// used by `#[command(persist)]` for the state hooks
+#[doc(hidden)]
+pub use anyhow;
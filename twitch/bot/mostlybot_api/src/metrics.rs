@@ -0,0 +1,337 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Upper bounds of the handler latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Counts per bucket in [`LATENCY_BUCKETS`], not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| value <= *le) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    messages: u64,
+    last_message: Option<Instant>,
+    /// ```(command, ok)```
+    handled: BTreeMap<(String, bool), u64>,
+    latency: BTreeMap<String, Histogram>,
+    errors: BTreeMap<&'static str, u64>,
+    cooldowns: BTreeMap<&'static str, u64>,
+    panics: BTreeMap<String, u64>,
+    over_budget: BTreeMap<String, u64>,
+    queues: BTreeMap<&'static str, usize>,
+    /// ```(filter, action)```
+    filtered: BTreeMap<(&'static str, &'static str), u64>,
+}
+
+/// Counters for ```/metrics```, shared between the bot loop that records them and the http
+/// server that reports them. Cheap to clone and safe to use from other threads
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().expect("metrics lock")
+    }
+
+    /// A chat message came in, whether or not it's a command
+    pub fn message_received(&self) {
+        let mut registry = self.registry();
+        registry.messages += 1;
+        registry.last_message = Some(Instant::now());
+    }
+
+    pub fn messages_received(&self) -> u64 {
+        self.registry().messages
+    }
+
+    /// How long ago the last chat message came in
+    pub fn last_message_age(&self) -> Option<Duration> {
+        self.registry().last_message.map(|at| at.elapsed())
+    }
+
+    /// A command's handler ran, successfully or not
+    pub fn command_handled(&self, command: &str, ok: bool, took: Duration) {
+        let mut registry = self.registry();
+        *registry
+            .handled
+            .entry((command.to_owned(), ok))
+            .or_default() += 1;
+        registry
+            .latency
+            .entry(command.to_owned())
+            .or_default()
+            .observe(took.as_secs_f64());
+    }
+
+    /// Something went wrong handling a message, ```kind``` is what chat was told
+    pub fn chat_error(&self, kind: &'static str) {
+        *self.registry().errors.entry(kind).or_default() += 1;
+    }
+
+    /// A command's handler panicked
+    pub fn command_panicked(&self, command: &str) {
+        *self
+            .registry()
+            .panics
+            .entry(command.to_owned())
+            .or_default() += 1;
+    }
+
+    /// A command's handler took longer than its budget
+    pub fn command_over_budget(&self, command: &str) {
+        *self
+            .registry()
+            .over_budget
+            .entry(command.to_owned())
+            .or_default() += 1;
+    }
+
+    /// A command was turned away because of a cooldown, ```scope``` is ```user``` or ```command```
+    pub fn cooldown_rejected(&self, scope: &'static str) {
+        *self.registry().cooldowns.entry(scope).or_default() += 1;
+    }
+
+    /// A chat filter caught a message, ```action``` is what was done about it
+    pub fn message_filtered(&self, filter: &'static str, action: &'static str) {
+        *self
+            .registry()
+            .filtered
+            .entry((filter, action))
+            .or_default() += 1;
+    }
+
+    /// How many incoming chat messages or outbox requests are waiting to be handled. What the
+    /// bot says is sent right away, so there's no outgoing queue to measure
+    pub fn set_inbound_queue_depth(&self, queue: &'static str, depth: usize) {
+        self.registry().queues.insert(queue, depth);
+    }
+
+    /// Everything in the Prometheus text format
+    pub fn render(&self) -> String {
+        let registry = self.registry();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "mostlybot_messages_total",
+            "counter",
+            "Chat messages received",
+        );
+        let _ = writeln!(out, "mostlybot_messages_total {}", registry.messages);
+
+        header(
+            &mut out,
+            "mostlybot_commands_handled_total",
+            "counter",
+            "Command handlers run, by command and whether they succeeded",
+        );
+        for ((command, ok), count) in &registry.handled {
+            let result = if *ok { "ok" } else { "error" };
+            let _ = writeln!(
+                out,
+                "mostlybot_commands_handled_total{{command=\"{}\",result=\"{result}\"}} {count}",
+                escape(command)
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_command_duration_seconds",
+            "histogram",
+            "How long command handlers take",
+        );
+        for (command, histogram) in &registry.latency {
+            let command = escape(command);
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "mostlybot_command_duration_seconds_bucket{{command=\"{command}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "mostlybot_command_duration_seconds_bucket{{command=\"{command}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "mostlybot_command_duration_seconds_sum{{command=\"{command}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "mostlybot_command_duration_seconds_count{{command=\"{command}\"}} {}",
+                histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_command_panics_total",
+            "counter",
+            "Command handlers that panicked",
+        );
+        for (command, count) in &registry.panics {
+            let _ = writeln!(
+                out,
+                "mostlybot_command_panics_total{{command=\"{}\"}} {count}",
+                escape(command)
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_command_over_budget_total",
+            "counter",
+            "Command handlers that took longer than their time budget",
+        );
+        for (command, count) in &registry.over_budget {
+            let _ = writeln!(
+                out,
+                "mostlybot_command_over_budget_total{{command=\"{}\"}} {count}",
+                escape(command)
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_chat_errors_total",
+            "counter",
+            "Error replies, by kind",
+        );
+        for (kind, count) in &registry.errors {
+            let _ = writeln!(
+                out,
+                "mostlybot_chat_errors_total{{kind=\"{kind}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_cooldown_rejections_total",
+            "counter",
+            "Commands turned away by a cooldown, per user or per command",
+        );
+        for (scope, count) in &registry.cooldowns {
+            let _ = writeln!(
+                out,
+                "mostlybot_cooldown_rejections_total{{scope=\"{scope}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_filtered_messages_total",
+            "counter",
+            "Chat messages caught by a moderation filter, by filter and action",
+        );
+        for ((filter, action), count) in &registry.filtered {
+            let _ = writeln!(
+                out,
+                "mostlybot_filtered_messages_total{{filter=\"{filter}\",action=\"{action}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "mostlybot_inbound_queue_depth",
+            "gauge",
+            "Incoming messages waiting to be handled",
+        );
+        for (queue, depth) in &registry.queues {
+            let _ = writeln!(
+                out,
+                "mostlybot_inbound_queue_depth{{queue=\"{queue}\"}} {depth}"
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Label values can't have raw quotes, backslashes or newlines
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+        assert_eq!(metrics.last_message_age(), None);
+
+        metrics.message_received();
+        assert_eq!(metrics.messages_received(), 1);
+        metrics.command_handled("ping", true, Duration::from_millis(3));
+        metrics.command_handled("ping", true, Duration::from_millis(200));
+        metrics.command_handled("ping", false, Duration::from_secs(10));
+        metrics.command_panicked("ping");
+        metrics.command_over_budget("ping");
+        metrics.chat_error("command_cooldown");
+        metrics.cooldown_rejected("command");
+        metrics.set_inbound_queue_depth("chat", 4);
+        metrics.message_filtered("links", "delete");
+
+        let text = metrics.render();
+        for line in [
+            "mostlybot_messages_total 1",
+            "mostlybot_commands_handled_total{command=\"ping\",result=\"ok\"} 2",
+            "mostlybot_commands_handled_total{command=\"ping\",result=\"error\"} 1",
+            "mostlybot_command_duration_seconds_bucket{command=\"ping\",le=\"0.001\"} 0",
+            "mostlybot_command_duration_seconds_bucket{command=\"ping\",le=\"0.005\"} 1",
+            "mostlybot_command_duration_seconds_bucket{command=\"ping\",le=\"0.25\"} 2",
+            "mostlybot_command_duration_seconds_bucket{command=\"ping\",le=\"5\"} 2",
+            "mostlybot_command_duration_seconds_bucket{command=\"ping\",le=\"+Inf\"} 3",
+            "mostlybot_command_duration_seconds_count{command=\"ping\"} 3",
+            "mostlybot_command_panics_total{command=\"ping\"} 1",
+            "mostlybot_command_over_budget_total{command=\"ping\"} 1",
+            "mostlybot_chat_errors_total{kind=\"command_cooldown\"} 1",
+            "mostlybot_cooldown_rejections_total{scope=\"command\"} 1",
+            "mostlybot_inbound_queue_depth{queue=\"chat\"} 4",
+            "mostlybot_filtered_messages_total{filter=\"links\",action=\"delete\"} 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line:?} in\n{text}"
+            );
+        }
+
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
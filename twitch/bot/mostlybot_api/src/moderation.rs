@@ -0,0 +1,635 @@
+use crate::{
+    api::{ModAction, TwitchApiWrapper},
+    audit::{AuditEvent, RotatingFile},
+    config::{deserialize_duration, LinkFilterConfig, ModerationConfig},
+    flood::Flood,
+    metrics::Metrics,
+    role::Role,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use twitcheventsub::MessageData;
+
+/// Top level domains that count as a link without ```https://``` or ```www.``` in front.
+/// Anything else would catch half of the code talked about on stream (```main.rs```,
+/// ```node.js```, ...)
+const LINK_TLDS: [&str; 36] = [
+    "ai", "app", "be", "biz", "cc", "cf", "click", "co", "com", "de", "dev", "fm", "fr", "ga",
+    "gg", "gq", "info", "io", "link", "live", "ly", "me", "ml", "net", "online", "org", "ru",
+    "shop", "site", "store", "tk", "top", "tv", "uk", "us", "xyz",
+];
+
+/// The review log is rotated once it's this big, see [`RotatingFile`]
+const LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// How many rotated review logs are kept
+const LOG_KEEP: usize = 3;
+
+/// How many filtered messages [`Moderation::recent`] remembers
+const KEEP_RECENT: usize = 50;
+
+// Filters --------------------------------------------------------------------
+
+/// The filters a message can trip, checked in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Links,
+    Words,
+    Zalgo,
+    Caps,
+    Symbols,
+    /// The same chatter saying (nearly) the same thing over and over
+    Repeat,
+    /// Lots of chatters pasting the same thing
+    Copypasta,
+    /// One chatter sending too many messages too fast
+    Burst,
+}
+
+impl Filter {
+    /// Like in the config, e.g. ```links```
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Links => "links",
+            Filter::Words => "words",
+            Filter::Zalgo => "zalgo",
+            Filter::Caps => "caps",
+            Filter::Symbols => "symbols",
+            Filter::Repeat => "repeat",
+            Filter::Copypasta => "copypasta",
+            Filter::Burst => "burst",
+        }
+    }
+
+    /// What chat is told
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Filter::Links => "no links please, ask a moderator for a !permit",
+            Filter::Words => "watch your language",
+            Filter::Zalgo => "please don't spam weird unicode",
+            Filter::Caps => "please don't shout",
+            Filter::Symbols => "please don't spam symbols",
+            Filter::Repeat => "please don't repeat yourself",
+            Filter::Copypasta => "no copypasta please",
+            Filter::Burst => "slow down a little",
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What the bot does about a message that tripped a filter, see
+/// [`ModerationConfig::actions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Tell them off, the message stays
+    Warn,
+    /// Delete the message and tell them why
+    Delete,
+    /// Time them out, which also clears their messages
+    Timeout {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+    },
+}
+
+impl FilterAction {
+    fn name(&self) -> &'static str {
+        match self {
+            FilterAction::Warn => "warn",
+            FilterAction::Delete => "delete",
+            FilterAction::Timeout { .. } => "timeout",
+        }
+    }
+}
+
+/// The hosts of everything in ```text``` that looks like a link, lowercase and without ```www.```
+pub(crate) fn links(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '/');
+            let (explicit, rest) = match word.split_once("://") {
+                Some((_, rest)) => (true, rest),
+                None => (false, word),
+            };
+            let host = rest
+                .split(['/', ':', '?', '#'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            let (explicit, host) = match host.strip_prefix("www.") {
+                Some(host) => (true, host.to_owned()),
+                None => (explicit, host),
+            };
+
+            let labels: Vec<_> = host.split('.').collect();
+            let valid = labels.len() >= 2
+                && labels
+                    .iter()
+                    .all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'));
+            let tld = labels.last().copied().unwrap_or_default();
+            (valid && (explicit || LINK_TLDS.contains(&tld))).then_some(host)
+        })
+        .collect()
+}
+
+/// Combining marks, the stuff zalgo text is stacked out of
+fn is_combining(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// Characters that take up no space. The zero width joiner is left out, emoji need it
+fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{200B}' | '\u{200C}' | '\u{200E}' | '\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{FEFF}')
+}
+
+/// More than ```max``` combining marks on one character, or more than ```max``` invisible ones
+fn is_zalgo(text: &str, max: usize) -> bool {
+    let mut run = 0;
+    let mut invisible = 0;
+    for c in text.chars() {
+        run = if is_combining(c) { run + 1 } else { 0 };
+        if is_invisible(c) {
+            invisible += 1;
+        }
+        if run > max || invisible > max {
+            return true;
+        }
+    }
+    false
+}
+
+/// How much of ```counted``` is ```matching```, if there's at least ```min``` of it
+fn ratio(
+    text: &str,
+    min: usize,
+    counted: impl Fn(char) -> bool,
+    matching: impl Fn(char) -> bool,
+) -> Option<f64> {
+    let total = text.chars().filter(|c| counted(*c)).count();
+    let matched = text.chars().filter(|c| counted(*c) && matching(*c)).count();
+    (total > 0 && total >= min).then(|| matched as f64 / total as f64)
+}
+
+// Review log -----------------------------------------------------------------
+
+/// A message a filter caught and what was done about it, for moderators to go through after
+/// a stream. Written to ```moderation.log``` in the data dir
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModerationEvent {
+    /// Unix millis
+    pub at: u64,
+    pub message_id: String,
+    pub user_id: String,
+    pub user_name: String,
+    pub filter: String,
+    /// ```warn```, ```delete``` or ```timeout```
+    pub action: String,
+    /// How many times the chatter tripped a filter lately, this one included
+    pub offence: usize,
+    pub text: String,
+}
+
+// Service --------------------------------------------------------------------
+
+#[derive(Debug)]
+struct State {
+    /// When each chatter tripped a filter, keyed by user id
+    offences: HashMap<String, Vec<Instant>>,
+    /// Until when each login may post links
+    permits: HashMap<String, Instant>,
+    /// ```links.permit``` from the config, as of the last message
+    permit_for: Duration,
+    /// ```words.patterns``` from the config and what they compiled to, redone when they change
+    patterns: (Vec<String>, Vec<Regex>),
+    flood: Flood,
+    /// The last filtered messages, newest last
+    recent: VecDeque<ModerationEvent>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            offences: HashMap::new(),
+            permits: HashMap::new(),
+            permit_for: LinkFilterConfig::default().permit,
+            patterns: Default::default(),
+            flood: Flood::default(),
+            recent: VecDeque::new(),
+        }
+    }
+}
+
+/// The chat filters every message goes through, see [`ModerationConfig`]. Keeps track of
+/// repeat offenders, ```!permit```s and what recent chat looked like. The default doesn't
+/// write a review log. Cheap to clone, clones share the state
+#[derive(Debug, Clone, Default)]
+pub struct Moderation {
+    state: Rc<RefCell<State>>,
+    log: Option<Rc<RefCell<RotatingFile>>>,
+}
+
+impl Moderation {
+    /// ```config.log``` is relative to ```data_dir```
+    pub fn new(config: &ModerationConfig, data_dir: &Path) -> Self {
+        let log = (!config.log.as_os_str().is_empty()).then(|| {
+            let path = data_dir.join(&config.log);
+            Rc::new(RefCell::new(RotatingFile::new(
+                path,
+                LOG_MAX_BYTES,
+                LOG_KEEP,
+            )))
+        });
+
+        Self {
+            log,
+            ..Self::default()
+        }
+    }
+
+    /// The last messages the filters caught, newest first
+    pub fn recent(&self) -> Vec<ModerationEvent> {
+        self.state.borrow().recent.iter().rev().cloned().collect()
+    }
+
+    fn record(&self, event: ModerationEvent) {
+        if let Some(log) = &self.log {
+            let written = serde_json::to_string(&event)
+                .map_err(std::io::Error::from)
+                .and_then(|line| log.borrow_mut().write_line(&line));
+            if let Err(e) = written {
+                tracing::error!(target: "moderation", error = %e, "unable to write the review log");
+            }
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.recent.push_back(event);
+        if state.recent.len() > KEEP_RECENT {
+            state.recent.pop_front();
+        }
+    }
+
+    /// Lets ```login``` post links for ```links.permit```, whatever their role. Returns for
+    /// how long
+    pub fn permit(&self, login: &str) -> Duration {
+        let mut state = self.state.borrow_mut();
+        let until = Instant::now() + state.permit_for;
+        state.permits.insert(login.to_lowercase(), until);
+        state.permit_for
+    }
+
+    fn is_permitted(&self, login: &str, now: Instant) -> bool {
+        let mut state = self.state.borrow_mut();
+        state.permits.retain(|_, until| *until > now);
+        state.permits.contains_key(&login.to_lowercase())
+    }
+
+    /// The first filter ```msg``` trips on its own, whoever sent it. The flood filters need
+    /// the rest of chat too, they only run in [`Moderation::moderate`]
+    pub fn check(&self, msg: &MessageData, config: &ModerationConfig) -> Option<Filter> {
+        self.check_at(msg, config, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        msg: &MessageData,
+        config: &ModerationConfig,
+        now: Instant,
+    ) -> Option<Filter> {
+        let text = &msg.message.text;
+
+        let link_filter = &config.links;
+        if link_filter.enabled
+            && Role::of(msg) < link_filter.role
+            && !self.is_permitted(&msg.chatter.login, now)
+        {
+            let allowed = |host: &str| {
+                link_filter.allowed.iter().any(|domain| {
+                    let domain = domain.to_lowercase();
+                    host == domain || host.ends_with(&format!(".{domain}"))
+                })
+            };
+            if links(text).iter().any(|host| !allowed(host)) {
+                return Some(Filter::Links);
+            }
+        }
+
+        if self.has_banned_words(text, config) {
+            return Some(Filter::Words);
+        }
+
+        if config.zalgo.enabled && is_zalgo(text, config.zalgo.max_marks) {
+            return Some(Filter::Zalgo);
+        }
+
+        let caps = &config.caps;
+        let shouting = ratio(
+            text,
+            caps.min_length,
+            char::is_alphabetic,
+            char::is_uppercase,
+        );
+        if caps.enabled && shouting.is_some_and(|r| r > caps.max_ratio) {
+            return Some(Filter::Caps);
+        }
+
+        let symbols = &config.symbols;
+        let is_symbol = |c: char| !c.is_alphanumeric();
+        let noise = ratio(text, symbols.min_length, |c| !c.is_whitespace(), is_symbol);
+        if symbols.enabled && noise.is_some_and(|r| r > symbols.max_ratio) {
+            return Some(Filter::Symbols);
+        }
+
+        None
+    }
+
+    fn has_banned_words(&self, text: &str, config: &ModerationConfig) -> bool {
+        let words = &config.words;
+        let lower = text.to_lowercase();
+        let banned = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| words.banned.iter().any(|b| b.to_lowercase() == word));
+        if banned {
+            return true;
+        }
+
+        let mut state = self.state.borrow_mut();
+        if state.patterns.0 != words.patterns {
+            let compiled = words
+                .patterns
+                .iter()
+                .filter_map(|pattern| match Regex::new(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        tracing::warn!(target: "moderation", %pattern, error = %e, "skipping invalid pattern");
+                        None
+                    }
+                })
+                .collect();
+            state.patterns = (words.patterns.clone(), compiled);
+        }
+        state.patterns.1.iter().any(|regex| regex.is_match(text))
+    }
+
+    /// How many times ```user_id``` tripped a filter lately, this time included
+    fn offence(&self, user_id: &str, forget: Duration, now: Instant) -> usize {
+        let mut state = self.state.borrow_mut();
+        state
+            .offences
+            .retain(|_, times| times.last().is_some_and(|t| now - *t < forget));
+
+        let times = state.offences.entry(user_id.to_owned()).or_default();
+        times.retain(|t| now - *t < forget);
+        times.push(now);
+        times.len()
+    }
+
+    /// Runs the filters over ```msg``` and acts on whatever it tripped, the action escalating
+    /// with every repeat offence. Moderators are never filtered. True if the message was
+    /// acted on, and shouldn't be looked at any further
+    pub fn moderate(
+        &self,
+        msg: &MessageData,
+        config: &ModerationConfig,
+        api: &mut TwitchApiWrapper,
+        metrics: &Metrics,
+    ) -> bool {
+        // every message comes through here first, including the ```!permit``` itself
+        self.state.borrow_mut().permit_for = config.links.permit;
+
+        if !config.enabled || Role::of(msg) >= config.exempt.min(Role::Moderator) {
+            return false;
+        }
+        let now = Instant::now();
+        // flood filters see every message, even ones another filter already caught
+        let content = self.check_at(msg, config, now);
+        let flood = self.state.borrow_mut().flood.check(msg, &config.flood, now);
+        let Some(filter) = content.or(flood) else {
+            return false;
+        };
+        let Some(last) = config.actions.len().checked_sub(1) else {
+            return false;
+        };
+
+        let offence = self.offence(&msg.chatter.id, config.forget, now);
+        let action = config.actions[(offence - 1).min(last)];
+        tracing::info!(
+            target: "moderation",
+            chatter = %msg.chatter.login,
+            %filter,
+            offence,
+            action = action.name(),
+            "message filtered"
+        );
+        metrics.message_filtered(filter.name(), action.name());
+        self.record(ModerationEvent {
+            at: AuditEvent::now(),
+            message_id: msg.message_id.clone(),
+            user_id: msg.chatter.id.clone(),
+            user_name: msg.chatter.name.clone(),
+            filter: filter.name().to_owned(),
+            action: action.name().to_owned(),
+            offence,
+            text: msg.message.text.clone(),
+        });
+
+        let warning = format!("@{}, {}", msg.chatter.name, filter.reason());
+        let done = match action {
+            FilterAction::Warn => api
+                .send_chat_message_with_reply(warning, Some(msg.message_id.clone()))
+                .map(|_| ()),
+            FilterAction::Delete => api
+                .moderate(ModAction::Delete {
+                    message_id: msg.message_id.clone(),
+                })
+                .and_then(|()| api.send_chat_message(warning).map(|_| ())),
+            FilterAction::Timeout { duration } => api
+                .moderate(ModAction::Timeout {
+                    user_id: msg.chatter.id.clone(),
+                    duration,
+                    reason: format!("{} ({filter} filter)", filter.reason()),
+                })
+                .and_then(|()| api.send_chat_message(warning).map(|_| ())),
+        };
+        if let Err(e) = done {
+            tracing::error!(target: "moderation", error = ?e, "unable to act on a filtered message");
+        }
+
+        true
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::MockTwitchEventSubApi,
+        test_util::{chat_message, MESSAGE_ID},
+    };
+
+    fn message(text: &str, badges: &[&str]) -> MessageData {
+        chat_message("Chatter", text, badges)
+    }
+
+    fn config() -> ModerationConfig {
+        let mut config = ModerationConfig {
+            enabled: true,
+            ..ModerationConfig::default()
+        };
+        config.words.banned = vec!["Heck".to_owned()];
+        config.words.patterns = vec!["(?i)free\\s+followers".to_owned()];
+        config
+    }
+
+    #[test]
+    fn review_log() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-moderation-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let config = config();
+        let moderation = Moderation::new(&config, &dir);
+        for text in [
+            "hi chat",
+            "i am a spam message",
+            "i am a spam message",
+            "i am a spam message",
+        ] {
+            moderation.moderate(&message(text, &[]), &config, &mut api, &Metrics::default());
+        }
+
+        let log = std::fs::read_to_string(dir.join("moderation.jsonl")).unwrap();
+        let event: serde_json::Value = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!(event["filter"], "repeat");
+        assert_eq!(event["action"], "warn");
+        assert_eq!(event["user_name"], "Chatter");
+        assert_eq!(event["text"], "i am a spam message");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_links() {
+        assert_eq!(
+            links("see https://Example.org/a?b, www.foo.dev and clips.twitch.tv/xyz!"),
+            ["example.org", "foo.dev", "clips.twitch.tv"]
+        );
+        assert!(links("main.rs and node.js, e.g. this... or that.").is_empty());
+        assert_eq!(links("(bit.ly/abc)"), ["bit.ly"]);
+    }
+
+    #[test]
+    fn filters() {
+        let moderation = Moderation::default();
+        let config = config();
+        let check = |text: &str| moderation.check(&message(text, &[]), &config);
+
+        assert_eq!(check("hello chat, how are we doing"), None);
+        assert_eq!(check("go to evil.com now"), Some(Filter::Links));
+        assert_eq!(check("watch at twitch.tv/mostlymaxi"), None);
+        assert_eq!(check("oh HECK"), Some(Filter::Words));
+        assert_eq!(check("heckin good"), None);
+        assert_eq!(check("get FREE   followers"), Some(Filter::Words));
+        assert_eq!(
+            check("h\u{0301}\u{0302}\u{0303}\u{0304}\u{0305}i"),
+            Some(Filter::Zalgo)
+        );
+        assert_eq!(
+            check("a\u{200B}b\u{200B}c\u{200B}d\u{200B}e\u{200B}"),
+            Some(Filter::Zalgo)
+        );
+        assert_eq!(check("WHY IS THIS HAPPENING"), Some(Filter::Caps));
+        assert_eq!(check("OK LUL"), None);
+        assert_eq!(check("!!!!!!!!!!!!!!!! ??????"), Some(Filter::Symbols));
+        assert_eq!(check(":) :( hi"), None);
+
+        assert_eq!(
+            moderation.check(&message("evil.com", &["vip"]), &config),
+            None
+        );
+
+        assert_eq!(moderation.permit("Chatter"), Duration::from_secs(120));
+        assert_eq!(check("go to evil.com now"), None);
+    }
+
+    #[test]
+    fn escalates() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let moderation = Moderation::default();
+        let metrics = Metrics::default();
+        let config = config();
+
+        for _ in 0..4 {
+            assert!(moderation.moderate(&message("oh heck", &[]), &config, &mut api, &metrics));
+        }
+        assert!(!moderation.moderate(&message("oh well", &[]), &config, &mut api, &metrics));
+
+        let msg = message("oh heck", &["moderator"]);
+        assert!(!moderation.moderate(&msg, &config, &mut api, &metrics));
+
+        let mock = api.as_mock().unwrap();
+        assert_eq!(mock.sent_messages(), ["@Chatter, watch your language"; 4]);
+        let timeout = ModAction::Timeout {
+            user_id: "Chatter".to_owned(),
+            duration: Duration::from_secs(600),
+            reason: "watch your language (words filter)".to_owned(),
+        };
+        assert_eq!(
+            mock.mod_actions(),
+            [
+                ModAction::Delete {
+                    message_id: MESSAGE_ID.to_owned()
+                },
+                timeout.clone(),
+                timeout,
+            ]
+        );
+
+        let recent = moderation.recent();
+        assert_eq!(recent.len(), 4);
+        assert_eq!(
+            (recent[0].offence, recent[0].action.as_str()),
+            (4, "timeout")
+        );
+        assert_eq!(
+            (recent[3].filter.as_str(), recent[3].action.as_str()),
+            ("words", "warn")
+        );
+
+        // old offences are forgotten
+        assert_eq!(
+            moderation.offence(
+                "Chatter",
+                Duration::from_secs(1),
+                Instant::now() + Duration::from_secs(5)
+            ),
+            1
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use crate::{
+    api::{ModAction, PlatformError, Trigger, TwitchApiWrapper},
+    config::{deserialize_duration, OutboxConfig, OutboxServiceConfig},
+    spam::RateLimiter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fmt, time::Duration};
+
+/// Twitch won't take anything longer
+pub(crate) const MAX_MESSAGE_LEN: usize = 500;
+
+/// Twitch won't time anyone out for longer than two weeks
+const MAX_TIMEOUT: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+// Requests -------------------------------------------------------------------
+
+/// The kinds of actions a service can be allowed, see [`OutboxServiceConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxKind {
+    Say,
+    Reply,
+    Announce,
+    Timeout,
+    Delete,
+}
+
+impl fmt::Display for OutboxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutboxKind::Say => "say",
+            OutboxKind::Reply => "reply",
+            OutboxKind::Announce => "announce",
+            OutboxKind::Timeout => "timeout",
+            OutboxKind::Delete => "delete",
+        })
+    }
+}
+
+/// Something another service wants the bot to do in chat
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum OutboxAction {
+    Say {
+        message: String,
+    },
+    Reply {
+        message: String,
+        parent_id: String,
+    },
+    Announce {
+        message: String,
+    },
+    Timeout {
+        user_id: String,
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        #[serde(default)]
+        reason: String,
+    },
+    Delete {
+        message_id: String,
+    },
+}
+
+impl OutboxAction {
+    pub fn kind(&self) -> OutboxKind {
+        match self {
+            OutboxAction::Say { .. } => OutboxKind::Say,
+            OutboxAction::Reply { .. } => OutboxKind::Reply,
+            OutboxAction::Announce { .. } => OutboxKind::Announce,
+            OutboxAction::Timeout { .. } => OutboxKind::Timeout,
+            OutboxAction::Delete { .. } => OutboxKind::Delete,
+        }
+    }
+
+    /// Catches what twitch would refuse anyway, before it counts towards the rate limit
+    fn validate(&self) -> Result<(), OutboxError> {
+        match self {
+            OutboxAction::Say { message }
+            | OutboxAction::Reply { message, .. }
+            | OutboxAction::Announce { message } => {
+                if message.trim().is_empty() {
+                    return Err(OutboxError::Invalid("empty message".to_owned()));
+                }
+                if message.chars().count() > MAX_MESSAGE_LEN {
+                    return Err(OutboxError::Invalid(format!(
+                        "message is longer than {MAX_MESSAGE_LEN} characters"
+                    )));
+                }
+            }
+            OutboxAction::Timeout { duration, .. } => {
+                if duration.is_zero() || *duration > MAX_TIMEOUT {
+                    return Err(OutboxError::Invalid(format!(
+                        "timeout has to be between 1s and {MAX_TIMEOUT:?}"
+                    )));
+                }
+            }
+            OutboxAction::Delete { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// One message on the outbox topic, like
+///
+/// ```json
+/// {"id": "42", "service": "points", "action": "say", "message": "someone redeemed hydrate"}
+/// {"id": "43", "service": "points", "action": "timeout", "user_id": "123", "duration": "10m", "reason": "redeemed a timeout"}
+/// ```
+///
+/// ```id``` is whatever the service wants back in the result to match it up
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OutboxRequest {
+    pub id: String,
+    /// Who the request says it's from. Franz doesn't tell the bot who produced a message, so
+    /// this is taken on trust: anything that can write to the outbox topic can claim to be any
+    /// service. It keeps well-behaved services apart, it doesn't stop a malicious one
+    pub service: String,
+    #[serde(flatten)]
+    pub action: OutboxAction,
+}
+
+impl OutboxRequest {
+    pub fn parse(raw: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(raw)
+    }
+}
+
+// Results --------------------------------------------------------------------
+
+#[derive(Debug)]
+pub enum OutboxError {
+    /// Not a request at all
+    Malformed(String),
+    UnknownService,
+    NotAllowed(OutboxKind),
+    Invalid(String),
+    /// The service used up its rate limit, try again after this long
+    RateLimited(Duration),
+    Platform(PlatformError),
+}
+
+impl fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(err) => write!(f, "malformed request: {err}"),
+            Self::UnknownService => write!(f, "unknown service"),
+            Self::NotAllowed(kind) => write!(f, "service isn't allowed to {kind}"),
+            Self::Invalid(err) => write!(f, "{err}"),
+            Self::RateLimited(wait) => write!(f, "rate limited, try again in {wait:.1?}"),
+            Self::Platform(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for OutboxError {}
+
+/// What the bot publishes to the results topic for every request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutboxResult {
+    /// ```None``` only if the request was too broken to tell
+    pub id: Option<String>,
+    pub service: Option<String>,
+    pub action: Option<OutboxKind>,
+    pub ok: bool,
+    /// Id of the chat message the bot sent, for say and reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl OutboxResult {
+    /// The result for something on the topic that isn't a request, with the ```id``` and
+    /// ```service``` if they're there so the sender still hears back
+    pub fn malformed(raw: &[u8], error: &serde_json::Error) -> Self {
+        let value: Value = serde_json::from_slice(raw).unwrap_or_default();
+        let field = |name: &str| value.get(name).and_then(Value::as_str).map(str::to_owned);
+
+        let error = OutboxError::Malformed(error.to_string());
+        tracing::warn!(target: "outbox", id = ?field("id"), service = ?field("service"), %error, "rejected");
+
+        Self {
+            id: field("id"),
+            service: field("service"),
+            action: None,
+            ok: false,
+            message_id: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("outbox results serialize")
+    }
+}
+
+// Gateway --------------------------------------------------------------------
+
+/// Lets other services talk in and moderate chat through the bot. Every request is checked
+/// against the service's allowed actions and rate limit and logged (target ```outbox```)
+/// whether it went through or not. The service is whatever the request claims, see
+/// [`OutboxRequest::service`]
+pub struct Outbox {
+    services: HashMap<String, OutboxServiceConfig>,
+    limiter: RateLimiter<String>,
+}
+
+impl Outbox {
+    pub fn new(config: &OutboxConfig) -> Self {
+        Self {
+            services: config.services.clone(),
+            limiter: RateLimiter::new(config.rate),
+        }
+    }
+
+    /// Swaps in new services and limits, keeping track of what's been used so far
+    pub fn configure(&mut self, config: &OutboxConfig) {
+        self.services = config.services.clone();
+        self.limiter.default_limit = config.rate;
+    }
+
+    /// Checks and carries out one request
+    pub fn handle(&mut self, request: &OutboxRequest, api: &mut TwitchApiWrapper) -> OutboxResult {
+        let kind = request.action.kind();
+        let outcome = self.check(request).and_then(|()| {
+            api.handling(Trigger::Outbox {
+                service: &request.service,
+                id: &request.id,
+            });
+            execute(&request.action, api)
+        });
+
+        match &outcome {
+            Ok(_) => tracing::info!(
+                target: "outbox",
+                id = %request.id,
+                service = %request.service,
+                action = %kind,
+                "done"
+            ),
+            Err(error) => tracing::warn!(
+                target: "outbox",
+                id = %request.id,
+                service = %request.service,
+                action = %kind,
+                %error,
+                "rejected"
+            ),
+        }
+
+        let (message_id, error) = match outcome {
+            Ok(message_id) => (message_id, None),
+            Err(error) => (None, Some(error.to_string())),
+        };
+        OutboxResult {
+            id: Some(request.id.clone()),
+            service: Some(request.service.clone()),
+            action: Some(kind),
+            ok: error.is_none(),
+            message_id,
+            error,
+        }
+    }
+
+    fn check(&mut self, request: &OutboxRequest) -> Result<(), OutboxError> {
+        let Some(service) = self.services.get(&request.service) else {
+            return Err(OutboxError::UnknownService);
+        };
+
+        let kind = request.action.kind();
+        if !service.actions.contains(&kind) {
+            return Err(OutboxError::NotAllowed(kind));
+        }
+        request.action.validate()?;
+
+        match self
+            .limiter
+            .enforce_limit(request.service.clone(), service.rate.as_ref())
+        {
+            Some(wait) => Err(OutboxError::RateLimited(wait)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the id of the sent message, if there is one
+fn execute(
+    action: &OutboxAction,
+    api: &mut TwitchApiWrapper,
+) -> Result<Option<String>, OutboxError> {
+    let sent = match action {
+        OutboxAction::Say { message } => api.send_chat_message(message.as_str()).map(Some),
+        OutboxAction::Reply { message, parent_id } => api
+            .send_chat_message_with_reply(message.as_str(), Some(parent_id.as_str()))
+            .map(Some),
+        OutboxAction::Announce { message } => api.announce(message.as_str()).map(|()| None),
+        OutboxAction::Timeout {
+            user_id,
+            duration,
+            reason,
+        } => api
+            .moderate(ModAction::Timeout {
+                user_id: user_id.clone(),
+                duration: *duration,
+                reason: reason.clone(),
+            })
+            .map(|()| None),
+        OutboxAction::Delete { message_id } => api
+            .moderate(ModAction::Delete {
+                message_id: message_id.clone(),
+            })
+            .map(|()| None),
+    };
+
+    sent.map_err(OutboxError::Platform)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::MockTwitchEventSubApi, spam::RateLimit};
+
+    fn outbox() -> Outbox {
+        let config: OutboxConfig = toml::from_str(
+            r#"
+            rate = { max = 2, per = "1m" }
+
+            [services.points]
+            actions = ["say", "timeout"]
+            "#,
+        )
+        .unwrap();
+        Outbox::new(&config)
+    }
+
+    fn request(json: &str) -> OutboxRequest {
+        OutboxRequest::parse(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            request(
+                r#"{"id":"1","service":"points","action":"timeout","user_id":"123","duration":"10m"}"#
+            ),
+            OutboxRequest {
+                id: "1".to_owned(),
+                service: "points".to_owned(),
+                action: OutboxAction::Timeout {
+                    user_id: "123".to_owned(),
+                    duration: Duration::from_secs(600),
+                    reason: String::new(),
+                },
+            }
+        );
+
+        assert!(OutboxRequest::parse(br#"{"id":"1","service":"points","action":"ban"}"#).is_err());
+        assert!(OutboxRequest::parse(br#"{"id":"1","action":"say","message":"hi"}"#).is_err());
+    }
+
+    #[test]
+    fn allowlist_and_rate_limit() {
+        let mut outbox = outbox();
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let mut handle = |json: &str| outbox.handle(&request(json), &mut api).error;
+
+        assert_eq!(
+            handle(r#"{"id":"1","service":"points","action":"say","message":"hi"}"#),
+            None
+        );
+        assert_eq!(
+            handle(r#"{"id":"2","service":"points","action":"announce","message":"hi"}"#).unwrap(),
+            "service isn't allowed to announce"
+        );
+        assert_eq!(
+            handle(r#"{"id":"3","service":"chess","action":"say","message":"hi"}"#).unwrap(),
+            "unknown service"
+        );
+        assert_eq!(
+            handle(r#"{"id":"4","service":"points","action":"say","message":" "}"#).unwrap(),
+            "empty message"
+        );
+        assert_eq!(
+            handle(
+                r#"{"id":"5","service":"points","action":"timeout","user_id":"123","duration":"1m"}"#
+            ),
+            None
+        );
+        // rejected requests above didn't count, these two did
+        assert!(
+            handle(r#"{"id":"6","service":"points","action":"say","message":"hi"}"#)
+                .unwrap()
+                .starts_with("rate limited")
+        );
+
+        let mock = api.as_mock().unwrap();
+        assert_eq!(mock.sent_messages(), ["hi"]);
+        assert_eq!(mock.mod_actions().len(), 1);
+
+        // a service with its own limit
+        let mut config = OutboxConfig::default();
+        config.services.insert(
+            "points".to_owned(),
+            OutboxServiceConfig {
+                actions: vec![OutboxKind::Say],
+                rate: Some(RateLimit::new(0, Duration::ZERO)),
+            },
+        );
+        outbox.configure(&config);
+        let result = outbox.handle(
+            &request(r#"{"id":"7","service":"points","action":"say","message":"hi"}"#),
+            &mut api,
+        );
+        assert!(result.ok);
+    }
+
+    #[test]
+    fn results() {
+        let mut outbox = outbox();
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+
+        let result = outbox.handle(
+            &request(r#"{"id":"1","service":"points","action":"say","message":"hi"}"#),
+            &mut api,
+        );
+        assert_eq!(
+            result.to_json(),
+            r#"{"id":"1","service":"points","action":"say","ok":true,"message_id":"mock-1"}"#
+        );
+
+        let raw = br#"{"id":"2","service":"points","action":"ban"}"#;
+        let err = OutboxRequest::parse(raw).unwrap_err();
+        let result = OutboxResult::malformed(raw, &err);
+        assert_eq!(result.id.as_deref(), Some("2"));
+        assert_eq!(result.service.as_deref(), Some("points"));
+        assert!(!result.ok);
+
+        let result = OutboxResult::malformed(b"not json", &err);
+        assert_eq!(result.id, None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{command::CommandMap, config::Config, spam::Spam};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
+
+/// What asked for the config to be reloaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadTrigger {
+    /// ```SIGHUP```
+    Signal,
+    /// The config file was modified
+    FileChanged,
+    /// ```!reload``` in chat, the outcome is sent as a reply to this message
+    Chat { message_id: String },
+}
+
+/// Asks the main loop to reload the config, between messages and without waiting for the
+/// next one, see [`ReloadHandle::requested`].
+///
+/// Clones share the same request, and asking again before the reload happens
+/// just replaces the trigger.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadHandle {
+    pending: Arc<Mutex<Option<ReloadTrigger>>>,
+    wake: Arc<Notify>,
+}
+
+impl ReloadHandle {
+    pub fn request(&self, trigger: ReloadTrigger) {
+        tracing::info!(?trigger, "config reload requested");
+        *self.pending.lock().unwrap() = Some(trigger);
+        self.wake.notify_one();
+    }
+
+    /// Resolves once a reload has been requested, right away if one already was. The request
+    /// may have been taken in the meantime, so [`ReloadHandle::take`] can still come up empty
+    pub async fn requested(&self) {
+        self.wake.notified().await;
+    }
+
+    /// The pending request if there is one, clearing it
+    pub fn take(&self) -> Option<ReloadTrigger> {
+        self.pending.lock().unwrap().take()
+    }
+}
+
+/// Swaps in ```new``` as the live config, updating the command map and spam limits
+/// to match. Nothing changes if the new config doesn't validate.
+pub fn apply_config(
+    new: Config,
+    config: &mut Config,
+    cmds: &mut CommandMap,
+    spam: &mut Spam,
+) -> Result<(), Vec<String>> {
+    let problems = new.validate(cmds);
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    cmds.set_responses(&new.responses);
+    cmds.configure(&new.commands);
+    spam.set_limits(&new.spam);
+    *config = new;
+
+    Ok(())
+}
+
+/// Reads the config at ```path``` and applies it with [`apply_config`]
+pub fn load_and_apply(
+    path: &Path,
+    config: &mut Config,
+    cmds: &mut CommandMap,
+    spam: &mut Spam,
+) -> Result<(), Vec<String>> {
+    let new = Config::load(path).map_err(|e| vec![format!("{e:#}")])?;
+    apply_config(new, config, cmds, spam)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        api::TwitchApiWrapper, command::ChatCommand, context::CommandContext, error::CommandResult,
+    };
+
+    struct Ping;
+
+    impl ChatCommand for Ping {
+        fn new() -> Self {
+            Self
+        }
+
+        fn names() -> Vec<String> {
+            vec!["ping".to_owned()]
+        }
+
+        fn handle(&mut self, _: &mut TwitchApiWrapper, _: &CommandContext) -> CommandResult {
+            Ok(())
+        }
+
+        fn help(&self) -> String {
+            "usage: !ping".to_owned()
+        }
+    }
+
+    fn config(toml: &str) -> Config {
+        let mut config = Config::from_toml(toml).unwrap();
+        config.franz.broker = "localhost:8085".to_owned();
+        config.twitch.bot_id = "1124612654".to_owned();
+        config
+    }
+
+    #[test]
+    fn handle_keeps_latest_request() {
+        let handle = ReloadHandle::default();
+        handle.clone().request(ReloadTrigger::Signal);
+        handle.request(ReloadTrigger::FileChanged);
+
+        assert_eq!(handle.take(), Some(ReloadTrigger::FileChanged));
+        assert_eq!(handle.take(), None);
+    }
+
+    #[test]
+    fn apply_valid_config() {
+        let mut cmds = CommandMap::new();
+        cmds.insert(Ping::new());
+        let mut spam = Spam::default();
+        let mut live = config("");
+
+        let new = config(
+            "[commands.ping]\nenabled = false\n[responses]\ndiscord = \"join the discord!\"",
+        );
+        apply_config(new, &mut live, &mut cmds, &mut spam).unwrap();
+
+        assert!(!cmds.get("ping").unwrap().config().enabled);
+        assert!(cmds.get("discord").is_some());
+        assert!(live.responses.contains_key("discord"));
+
+        // responses that are gone from the config are gone from chat
+        apply_config(config(""), &mut live, &mut cmds, &mut spam).unwrap();
+        assert!(cmds.get("ping").unwrap().config().enabled);
+        assert!(cmds.get("discord").is_none());
+    }
+
+    #[test]
+    fn apply_invalid_config() {
+        let mut cmds = CommandMap::new();
+        cmds.insert(Ping::new());
+        let mut spam = Spam::default();
+        let mut live = config("[commands.ping]\nenabled = false");
+        apply_config(live.clone(), &mut live, &mut cmds, &mut spam).unwrap();
+
+        let new = config("[chat]\nprefixes = []\n[commands.ping]\nenabled = true");
+        let problems = apply_config(new, &mut live, &mut cmds, &mut spam).unwrap_err();
+
+        assert_eq!(
+            problems,
+            ["chat.prefixes is empty, nothing would be a command"]
+        );
+        assert_eq!(live.chat.prefixes, ['!']);
+        assert!(!cmds.get("ping").unwrap().config().enabled);
+    }
+}
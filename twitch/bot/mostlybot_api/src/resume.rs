@@ -0,0 +1,199 @@
+use crate::state::write_atomic;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The cursor is saved after this many messages...
+const SAVE_EVERY: u32 = 50;
+
+/// ...or once this long has passed since it was last saved, whichever comes first
+const SAVE_AFTER: Duration = Duration::from_secs(5);
+
+/// How many message ids are kept to recognise messages the broker hands out again
+const RECENT_IDS: usize = 200;
+
+/// The last chat message the bot got to, kept across restarts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatPosition {
+    /// Offset of the next message to read from the chat topic
+    pub offset: u64,
+    /// Ids of the last messages read, newest last, in case the broker hands them out again
+    pub recent: VecDeque<String>,
+}
+
+/// Where the position in a topic lives inside the data dir, ```name``` is ```"chat"``` or
+/// ```"outbox"```
+pub fn position_path(data_dir: &Path, name: &str) -> PathBuf {
+    data_dir.join(format!("{name}_position.json"))
+}
+
+/// Keeps track of where the bot is in the chat topic (or the outbox) so a restart picks up
+/// where the last run stopped instead of at the start of the topic. It's saved every
+/// ```SAVE_EVERY``` messages or ```SAVE_AFTER```, and with [`ChatCursor::save`] on shutdown,
+/// so a crash can replay a few seconds of messages
+#[derive(Debug)]
+pub struct ChatCursor {
+    path: PathBuf,
+    position: ChatPosition,
+    /// messages since the last save
+    unsaved: u32,
+    last_saved: Instant,
+}
+
+impl ChatCursor {
+    /// Reads the saved position, no file means the bot never ran here and starts at 0
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let position = if path.exists() {
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            serde_json::from_str(&json).with_context(|| format!("parsing {}", path.display()))?
+        } else {
+            ChatPosition::default()
+        };
+
+        Ok(Self {
+            path,
+            position,
+            unsaved: 0,
+            last_saved: Instant::now(),
+        })
+    }
+
+    /// Offset to start the consumer at
+    pub fn offset(&self) -> u64 {
+        self.position.offset
+    }
+
+    /// True if this is one of the last messages read, by this run or a saved one
+    pub fn seen(&self, message_id: &str) -> bool {
+        self.position.recent.iter().any(|id| id == message_id)
+    }
+
+    /// Moves past one message, saving if it's time to. Call it before handling the message
+    pub fn advance(&mut self, message_id: Option<&str>) -> anyhow::Result<()> {
+        self.position.offset += 1;
+        if let Some(message_id) = message_id {
+            if self.position.recent.len() >= RECENT_IDS {
+                self.position.recent.pop_front();
+            }
+            self.position.recent.push_back(message_id.to_owned());
+        }
+
+        self.unsaved += 1;
+        if self.unsaved >= SAVE_EVERY || self.last_saved.elapsed() >= SAVE_AFTER {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Saves the position now, if anything changed since the last save
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        if self.unsaved == 0 {
+            return Ok(());
+        }
+
+        let json = serde_json::to_string(&self.position)?;
+        write_atomic(&self.path, json)?;
+        self.unsaved = 0;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+}
+
+/// How old a raw chat message is, from the ```received_at``` (unix millis) the data collector
+/// adds. Messages from before it did that have no age
+pub fn message_age(raw: &str, now: SystemTime) -> Option<Duration> {
+    #[derive(Deserialize)]
+    struct Stamp {
+        received_at: Option<u64>,
+    }
+
+    let received_at = serde_json::from_str::<Stamp>(raw).ok()?.received_at?;
+    let received_at = UNIX_EPOCH + Duration::from_millis(received_at);
+    // a clock that's a little behind the collector's counts as brand new
+    Some(now.duration_since(received_at).unwrap_or_default())
+}
+
+/// True if the message is too old to answer, a ```max_age``` of zero never skips anything
+pub fn is_stale(raw: &str, max_age: Duration, now: SystemTime) -> bool {
+    !max_age.is_zero() && message_age(raw, now).is_some_and(|age| age > max_age)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-cursor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = position_path(&dir, "chat");
+
+        let mut cursor = ChatCursor::load(path.clone()).unwrap();
+        assert_eq!(cursor.offset(), 0);
+        assert!(!cursor.seen("a"));
+
+        cursor.advance(Some("a")).unwrap();
+        cursor.advance(None).unwrap();
+        cursor.advance(Some("c")).unwrap();
+        assert!(cursor.seen("a"));
+
+        // not saved yet, only a shutdown or enough messages do that
+        assert_eq!(ChatCursor::load(path.clone()).unwrap().offset(), 0);
+        cursor.save().unwrap();
+
+        // what a restart would see
+        let mut cursor = ChatCursor::load(path.clone()).unwrap();
+        assert_eq!(cursor.offset(), 3);
+        assert!(cursor.seen("c"));
+        assert!(cursor.seen("a"));
+        assert!(!cursor.seen("b"));
+
+        for i in 0..SAVE_EVERY {
+            cursor.advance(Some(&i.to_string())).unwrap();
+        }
+        assert_eq!(
+            ChatCursor::load(path).unwrap().offset(),
+            3 + u64::from(SAVE_EVERY)
+        );
+
+        // only the last few ids are kept
+        for i in 0..RECENT_IDS {
+            cursor.advance(Some(&format!("x{i}"))).unwrap();
+        }
+        assert!(!cursor.seen("c"));
+        assert!(cursor.seen(&format!("x{}", RECENT_IDS - 1)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_messages() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let sent = |secs_ago: u64| {
+            let millis = (1_000_000 - secs_ago) * 1000;
+            format!(r#"{{"message_id":"a","received_at":{millis}}}"#)
+        };
+        let max_age = Duration::from_secs(120);
+
+        assert_eq!(message_age(&sent(30), now), Some(Duration::from_secs(30)));
+        assert!(!is_stale(&sent(30), max_age, now));
+        assert!(is_stale(&sent(3600), max_age, now));
+        assert!(!is_stale(&sent(3600), Duration::ZERO, now));
+
+        // no timestamp, no way to tell
+        assert_eq!(message_age(r#"{"message_id":"a"}"#, now), None);
+        assert!(!is_stale(r#"{"message_id":"a"}"#, max_age, now));
+
+        // from the future
+        let ahead = format!(r#"{{"received_at":{}}}"#, 1_000_005 * 1000);
+        assert_eq!(message_age(&ahead, now), Some(Duration::ZERO));
+    }
+}
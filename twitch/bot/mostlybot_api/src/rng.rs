@@ -0,0 +1,87 @@
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// Shared random number generator handed to commands through their context.
+///
+/// Commands should use this instead of `rand::thread_rng()` so that tests (and
+/// simulations) can seed it and get the same output every time.
+#[derive(Clone)]
+pub struct BotRng {
+    inner: Rc<RefCell<StdRng>>,
+}
+
+impl BotRng {
+    /// Seeds the generator from the operating system, used by the live bot
+    pub fn from_entropy() -> Self {
+        Self::new(StdRng::from_entropy())
+    }
+
+    /// Seeds the generator with a fixed value for reproducible output
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+
+    fn new(rng: StdRng) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(rng)),
+        }
+    }
+}
+
+impl Default for BotRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl fmt::Debug for BotRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BotRng")
+    }
+}
+
+impl RngCore for BotRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.borrow_mut().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.inner.borrow_mut().try_fill_bytes(dest)
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::BotRng;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_rngs_match() {
+        let mut a = BotRng::seed_from_u64(69);
+        let mut b = BotRng::seed_from_u64(69);
+
+        for _ in 0..16 {
+            assert_eq!(a.gen::<u64>(), b.gen::<u64>());
+        }
+    }
+
+    #[test]
+    fn clones_share_state() {
+        let mut a = BotRng::seed_from_u64(420);
+        let mut b = a.clone();
+        let mut reference = BotRng::seed_from_u64(420);
+
+        assert_eq!(a.gen::<u64>(), reference.gen::<u64>());
+        assert_eq!(b.gen::<u64>(), reference.gen::<u64>());
+    }
+}
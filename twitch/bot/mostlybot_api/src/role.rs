@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use std::fmt;
+use twitcheventsub::MessageData;
+
+/// Who is allowed to run a command, ordered from least to most trusted.
+///
+/// A chatter can run any command at or below their own role, so a
+/// moderator can run ```Role::Vip``` commands but not ```Role::Broadcaster``` ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl Role {
+    /// The highest role the chatter who sent the message has
+    pub fn of(msg: &MessageData) -> Self {
+        let has_badge = |set_id: &str| msg.badges.iter().any(|b| b.set_id == set_id);
+
+        let is_broadcaster = !msg.chatter.id.is_empty() && msg.chatter.id == msg.broadcaster.id;
+        if is_broadcaster || has_badge("broadcaster") {
+            Self::Broadcaster
+        } else if msg.moderator || has_badge("moderator") {
+            Self::Moderator
+        } else if has_badge("vip") {
+            Self::Vip
+        } else if has_badge("subscriber") || has_badge("founder") {
+            Self::Subscriber
+        } else {
+            Self::Everyone
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Everyone => "everyone",
+            Self::Subscriber => "subscriber",
+            Self::Vip => "vip",
+            Self::Moderator => "moderator",
+            Self::Broadcaster => "broadcaster",
+        };
+        f.write_str(name)
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{chat_message, BROADCASTER_ID};
+
+    fn message(chatter_id: &str, badges: &[&str]) -> MessageData {
+        chat_message(chatter_id, "!ping", badges)
+    }
+
+    #[test]
+    fn roles_from_badges() {
+        assert_eq!(Role::of(&message("1", &[])), Role::Everyone);
+        assert_eq!(Role::of(&message("1", &["founder"])), Role::Subscriber);
+        assert_eq!(Role::of(&message("1", &["vip"])), Role::Vip);
+        assert_eq!(
+            Role::of(&message("1", &["subscriber", "moderator"])),
+            Role::Moderator
+        );
+        assert_eq!(Role::of(&message(BROADCASTER_ID, &[])), Role::Broadcaster);
+        assert_eq!(Role::of(&message("1", &["broadcaster"])), Role::Broadcaster);
+    }
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(Role::Everyone < Role::Subscriber);
+        assert!(Role::Moderator < Role::Broadcaster);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    config::{Config, OutputConfig},
+    moderation::links,
+    outbox::MAX_MESSAGE_LEN,
+};
+
+/// What chat gets instead of a message with a blocked word in it
+const REFUSE_BLOCKED: &str = "sorry, I'd rather not say that";
+
+/// What chat gets instead of a message that repeats too much of what a chatter said
+const REFUSE_TOO_LONG: &str = "sorry, that's too long for me to repeat";
+
+/// Shown where a link a chatter got the bot to repeat used to be
+const LINK_REMOVED: &str = "<link removed>";
+
+/// Lowercase words separated by single spaces, with a space on either end so whole words and
+/// phrases can be found with ```contains```: ```"Oh, don't!"``` becomes ```" oh don t "```
+fn normalize(text: &str) -> String {
+    let words: Vec<_> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    format!(" {} ", words.join(" ").to_lowercase())
+}
+
+/// The longest stretch of characters ```a``` and ```b``` have in common. Both are cut off at
+/// the longest message twitch takes, nothing past that could be repeated anyway
+fn longest_common(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().take(MAX_MESSAGE_LEN).collect();
+    // one row, walked backwards so each cell still sees the previous row's value on its left
+    let mut row = vec![0; b.len() + 1];
+    let mut longest = 0;
+
+    for x in a.chars().take(MAX_MESSAGE_LEN) {
+        for (j, y) in b.iter().enumerate().rev() {
+            row[j + 1] = if x == *y { row[j] + 1 } else { 0 };
+            longest = longest.max(row[j + 1]);
+        }
+    }
+
+    longest
+}
+
+/// Checks everything the bot is about to say, see [`OutputConfig`]. The
+/// [`TwitchApiWrapper`](crate::TwitchApiWrapper) runs every message through it, so commands
+/// that repeat what chat said don't have to
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    config: OutputConfig,
+    /// ```moderation.links.allowed```, links to these are never removed
+    allowed: Vec<String>,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::new(&Config::default())
+    }
+}
+
+impl Sanitizer {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.output.clone(),
+            allowed: config.moderation.links.allowed.clone(),
+        }
+    }
+
+    /// ```message``` as it's safe to send, or the polite refusal to send instead. ```echo``` is
+    /// whatever chatter's message the bot is answering, empty if it's not answering anyone
+    pub fn sanitize(&self, message: &str, echo: &str) -> Result<String, &'static str> {
+        let config = &self.config;
+
+        // entries are normalized the same way, so "heck no" and "don't" match too
+        let normalized = normalize(message);
+        let blocked = config
+            .blocked
+            .iter()
+            .map(|entry| normalize(entry))
+            .any(|entry| entry.trim() != "" && normalized.contains(&entry));
+        if blocked {
+            return Err(REFUSE_BLOCKED);
+        }
+
+        if config.max_echo > 0 && longest_common(message, echo) > config.max_echo {
+            return Err(REFUSE_TOO_LONG);
+        }
+
+        let mut message = message.to_owned();
+        if config.strip_links {
+            let echoed = links(echo);
+            let allowed = |host: &str| {
+                self.allowed.iter().any(|domain| {
+                    let domain = domain.to_lowercase();
+                    host == domain || host.ends_with(&format!(".{domain}"))
+                })
+            };
+            let words: Vec<_> = message
+                .split(' ')
+                .map(|word| {
+                    let strip = links(word)
+                        .iter()
+                        .any(|host| echoed.contains(host) && !allowed(host));
+                    if strip {
+                        LINK_REMOVED
+                    } else {
+                        word
+                    }
+                })
+                .collect();
+            message = words.join(" ");
+        }
+
+        if config.neutralize_commands {
+            message = message
+                .trim_start_matches(|c: char| c == '/' || c == '.' || c.is_whitespace())
+                .to_owned();
+        }
+
+        Ok(message)
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sanitizes() {
+        let mut config = Config::default();
+        config.output.blocked = vec![
+            "Heck".to_owned(),
+            "shut up".to_owned(),
+            "don't".to_owned(),
+            "-".to_owned(),
+        ];
+        config.output.max_echo = 20;
+        let sanitizer = Sanitizer::new(&config);
+        let sanitize = |message: &str, echo: &str| sanitizer.sanitize(message, echo);
+
+        assert_eq!(sanitize("oh HECK!", ""), Err(REFUSE_BLOCKED));
+        assert_eq!(sanitize("heckin good", "").as_deref(), Ok("heckin good"));
+        assert_eq!(sanitize("please SHUT  UP", ""), Err(REFUSE_BLOCKED));
+        assert_eq!(sanitize("shut-up", ""), Err(REFUSE_BLOCKED));
+        assert_eq!(
+            sanitize("shut upstairs", "").as_deref(),
+            Ok("shut upstairs")
+        );
+        assert_eq!(sanitize("Don't!", ""), Err(REFUSE_BLOCKED));
+        assert_eq!(sanitize("a - b", "").as_deref(), Ok("a - b"));
+        assert_eq!(
+            sanitize("/ban mostlymaxi has been banned", "!ban /ban mostlymaxi").as_deref(),
+            Ok("ban mostlymaxi has been banned")
+        );
+        assert_eq!(sanitize(" ..timeout me", "").as_deref(), Ok("timeout me"));
+        assert_eq!(
+            sanitize(
+                "aaaaaaaaaaaaaaaaaaaaaaaaa",
+                "!uwu aaaaaaaaaaaaaaaaaaaaaaaaa"
+            ),
+            Err(REFUSE_TOO_LONG)
+        );
+
+        // only links a chatter got the bot to repeat are removed
+        assert_eq!(
+            sanitize("evil.com has been banned", "!ban evil.com").as_deref(),
+            Ok("<link removed> has been banned")
+        );
+        assert_eq!(
+            sanitize("join us at https://discord.gg/abc", "!discord").as_deref(),
+            Ok("join us at https://discord.gg/abc")
+        );
+        assert_eq!(
+            sanitize("twitch.tv/mostlymaxi is cool", "!ban twitch.tv/mostlymaxi").as_deref(),
+            Ok("twitch.tv/mostlymaxi is cool")
+        );
+
+        let defaults = Sanitizer::default();
+        assert_eq!(
+            defaults.sanitize("/me dances", "").as_deref(),
+            Ok("me dances")
+        );
+        assert_eq!(longest_common("abcdef", "xxcdeyy"), 3);
+        assert_eq!(longest_common("abcab", "cabcab"), 5);
+        assert_eq!(longest_common("aaaa", "aa"), 2);
+        let long = "a".repeat(2000);
+        assert_eq!(longest_common(&long, &long), MAX_MESSAGE_LEN);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// How many of its messages the bot remembers
+const KEEP: usize = 100;
+
+/// A message the bot sent, and who made it send it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentMessage {
+    pub id: String,
+    pub user_id: String,
+    pub user_login: String,
+    /// Whichever of the command's names was used
+    pub command: String,
+}
+
+/// The bot's last messages that came from a command, error replies included, so moderators
+/// can take them back with ```!undo```. Cheap to clone, clones share the messages
+#[derive(Debug, Clone, Default)]
+pub struct SentMessages {
+    messages: Rc<RefCell<VecDeque<SentMessage>>>,
+}
+
+impl SentMessages {
+    /// Forgets the oldest message once there are too many
+    pub fn record(&self, message: SentMessage) {
+        let mut messages = self.messages.borrow_mut();
+        messages.push_back(message);
+        if messages.len() > KEEP {
+            messages.pop_front();
+        }
+    }
+
+    /// Takes the newest message out, it's not undone twice
+    pub fn take_last(&self) -> Option<SentMessage> {
+        self.messages.borrow_mut().pop_back()
+    }
+
+    /// Takes every message ```login``` made the bot send out, oldest first
+    pub fn take_by(&self, login: &str) -> Vec<SentMessage> {
+        let mut messages = self.messages.borrow_mut();
+        let (taken, kept): (VecDeque<_>, VecDeque<_>) = messages
+            .drain(..)
+            .partition(|message| message.user_login.eq_ignore_ascii_case(login));
+        *messages = kept;
+        taken.into()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.borrow().is_empty()
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sent(id: usize, login: &str) -> SentMessage {
+        SentMessage {
+            id: id.to_string(),
+            user_id: format!("id_{login}"),
+            user_login: login.to_owned(),
+            command: "ping".to_owned(),
+        }
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let messages = SentMessages::default();
+        for id in 0..KEEP + 5 {
+            messages.record(sent(id, if id % 2 == 0 { "even" } else { "odd" }));
+        }
+        assert_eq!(messages.len(), KEEP);
+
+        assert_eq!(messages.take_last().unwrap().id, (KEEP + 4).to_string());
+        let odd = messages.clone().take_by("ODD");
+        assert_eq!(odd.len(), KEEP / 2);
+        assert_eq!(odd[0].id, "5");
+        assert!(messages.take_by("odd").is_empty());
+        assert_eq!(messages.len(), KEEP / 2 - 1);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+use twitcheventsub::MessageData;
+
+/// Anything that hands out raw events one at a time: a franz topic, a file of recorded
+/// events, stdin or a channel in a test. The bot loop only ever sees this, so it runs the
+/// same with or without a broker
+pub trait EventSource {
+    /// Blocks until the next event, ```None``` once there won't be any more
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+impl<S: EventSource + ?Sized> EventSource for Box<S> {
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        (**self).recv()
+    }
+}
+
+/// A franz consumer on its own errors out when the broker goes away, wrap it in a
+/// [`SupervisedConsumer`](crate::SupervisedConsumer) to reconnect instead
+impl EventSource for franz_client::Consumer {
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        franz_client::Consumer::recv(self).map(Some)
+    }
+}
+
+// Lines ----------------------------------------------------------------------
+
+/// One event per line, like a ```.jsonl``` file of recorded chat or whatever is piped into
+/// stdin. Blank lines are skipped
+pub struct LineSource<R> {
+    reader: R,
+}
+
+impl<R: BufRead> LineSource<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl LineSource<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl LineSource<io::StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Self::new(io::stdin().lock())
+    }
+}
+
+impl<R: BufRead> EventSource for LineSource<R> {
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim_ascii().is_empty() {
+                return Ok(Some(line.trim_ascii().to_vec()));
+            }
+        }
+    }
+}
+
+// Channel --------------------------------------------------------------------
+
+/// Events sent from somewhere else in the process, runs dry once every sender is dropped
+pub struct ChannelSource {
+    rx: mpsc::Receiver<Vec<u8>>,
+}
+
+impl ChannelSource {
+    pub fn new() -> (mpsc::Sender<Vec<u8>>, Self) {
+        let (tx, rx) = mpsc::channel();
+        (tx, Self { rx })
+    }
+}
+
+impl EventSource for ChannelSource {
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.rx.recv().ok())
+    }
+}
+
+// Chat -----------------------------------------------------------------------
+
+/// A raw event from a chat source, with the chat message in it if there was one
+#[derive(Debug)]
+pub struct ChatEvent {
+    pub raw: String,
+    pub message: Option<MessageData>,
+}
+
+impl ChatEvent {
+    pub fn parse(raw: Vec<u8>) -> Self {
+        let raw = String::from_utf8_lossy(&raw).into_owned();
+        let message = serde_json::from_str(&raw).ok();
+        Self { raw, message }
+    }
+
+    /// How long ago twitch received the message, see [`message_age`](crate::message_age)
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        crate::resume::message_age(&self.raw, now)
+    }
+
+    /// True if the message is too old to answer, see [`is_stale`](crate::is_stale)
+    pub fn is_stale(&self, max_age: Duration, now: SystemTime) -> bool {
+        crate::resume::is_stale(&self.raw, max_age, now)
+    }
+}
+
+/// An [`EventSource`] of twitch chat messages
+pub trait ChatSource: EventSource {
+    /// The next event, decoded. Events that aren't chat messages come back with no
+    /// ```message``` instead of being skipped, they still count towards the offset
+    fn next_chat(&mut self) -> io::Result<Option<ChatEvent>> {
+        Ok(self.recv()?.map(ChatEvent::parse))
+    }
+}
+
+impl<S: EventSource + ?Sized> ChatSource for S {}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{chat_message_json, BROADCASTER_ID};
+
+    fn chat() -> String {
+        chat_message_json(BROADCASTER_ID, "!ping", &[]).to_string()
+    }
+
+    #[test]
+    fn lines() {
+        let input = format!("{}\n\n  \nnot chat\r\n{}", chat(), chat());
+        let mut source = LineSource::new(input.as_bytes());
+
+        let first = source.next_chat().unwrap().unwrap();
+        assert_eq!(first.message.unwrap().message.text, "!ping");
+
+        let second = source.next_chat().unwrap().unwrap();
+        assert_eq!(second.raw, "not chat");
+        assert!(second.message.is_none());
+
+        // no trailing newline is fine
+        assert!(source.next_chat().unwrap().unwrap().message.is_some());
+        assert!(source.next_chat().unwrap().is_none());
+    }
+
+    #[test]
+    fn channel() {
+        let (tx, mut source) = ChannelSource::new();
+        tx.send(chat().into_bytes()).unwrap();
+        drop(tx);
+
+        assert!(source.next_chat().unwrap().unwrap().message.is_some());
+        assert!(source.next_chat().unwrap().is_none());
+    }
+}
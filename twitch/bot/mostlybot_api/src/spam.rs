@@ -1,11 +1,17 @@
+use crate::config::{deserialize_duration, SpamConfig};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
 // Rate Limiter ---------------------------------------------------------------
 
-#[derive(Copy, Clone, Debug)]
+/// ```max_attempts``` every ```duration```, written as ```{ max = 1, per = "5s" }``` in the config
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RateLimit {
+    #[serde(rename = "max")]
     max_attempts: usize,
+    #[serde(rename = "per", deserialize_with = "deserialize_duration")]
     duration: Duration,
 }
 
@@ -22,6 +28,7 @@ impl RateLimit {
     }
 }
 
+#[derive(Debug)]
 struct UsageState {
     attempts: usize,
     last_reset: Instant,
@@ -36,13 +43,14 @@ impl UsageState {
     }
 }
 
-struct RateLimiter<K> {
-    default_limit: RateLimit,
+#[derive(Debug)]
+pub(crate) struct RateLimiter<K> {
+    pub(crate) default_limit: RateLimit,
     usage: HashMap<K, UsageState>,
 }
 
 impl<K: Eq + std::hash::Hash> RateLimiter<K> {
-    fn new(default_limit: RateLimit) -> Self {
+    pub(crate) fn new(default_limit: RateLimit) -> Self {
         Self {
             default_limit,
             usage: HashMap::new(),
@@ -50,7 +58,11 @@ impl<K: Eq + std::hash::Hash> RateLimiter<K> {
     }
 
     /// Enforces the rate limit for a key, returning the remaining cooldown if the limit is exceeded
-    fn enforce_limit(&mut self, key: K, custom_limit: Option<&RateLimit>) -> Option<Duration> {
+    pub(crate) fn enforce_limit(
+        &mut self,
+        key: K,
+        custom_limit: Option<&RateLimit>,
+    ) -> Option<Duration> {
         let limit = custom_limit.unwrap_or(&self.default_limit);
         // no cooldown if no limit, allow all attempts
         if limit.is_unlimited() {
@@ -101,6 +113,13 @@ impl Spam {
         }
     }
 
+    /// Swaps in new limits, keeping track of what's been used so far
+    pub fn set_limits(&mut self, config: &SpamConfig) {
+        self.user_limiter.default_limit = config.user;
+        self.global_command_limiter.default_limit = config.command;
+        self.failed_command_limiter.default_limit = config.failed_command;
+    }
+
     /// Checks the cooldown for failed commands per user and returns remaining time if limit exceeded
     pub fn check_failed_command_cooldown(&mut self, user_id: &UserId) -> Option<Duration> {
         self.failed_command_limiter
@@ -123,17 +142,15 @@ impl Spam {
     }
 }
 
+impl From<&SpamConfig> for Spam {
+    fn from(config: &SpamConfig) -> Self {
+        Self::new(config.user, config.command, config.failed_command)
+    }
+}
+
 impl Default for Spam {
     fn default() -> Self {
-        Self::new(
-            // max any 1 bot command per-user every 5 seconds
-            RateLimit::new(1, Duration::from_secs(5)),
-            // max any 1 bot command by any user every 5 seconds
-            // use any sensible defaults, will be overridden by the user provided value
-            RateLimit::new(1, Duration::from_secs(5)),
-            // max any 2 failed command messages in chat, per-user, every 30 seconds
-            RateLimit::new(2, Duration::from_secs(30)),
-        )
+        Self::from(&SpamConfig::default())
     }
 }
 
@@ -148,6 +165,7 @@ mod test {
     const USER2: &str = "user2";
 
     /// Tests cooldown behavior, cooldown resets, and respects rate limit config
+    #[allow(clippy::clone_on_copy)]
     fn test_rate_limiter<'a>(
         limiter: &mut RateLimiter<&'a str>,
         key: &'a str,
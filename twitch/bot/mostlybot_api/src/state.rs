@@ -0,0 +1,71 @@
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where a command's saved state lives inside the data dir
+pub fn state_path(data_dir: &Path, name: &str) -> PathBuf {
+    data_dir.join("state").join(format!("{name}.json"))
+}
+
+/// Reads a command's state, ```None``` if nothing was saved yet
+pub fn load_state<T: DeserializeOwned>(data_dir: &Path, name: &str) -> anyhow::Result<Option<T>> {
+    let path = state_path(data_dir, name);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json =
+        std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    let state =
+        serde_json::from_str(&json).with_context(|| format!("parsing {}", path.display()))?;
+    Ok(Some(state))
+}
+
+pub fn save_state<T: Serialize>(data_dir: &Path, name: &str, state: &T) -> anyhow::Result<()> {
+    let path = state_path(data_dir, name);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+
+    let json = serde_json::to_string(state)?;
+    write_atomic(&path, json)
+}
+
+/// Writes to a temp file and renames it over ```path```, so a crash or a kill mid-write never
+/// leaves half a file behind
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    std::fs::write(&tmp, contents).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("writing {}", path.display()))
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-state-{}", std::process::id()));
+
+        assert_eq!(load_state::<usize>(&dir, "count").unwrap(), None);
+
+        save_state(&dir, "count", &3usize).unwrap();
+        assert_eq!(load_state::<usize>(&dir, "count").unwrap(), Some(3));
+
+        let lurkers = HashMap::from([("mostlymaxi".to_owned(), Some("coding".to_owned()))]);
+        save_state(&dir, "lurk", &lurkers).unwrap();
+        assert_eq!(load_state(&dir, "lurk").unwrap(), Some(lurkers));
+
+        // the wrong shape is an error, not a silent reset
+        assert!(load_state::<usize>(&dir, "lurk").is_err());
+        assert!(!dir.join("state").join("lurk.json.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long [`RuntimeStats::storage_ok`] goes by the last check
+const STORAGE_CHECK_EVERY: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Stats {
+    started: Instant,
+    version: &'static str,
+    commands_loaded: usize,
+    last_error: Option<Instant>,
+    /// When the data dir was last checked, and whether it was writable
+    storage: Option<(Instant, bool)>,
+}
+
+/// Facts about the running bot for commands like ```!status``` to read, kept up to date by
+/// the bot loop. Cheap to clone, clones share the same stats
+///
+/// There's no consumer lag behind the ```chat``` topic in here: franz-client can only open a
+/// consumer at an offset and read from it, it can't ask the broker where the topic ends
+#[derive(Debug, Clone)]
+pub struct RuntimeStats {
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl RuntimeStats {
+    /// ```version``` is the bot's, ```env!("CARGO_PKG_VERSION")``` in the binary
+    pub fn new(version: &'static str) -> Self {
+        Self {
+            stats: Arc::new(Mutex::new(Stats {
+                started: Instant::now(),
+                version,
+                commands_loaded: 0,
+                last_error: None,
+                storage: None,
+            })),
+        }
+    }
+
+    fn stats(&self) -> std::sync::MutexGuard<'_, Stats> {
+        self.stats.lock().expect("stats lock")
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.stats().started.elapsed()
+    }
+
+    pub fn version(&self) -> &'static str {
+        self.stats().version
+    }
+
+    pub fn commands_loaded(&self) -> usize {
+        self.stats().commands_loaded
+    }
+
+    pub fn set_commands_loaded(&self, commands: usize) {
+        self.stats().commands_loaded = commands;
+    }
+
+    /// How long ago something last went wrong
+    pub fn last_error(&self) -> Option<Duration> {
+        self.stats().last_error.map(|at| at.elapsed())
+    }
+
+    /// Something went wrong, what exactly is already in the logs
+    pub fn error(&self) {
+        self.stats().last_error = Some(Instant::now());
+    }
+
+    /// Whether the data dir is writable, with [`check_storage`] but at most once every
+    /// ```STORAGE_CHECK_EVERY``` so chat can't make the bot hammer the disk
+    pub fn storage_ok(&self, data_dir: &Path) -> bool {
+        let mut stats = self.stats();
+        if let Some((at, ok)) = stats.storage {
+            if at.elapsed() < STORAGE_CHECK_EVERY {
+                return ok;
+            }
+        }
+
+        let ok = match check_storage(data_dir) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(error = %e, "data dir isn't writable");
+                false
+            }
+        };
+        stats.storage = Some((Instant::now(), ok));
+        ok
+    }
+}
+
+impl Default for RuntimeStats {
+    fn default() -> Self {
+        Self::new("dev")
+    }
+}
+
+/// Whether the data dir can still be written to, by writing and removing a small file
+pub fn check_storage(data_dir: &Path) -> io::Result<()> {
+    let probe = data_dir.join(".storage_check");
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(&probe)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared() {
+        let stats = RuntimeStats::new("1.2.3");
+        let clone = stats.clone();
+
+        clone.set_commands_loaded(7);
+        assert_eq!(stats.last_error(), None);
+        clone.error();
+
+        assert_eq!(stats.version(), "1.2.3");
+        assert_eq!(stats.commands_loaded(), 7);
+        assert!(stats.last_error().is_some());
+
+        // checked once, then remembered for a while
+        let dir = std::env::temp_dir().join(format!("mostlybot-stats-{}", std::process::id()));
+        assert!(!stats.storage_ok(&dir));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(!stats.storage_ok(&dir));
+
+        assert!(check_storage(&dir).is_ok());
+        assert!(check_storage(Path::new("/does/not/exist")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+//! Helpers for tests, here and in the command crates with the ```test-util``` feature
+
+use serde_json::{json, Value};
+use twitcheventsub::MessageData;
+
+/// The broadcaster of every test message
+pub const BROADCASTER_ID: &str = "938429017";
+
+/// The id of every test message
+pub const MESSAGE_ID: &str = "3104f083-2bdb-4d6a-bb5d-30b407876ea4";
+
+/// A chat message as it comes from twitch, see [`chat_message`]
+pub fn chat_message_json(chatter: &str, text: &str, badges: &[&str]) -> Value {
+    let badges: Vec<_> = badges
+        .iter()
+        .map(|set_id| json!({ "set_id": set_id, "id": "1", "info": "" }))
+        .collect();
+
+    json!({
+        "broadcaster_user_id": BROADCASTER_ID,
+        "broadcaster_user_name": "mostlymaxi",
+        "broadcaster_user_login": "mostlymaxi",
+        "chatter_user_id": chatter,
+        "chatter_user_name": chatter,
+        "chatter_user_login": chatter,
+        "message_id": MESSAGE_ID,
+        "message": { "text": text, "fragments": [] },
+        "color": "#FF0000",
+        "badges": badges,
+        "message_type": "text",
+        "cheer": null,
+        "reply": null,
+        "channel_points_custom_reward_id": null,
+        "channel_points_animation_id": null
+    })
+}
+
+/// A chat message from ```chatter```, which is their id, name and login. ```badges``` are set ids
+/// like ```moderator```, use [`BROADCASTER_ID`] as the chatter for the broadcaster themselves
+pub fn chat_message(chatter: &str, text: &str, badges: &[&str]) -> MessageData {
+    serde_json::from_value(chat_message_json(chatter, text, badges)).unwrap()
+}
@@ -0,0 +1,75 @@
+use crate::state::write_atomic;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// What a moderator changed about a command with ```!cmd```, kept across restarts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandToggle {
+    pub disabled: bool,
+    /// Replaces the command's rate limit with one use per ```cooldown```
+    pub cooldown: Option<Duration>,
+}
+
+/// Where the toggles live inside the data dir
+pub fn toggles_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("command_toggles.json")
+}
+
+/// Reads saved toggles, no file just means nothing was toggled yet
+pub fn load_toggles(path: &Path) -> anyhow::Result<BTreeMap<String, CommandToggle>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("parsing {}", path.display()))
+}
+
+pub fn save_toggles(path: &Path, toggles: &BTreeMap<String, CommandToggle>) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(toggles)?;
+    write_atomic(path, json)
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-toggles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = toggles_path(&dir);
+
+        assert!(load_toggles(&path).unwrap().is_empty());
+
+        let toggles = BTreeMap::from([
+            (
+                "js".to_owned(),
+                CommandToggle {
+                    disabled: true,
+                    cooldown: None,
+                },
+            ),
+            (
+                "tictactoe".to_owned(),
+                CommandToggle {
+                    disabled: false,
+                    cooldown: Some(Duration::from_secs(30)),
+                },
+            ),
+        ]);
+        save_toggles(&path, &toggles).unwrap();
+        assert_eq!(load_toggles(&path).unwrap(), toggles);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use crate::state::{load_state, save_state};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Chat going quiet for this long means the stream is over, the next command starts a new one
+const STREAM_GAP: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// Counts are saved at most this often while the bot runs, and always on shutdown
+const SAVE_EVERY: Duration = Duration::from_secs(5 * 60);
+
+// Report ---------------------------------------------------------------------
+
+/// How often each command was run, in total, per chatter and per stream. Counts are keyed by
+/// the command's name, whichever alias was used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageReport {
+    pub commands: BTreeMap<String, u64>,
+    /// Keyed by user id
+    pub users: BTreeMap<String, UserUsage>,
+    /// Oldest first
+    pub streams: Vec<StreamUsage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserUsage {
+    /// The name they last used a command under
+    pub name: String,
+    pub commands: BTreeMap<String, u64>,
+}
+
+impl UserUsage {
+    pub fn total(&self) -> u64 {
+        self.commands.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamUsage {
+    /// Unix millis of the first command of the stream
+    pub started: u64,
+    /// Unix millis of the last one
+    pub last: u64,
+    pub commands: BTreeMap<String, u64>,
+}
+
+impl UsageReport {
+    fn record(&mut self, command: &str, user_id: &str, user_name: &str, at: u64) {
+        *self.commands.entry(command.to_owned()).or_default() += 1;
+
+        let user = self.users.entry(user_id.to_owned()).or_default();
+        user.name = user_name.to_owned();
+        *user.commands.entry(command.to_owned()).or_default() += 1;
+
+        let gap = STREAM_GAP.as_millis() as u64;
+        match self.streams.last_mut() {
+            Some(stream) if at.saturating_sub(stream.last) < gap => stream.last = at,
+            _ => self.streams.push(StreamUsage {
+                started: at,
+                last: at,
+                commands: BTreeMap::new(),
+            }),
+        }
+        let stream = self.streams.last_mut().expect("pushed above");
+        *stream.commands.entry(command.to_owned()).or_default() += 1;
+    }
+
+    /// The stream going on right now, or the last one
+    pub fn current_stream(&self) -> Option<&StreamUsage> {
+        self.streams.last()
+    }
+
+    /// Most used first, ties by name
+    pub fn top(commands: &BTreeMap<String, u64>, n: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<_> = commands
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    /// One row per count: ```scope,id,name,command,count``` where ```scope``` is ```total```,
+    /// ```user``` (with their id and name) or ```stream``` (with when it started)
+    pub fn to_csv(&self) -> String {
+        let mut out = "scope,id,name,command,count\n".to_owned();
+
+        for (command, count) in &self.commands {
+            let _ = writeln!(out, "total,,,{},{count}", csv(command));
+        }
+        for (id, user) in &self.users {
+            for (command, count) in &user.commands {
+                let _ = writeln!(
+                    out,
+                    "user,{},{},{},{count}",
+                    csv(id),
+                    csv(&user.name),
+                    csv(command)
+                );
+            }
+        }
+        for stream in &self.streams {
+            for (command, count) in &stream.commands {
+                let _ = writeln!(out, "stream,{},,{},{count}", stream.started, csv(command));
+            }
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("usage is always valid json")
+    }
+}
+
+/// Quotes a field if it needs it
+fn csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+// Service --------------------------------------------------------------------
+
+/// Counts every command the dispatcher runs, see [`UsageReport`]. The default only counts in
+/// memory, [`Usage::load`] keeps the counts in the data dir. Cheap to clone, clones share the
+/// counts
+#[derive(Debug, Clone)]
+pub struct Usage {
+    report: Rc<RefCell<UsageReport>>,
+    data_dir: Option<PathBuf>,
+    last_saved: Rc<Cell<Instant>>,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            report: Rc::default(),
+            data_dir: None,
+            last_saved: Rc::new(Cell::new(Instant::now())),
+        }
+    }
+}
+
+impl Usage {
+    /// The name the counts are saved under, see [`state_path`](crate::state_path)
+    pub const STATE: &'static str = "usage";
+
+    /// Picks up the counts from before the restart
+    pub fn load(data_dir: &Path) -> anyhow::Result<Self> {
+        let report = load_state(data_dir, Self::STATE)?.unwrap_or_default();
+        Ok(Self {
+            report: Rc::new(RefCell::new(report)),
+            data_dir: Some(data_dir.to_owned()),
+            ..Self::default()
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(data_dir) = &self.data_dir else {
+            return Ok(());
+        };
+        self.last_saved.set(Instant::now());
+        save_state(data_dir, Self::STATE, &*self.report.borrow())
+    }
+
+    /// ```at``` is in unix millis
+    pub fn record(&self, command: &str, user_id: &str, user_name: &str, at: u64) {
+        self.report
+            .borrow_mut()
+            .record(command, user_id, user_name, at);
+
+        if self.last_saved.get().elapsed() >= SAVE_EVERY {
+            if let Err(e) = self.save() {
+                tracing::error!(error = ?e, "unable to save command usage");
+            }
+        }
+    }
+
+    pub fn report(&self) -> Ref<'_, UsageReport> {
+        self.report.borrow()
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    #[test]
+    fn counts() {
+        let usage = Usage::default();
+        usage.record("ping", "1", "maxi", 0);
+        usage.record("ping", "2", "luni", HOUR);
+        usage.record("lurk", "1", "mostlymaxi", 2 * HOUR);
+        // a new stream after chat was quiet for a while
+        usage.record("lurk", "1", "mostlymaxi", 6 * HOUR);
+
+        let report = usage.report();
+        assert_eq!(
+            UsageReport::top(&report.commands, 5),
+            [("lurk", 2), ("ping", 2)]
+        );
+        assert_eq!(report.users["1"].name, "mostlymaxi");
+        assert_eq!(report.users["1"].total(), 3);
+        assert_eq!(report.streams.len(), 2);
+        assert_eq!(report.streams[0].commands["ping"], 2);
+        assert_eq!(report.current_stream().unwrap().started, 6 * HOUR);
+
+        let csv = report.to_csv();
+        assert!(csv.starts_with("scope,id,name,command,count\ntotal,,,lurk,2\n"));
+        assert!(csv.contains("user,1,mostlymaxi,ping,1\n"));
+        assert!(csv.contains(&format!("stream,{},,lurk,1\n", 6 * HOUR)));
+        assert_eq!(
+            serde_json::from_str::<UsageReport>(&report.to_json()).unwrap(),
+            *report
+        );
+        assert_eq!(self::csv("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn persisted() {
+        let dir = std::env::temp_dir().join(format!("mostlybot-usage-{}", std::process::id()));
+
+        let usage = Usage::load(&dir).unwrap();
+        usage.record("ping", "1", "maxi", 0);
+        usage.save().unwrap();
+
+        let usage = Usage::load(&dir).unwrap();
+        assert_eq!(usage.report().commands["ping"], 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
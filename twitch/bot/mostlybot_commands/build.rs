@@ -0,0 +1,36 @@
+//! declares a module for every command file in ```src/```, so adding a command
+//! is just adding a file
+
+use std::{env, fs, path::Path};
+
+/// files in ```src/``` that aren't commands
+const SKIP: [&str; 2] = ["lib", "template"];
+
+fn main() {
+    let src = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src");
+    println!("cargo:rerun-if-changed={}", src.display());
+
+    let mut modules: Vec<_> = fs::read_dir(&src)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_stem()?.to_str()?.to_owned();
+            let is_command = path.extension()? == "rs" && !SKIP.contains(&name.as_str());
+            is_command.then_some((name, path))
+        })
+        .collect();
+    modules.sort();
+
+    let declarations: String = modules
+        .iter()
+        .map(|(name, path)| {
+            format!(
+                "#[allow(clippy::module_inception)]\n#[path = {:?}]\npub mod {name};\n",
+                path.display().to_string()
+            )
+        })
+        .collect();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("commands.rs");
+    fs::write(out, declarations).unwrap();
+}
@@ -4,46 +4,18 @@
 //!
 //! author: Cathyprime
 
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use tracing::{debug, error};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandError, CommandResult, TwitchApiWrapper};
 
-pub struct MostlyBan {}
+#[command(names = ["ban"], help = "usage: !ban <arguments>")]
+pub fn mostly_ban(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let arg: Vec<_> = ctx.message.text.split_whitespace().skip(1).collect();
+    let arg: String = arg.join(" ");
 
-impl ChatCommand for MostlyBan {
-    fn new() -> Self {
-        Self {}
+    if arg.is_empty() {
+        return Err(CommandError::Usage);
     }
 
-    fn names() -> Vec<String> {
-        vec!["ban".to_string()]
-    }
-
-    fn help(&self) -> String {
-        "usage: !ban <arguments>".to_string()
-    }
-
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let arg: Vec<_> = ctx.message.text.split_whitespace().skip(1).collect();
-        let arg: String = arg.join(" ");
-
-        if arg.is_empty() {
-            Err(anyhow!("No argument provided"))?
-        }
-        let msg = format!("{} has been banned", arg.trim());
-
-        match api.send_chat_message_with_reply(&msg, Some(&ctx.message_id)) {
-            Ok(s) => {
-                debug!(reply = %s);
-                Ok(())
-            }
-            Err(e) => {
-                error!(error = ?e);
-                Err(anyhow!("{:?}", e))
-            }
-        }
-    }
+    api.reply(ctx, format!("{} has been banned", arg.trim()))
 }
 
 #[cfg(test)]
@@ -56,41 +28,44 @@ mod test {
     }
 
     use super::*;
-    use mostlybot_api::{MockTwitchEventSubApi, TwitchApiWrapper};
+    use mostlybot_api::{BotRng, ChatCommand, MockTwitchEventSubApi};
 
     #[test]
     fn handle() {
-        let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
         let mut cmd = MostlyBan::new();
 
         let test_msg = message!("!ban @mostlymaxi");
 
-        let ctx = serde_json::from_str(test_msg).unwrap();
+        let msg = serde_json::from_str(test_msg).unwrap();
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(0));
         cmd.handle(&mut api, &ctx).unwrap();
     }
 
     #[test]
     fn handle_many() {
-        let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
         let mut cmd = MostlyBan::new();
 
         let test_msg = message!("!ban rust users Kappa");
 
-        let ctx = serde_json::from_str(test_msg).unwrap();
+        let msg = serde_json::from_str(test_msg).unwrap();
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(0));
         cmd.handle(&mut api, &ctx).unwrap();
     }
 
     #[test]
     fn missing_arg() {
-        let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
         let mut cmd = MostlyBan::new();
 
         let test_msg = message!("!ban ");
 
-        let ctx = serde_json::from_str(test_msg).unwrap();
-        assert_eq!(
-            cmd.handle(&mut api, &ctx).unwrap_err().to_string(),
-            "No argument provided"
-        )
+        let msg = serde_json::from_str(test_msg).unwrap();
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(0));
+        assert!(matches!(
+            cmd.handle(&mut api, &ctx),
+            Err(CommandError::Usage)
+        ))
     }
 }
@@ -3,39 +3,31 @@
 //! usage: ```!bot-time```
 //!
 //! author: Nilando
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
 use std::time::SystemTime;
 use tracing::instrument;
-use twitcheventsub::MessageData;
 
+#[command(names = ["bottime", "bot_time", "bot-time"], help = "usage: !bottime")]
 pub struct BotTime {
     start_time: SystemTime,
 }
 
-impl ChatCommand for BotTime {
-    fn new() -> Self {
+impl Default for BotTime {
+    fn default() -> Self {
         Self {
             start_time: SystemTime::now(),
         }
     }
+}
 
-    fn names() -> Vec<String> {
-        vec![
-            "bottime".to_string(),
-            "bot_time".to_string(),
-            "bot-time".to_string(),
-        ]
-    }
-
-    fn help(&self) -> String {
-        "usage: !bottime".to_string()
-    }
-
+impl BotTime {
     #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
         let now = SystemTime::now();
-        let seconds = now.duration_since(self.start_time)?.as_secs();
+        let seconds = now
+            .duration_since(self.start_time)
+            .map_err(anyhow::Error::from)?
+            .as_secs();
         let minutes = seconds / 60;
         let hours = minutes / 60;
 
@@ -47,9 +39,6 @@ impl ChatCommand for BotTime {
             format!("Bot has been running for {hours} hours.")
         };
 
-        match api.send_chat_message_with_reply(&msg, Some(&ctx.message_id)) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(anyhow!("{:?}", e)),
-        }
+        api.reply(ctx, msg)
     }
 }
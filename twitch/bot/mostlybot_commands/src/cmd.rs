@@ -0,0 +1,188 @@
+//! lets moderators turn commands off and on, or change their cooldown, without a redeploy
+//!
+//! usage: ```!cmd disable <name>``` or ```!cmd enable <name>``` or
+//! ```!cmd cooldown <name> <duration>``` or ```!cmd list-disabled```
+//!
+//! changes are saved in the data dir so they survive a restart
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{
+    format_duration, save_toggles, toggles_path, ChatCommand, CommandArgs, CommandContext,
+    CommandError, CommandResult, Role, TwitchApiWrapper,
+};
+use std::time::Duration;
+use tracing::instrument;
+
+#[derive(Debug, PartialEq, CommandArgs)]
+enum CmdArgs {
+    #[arg(subcommand)]
+    Disable { name: String },
+    #[arg(subcommand)]
+    Enable { name: String },
+    #[arg(subcommand)]
+    Cooldown { name: String, duration: Duration },
+    #[arg(subcommand = "list-disabled")]
+    ListDisabled,
+}
+
+/// Like help, this command is special and needs the command map, see
+/// [`CommandContext::commands`]
+pub struct MostlyCmd {}
+
+impl MostlyCmd {
+    /// Saves the toggles, replying with ```done``` and a warning if that didn't work
+    fn save_and_reply(
+        &self,
+        api: &mut TwitchApiWrapper,
+        ctx: &CommandContext,
+        done: String,
+    ) -> CommandResult {
+        let path = toggles_path(ctx.data_dir());
+        match save_toggles(&path, &ctx.commands().toggles()) {
+            Ok(()) => api.reply(ctx, done),
+            Err(e) => {
+                tracing::error!(error = ?e, "unable to save command toggles");
+                api.reply(ctx, format!("{done} (couldn't save it, it resets on restart)"))
+            }
+        }
+    }
+}
+
+impl ChatCommand for MostlyCmd {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn names() -> Vec<String> {
+        vec!["cmd".to_owned()]
+    }
+
+    fn role(&self) -> Role {
+        Role::Moderator
+    }
+
+    #[instrument(skip(self, api))]
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        let args = CmdArgs::parse(&ctx.message.text)?;
+
+        let name = match &args {
+            CmdArgs::Disable { name } | CmdArgs::Enable { name } | CmdArgs::Cooldown { name, .. } => {
+                // "!cmd disable !js" works too
+                name.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '_')
+            }
+            CmdArgs::ListDisabled => {
+                let mut disabled: Vec<_> = ctx
+                    .commands()
+                    .commands()
+                    .filter(|cmd| !cmd.is_enabled())
+                    .map(|cmd| format!("!{}", cmd.name()))
+                    .collect();
+                disabled.sort();
+
+                if disabled.is_empty() {
+                    return api.reply(ctx, "no commands are disabled");
+                }
+                return api.reply(ctx, format!("disabled: {}", disabled.join(", ")));
+            }
+        };
+
+        // moderators can't lock themselves out with "!cmd disable cmd"
+        let Some(cmd) = ctx.commands().get(name).filter(|cmd| cmd.name() != "cmd") else {
+            return Err(CommandError::user_facing(format!(
+                "\"{name}\" is not a command you can toggle"
+            )));
+        };
+
+        let mut toggle = cmd.toggle();
+        let done = match args {
+            CmdArgs::Disable { .. } => {
+                toggle.disabled = true;
+                format!("!{} disabled", cmd.name())
+            }
+            CmdArgs::Enable { .. } => {
+                toggle.disabled = false;
+                if cmd.config().enabled {
+                    format!("!{} enabled", cmd.name())
+                } else {
+                    format!("!{} is still disabled by the config", cmd.name())
+                }
+            }
+            CmdArgs::Cooldown { duration, .. } => {
+                toggle.cooldown = Some(duration);
+                format!(
+                    "!{} cooldown set to {}",
+                    cmd.name(),
+                    format_duration(duration)
+                )
+            }
+            CmdArgs::ListDisabled => unreachable!("handled above"),
+        };
+        cmd.set_toggle(toggle);
+
+        self.save_and_reply(api, ctx, done)
+    }
+
+    fn help(&self) -> String {
+        CmdArgs::usage("cmd")
+    }
+}
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ping;
+    use mostlybot_api::{
+        test_util::{chat_message, BROADCASTER_ID},
+        BotRng, CommandMap, MockTwitchEventSubApi, Services,
+    };
+
+    #[test]
+    fn toggles() {
+        let data_dir = std::env::temp_dir().join(format!("mostlybot-cmd-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let mut cmds = CommandMap::new();
+        cmds.insert(ping::MostlyPing::new());
+        cmds.insert(MostlyCmd::new());
+        let mut cmd = MostlyCmd::new();
+
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let services = Services {
+            data_dir: data_dir.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        let mut send = |text: &str| {
+            let msg = chat_message(BROADCASTER_ID, text, &["broadcaster"]);
+            let ctx = CommandContext::with_services(&msg, &services).with_commands(&cmds);
+            let result = cmd.handle(&mut api, &ctx);
+            (result, api.as_mock().unwrap().sent_messages().last().cloned())
+        };
+
+        assert_eq!(send("!cmd disable !ping").1.unwrap(), "!ping disabled");
+        assert!(!cmds.get("ping").unwrap().is_enabled());
+        assert_eq!(send("!cmd list-disabled").1.unwrap(), "disabled: !ping");
+
+        assert_eq!(send("!cmd cooldown ping 30s").1.unwrap(), "!ping cooldown set to 30s");
+        assert_eq!(
+            cmds.get("ping").unwrap().toggle().cooldown,
+            Some(Duration::from_secs(30))
+        );
+
+        assert_eq!(send("!cmd enable ping").1.unwrap(), "!ping enabled");
+        assert_eq!(send("!cmd list-disabled").1.unwrap(), "no commands are disabled");
+
+        assert!(matches!(
+            send("!cmd disable cmd").0,
+            Err(CommandError::UserFacing(_))
+        ));
+
+        // what's saved is what a restart would load
+        let saved = mostlybot_api::load_toggles(&toggles_path(&data_dir)).unwrap();
+        assert_eq!(saved, cmds.toggles());
+
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
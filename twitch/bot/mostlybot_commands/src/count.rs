@@ -4,27 +4,17 @@
 //!
 //! author: lunispang
 
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use serde::{Deserialize, Serialize};
 use tracing::instrument;
-use twitcheventsub::MessageData;
 
+#[command(names = ["count"], help = "usage: !count", persist)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Count(usize);
 
-impl ChatCommand for Count {
-    fn new() -> Self {
-        Self(0)
-    }
-
-    fn names() -> Vec<String> {
-        vec!["count".to_string()]
-    }
-
-    fn help(&self) -> String {
-        "usage: !count".to_string()
-    }
-
+impl Count {
     #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
         let Self(count) = self;
         if api
             .send_chat_message(format!("current count: {count}"))
@@ -3,30 +3,9 @@
 //! usage: ```!git```
 //!
 //! author: mostlymaxi
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
 
-pub struct MostlyGit {}
-
-impl ChatCommand for MostlyGit {
-    fn new() -> Self {
-        MostlyGit {}
-    }
-
-    fn help(&self) -> String {
-        "usage: !git".to_string()
-    }
-
-    fn names() -> Vec<String> {
-        vec!["git".to_string(), "github".to_string()]
-    }
-
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let _ = api.send_chat_message_with_reply(
-            "check out maxi's git: https://github.com/mostlymaxi",
-            Some(&ctx.message_id),
-        );
-
-        Ok(())
-    }
+#[command(names = ["git", "github"], help = "usage: !git")]
+pub fn mostly_git(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    api.reply(ctx, "check out maxi's git: https://github.com/mostlymaxi")
 }
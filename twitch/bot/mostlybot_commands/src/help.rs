@@ -4,30 +4,18 @@
 //!
 //! author: mostlymaxi
 
-use anyhow::{anyhow, Result};
-use mostlybot_api::{ChatCommand, CommandMap, TwitchApiWrapper};
+use mostlybot_api::{
+    ChatCommand, CommandContext, CommandError, CommandResult, TwitchApiWrapper,
+};
 use tracing::instrument;
-use twitcheventsub::MessageData;
 
-pub const HELP_COOLDOWN_SECS: u64 = 3;
-
-pub struct MostlyHelp {
-    cmds: CommandMap,
-}
-
-impl MostlyHelp {
-    /// the help command is special in that it needs access to the command
-    /// map to call the help function on a specified command
-    pub fn init(&mut self, cmds: CommandMap) {
-        self.cmds = cmds;
-    }
-}
+/// the help command is special in that it needs the command map, see
+/// [`CommandContext::commands`], to call the help function on a specified command
+pub struct MostlyHelp {}
 
 impl ChatCommand for MostlyHelp {
     fn new() -> Self {
-        Self {
-            cmds: CommandMap::new(),
-        }
+        Self {}
     }
 
     fn names() -> Vec<String> {
@@ -35,7 +23,7 @@ impl ChatCommand for MostlyHelp {
     }
 
     #[instrument(skip(self, api, ctx))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> Result<()> {
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
         let mut args = ctx.message.text.split_whitespace();
         let _ = args.next();
 
@@ -53,12 +41,23 @@ impl ChatCommand for MostlyHelp {
         };
 
         if args.next().is_some() {
-            return Err(anyhow!("too many arguments"));
+            return Err(CommandError::Usage);
         }
 
-        self.cmds.get_mut(cmd_name).map(|c| c.borrow().help());
+        // "!help !discord" works too
+        let cmd_name = cmd_name.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '_');
+        let help = match ctx.commands().get(cmd_name) {
+            // help itself is borrowed while it runs
+            Some(cmd) if cmd.name() == "help" => self.help(),
+            Some(cmd) => cmd.borrow().help(),
+            None => {
+                return Err(CommandError::user_facing(format!(
+                    "there's no command called \"{cmd_name}\""
+                )))
+            }
+        };
 
-        Ok(())
+        api.reply(ctx, help)
     }
 
     fn help(&self) -> String {
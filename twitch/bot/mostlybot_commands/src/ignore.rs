@@ -0,0 +1,121 @@
+//! lets moderators make the bot ignore a chatter, like another bot or someone abusing commands
+//!
+//! usage: ```!ignore @user``` or ```!unignore @user``` or ```!ignored```
+//!
+//! the list is saved in the data dir so it survives a restart. well known bots (Nightbot,
+//! StreamElements, ...) are picked up without being added, see ```[ignore]``` in the config
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{
+    command, CommandArgs, CommandContext, CommandError, CommandResult, Mention, TwitchApiWrapper,
+};
+use tracing::instrument;
+
+/// The command name decides what to do, e.g. ```!unignore```
+#[derive(Debug, PartialEq, CommandArgs)]
+enum IgnoreArgs {
+    #[arg(subcommand)]
+    Ignore { user: Mention },
+    #[arg(subcommand)]
+    Unignore { user: Mention },
+    #[arg(subcommand)]
+    Ignored,
+}
+
+#[command(
+    names = ["ignore", "unignore", "ignored"],
+    help = IgnoreArgs::invocation_usage(),
+    role = "moderator"
+)]
+#[instrument(skip(api))]
+pub fn mostly_ignore(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let list = ctx.ignore_list();
+
+    let (changed, reply) = match IgnoreArgs::parse_invocation(&ctx.message.text)? {
+        IgnoreArgs::Ignore { user: Mention(user) } => {
+            if user.eq_ignore_ascii_case(&ctx.broadcaster.login) {
+                return Err(CommandError::user_facing("can't ignore the broadcaster"));
+            }
+            (
+                list.ignore(&user),
+                [
+                    format!("ignoring @{user} from now on"),
+                    format!("@{user} is already ignored"),
+                ],
+            )
+        }
+        IgnoreArgs::Unignore { user: Mention(user) } => (
+            list.unignore(&user),
+            [
+                format!("no longer ignoring @{user}"),
+                format!("@{user} wasn't ignored"),
+            ],
+        ),
+        IgnoreArgs::Ignored => {
+            let logins = list.logins();
+            if logins.is_empty() {
+                return api.reply(ctx, "nobody is ignored");
+            }
+            return api.reply(ctx, format!("ignored: {}", logins.join(", ")));
+        }
+    };
+
+    let [done, unchanged] = reply;
+    match changed {
+        Ok(true) => api.reply(ctx, done),
+        Ok(false) => api.reply(ctx, unchanged),
+        Err(e) => {
+            tracing::error!(error = ?e, "unable to save the ignore list");
+            api.reply(ctx, format!("{done} (couldn't save it, it resets on restart)"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, IgnoreList, MockTwitchEventSubApi, Services,
+    };
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let list = IgnoreList::default();
+        let services = Services {
+            ignore: list.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+
+        for text in [
+            "!ignored",
+            "!ignore @Troll",
+            "!ignore troll",
+            "!ignored",
+            "!unignore @troll",
+            "!unignore @troll",
+        ] {
+            let msg = chat_message("modperson", text, &["moderator"]);
+            let ctx = CommandContext::with_services(&msg, services.clone());
+            MostlyIgnore::new().handle(&mut api, &ctx).unwrap();
+        }
+
+        let msg = chat_message("modperson", "!ignore @mostlymaxi", &["moderator"]);
+        let ctx = CommandContext::with_services(&msg, services);
+        assert!(MostlyIgnore::new().handle(&mut api, &ctx).is_err());
+
+        assert_eq!(
+            api.as_mock().unwrap().sent_messages(),
+            [
+                "nobody is ignored",
+                "ignoring @Troll from now on",
+                "@troll is already ignored",
+                "ignored: troll",
+                "no longer ignoring @troll",
+                "@troll wasn't ignored",
+            ]
+        );
+        assert!(list.logins().is_empty());
+    }
+}
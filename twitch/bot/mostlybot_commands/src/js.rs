@@ -4,41 +4,35 @@
 //!
 //! author: Cathyprime
 
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
 use rand::seq::SliceRandom;
-use tracing::{debug, error};
-use twitcheventsub::MessageData;
 
-pub struct MostlyJs {}
+const JS_MSGS: [&str; 3] = ["Undefined", "[object Object]", "x === y"];
 
-impl ChatCommand for MostlyJs {
-    fn new() -> Self {
-        Self {}
-    }
+#[command(names = ["js"], help = "usage: !js")]
+pub fn mostly_js(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let js = JS_MSGS.choose(&mut ctx.rng());
+    api.reply(ctx, format!("\"{}\" does not exist", js.unwrap()))
+}
 
-    fn names() -> Vec<String> {
-        vec!["js".to_string()]
-    }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{test_util::chat_message, BotRng, ChatCommand, MockTwitchEventSubApi};
 
-    fn help(&self) -> String {
-        "usage: !js".to_string()
-    }
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let mut cmd = MostlyJs::new();
 
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let msgs = vec!["Undefined", "[object Object]", "x === y"];
-        let js = msgs.choose(&mut rand::thread_rng());
-        let msg = format!("\"{}\" does not exist", js.unwrap());
+        let msg = chat_message("mostlymaxi", "!js", &[]);
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(7));
+        cmd.handle(&mut api, &ctx).unwrap();
 
-        match api.send_chat_message_with_reply(&msg, Some(&ctx.message_id)) {
-            Ok(s) => {
-                debug!(reply = %s);
-                Ok(())
-            }
-            Err(e) => {
-                error!(error = ?e);
-                Err(anyhow!("{:?}", e))
-            }
-        }
+        let expected = JS_MSGS.choose(&mut BotRng::seed_from_u64(7)).unwrap();
+        assert_eq!(
+            api.as_mock().unwrap().sent_messages(),
+            [format!("\"{expected}\" does not exist")]
+        );
     }
 }
@@ -1,71 +1,57 @@
 //! module containing all commands
 
-#[allow(clippy::module_inception)]
-pub mod commands;
-
 // ----------------------------------------------------------------------------
-// NOTE: modules must match the command you expect people to use in chat (or at least one of them).
+// NOTE: every file in this directory is a command module (except this one and
+// template.rs), they get declared by build.rs so there's no list to add to here.
+//
+// modules must match the command you expect people to use in chat (or at least one of them).
 // For example: mostlypasta -> !mostlypasta <gnu> <linux>
 //
 // but this does not apply to the internal struct.
-//
-//
-// add your command module to this list:
-pub mod ban;
-pub mod bot_time;
-pub mod count;
-pub mod discord;
-pub mod git;
-pub mod help;
-pub mod js;
-pub mod kofi;
-pub mod lurk;
-pub mod mostlybot;
-pub mod mostlypasta;
-pub mod ping;
-pub mod pong;
-pub mod progress;
-pub mod rewrite;
-pub mod status;
-pub mod tictactoe;
-pub mod uwu;
-pub mod vods;
-pub mod youtube;
+include!(concat!(env!("OUT_DIR"), "/commands.rs"));
 
 // ----------------------------------------------------------------------------
 
 use mostlybot_api::{ChatCommand, CommandMap};
 
-pub const DEFAULT_CMD_COOLDOWN_MS: u64 = 250;
-
 pub fn init() -> CommandMap {
-    let mut map = CommandMap::new();
-    // most commands will just be inserted
-    map.insert(mostlypasta::MostlyPasta::new());
-    map.insert(ping::MostlyPing::new());
-    map.insert(pong::MostlyPong::new());
-    map.insert(ban::MostlyBan::new());
-    map.insert(commands::MostlyCommands::new());
-    map.insert(mostlybot::MostlyBot::new());
-    map.insert(count::Count::new());
-    map.insert(kofi::MostlyKofi::new());
-    map.insert(progress::Progress::new());
-    map.insert(youtube::MostlyYoutube::new());
-    map.insert(git::MostlyGit::new());
-    map.insert(discord::MostlyDiscord::new());
-    map.insert(vods::MostlyVods::new());
-    map.insert(lurk::Lurk::new());
-    map.insert(bot_time::BotTime::new());
-    map.insert(uwu::MostlyUwU::new());
-    map.insert(rewrite::MostlyRewrite::new());
-    map.insert(status::MostlyStatus::new());
-    map.insert(tictactoe::TicTacToe::new());
-    map.insert(js::MostlyJs::new());
+    // commands register themselves with #[command]
+    let mut map = CommandMap::registered();
 
-    // help is special
-    let mut help = help::MostlyHelp::new();
-    help.init(map.clone());
-    map.insert(help);
+    // cmd and help are special, they look through the map they're in
+    map.insert(cmd::MostlyCmd::new());
+    map.insert(help::MostlyHelp::new());
 
     map
 }
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registered_commands() {
+        let map = init();
+
+        for name in [
+            "ping",
+            "pong",
+            "lurk",
+            "ttt",
+            "uwu",
+            "status",
+            "train",
+            "topcommands",
+            "mystats",
+            "permit",
+            "modlog",
+            "undo",
+            "cmd",
+            "help",
+        ] {
+            assert!(map.get(name).is_some(), "!{name} is not registered");
+        }
+    }
+}
@@ -6,10 +6,12 @@
 //!
 //! author: bhavyakukkar
 
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
+use mostlybot_api::{
+    command, CommandArgs, CommandContext, CommandError, CommandResult, Mention, PlatformError,
+    TwitchApiWrapper,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use twitcheventsub::{EventSubError, MessageData};
 
 mod replies {
     pub const LURK_SUCCESSFUL: &str = "have a nice lurk!";
@@ -35,10 +37,48 @@ mod replies {
     pub const LURK_ACTIVITY_NO_STATUS: &str = "just lurking";
 }
 
+/// The command name decides what to do, e.g. ```!unlurk```. Anything chatters add that
+/// isn't an argument is just chatter, the status is set with ```!lurkwith```
+#[derive(CommandArgs)]
+enum LurkArgs {
+    #[arg(subcommand)]
+    Lurk {
+        #[arg(rest, ignore)]
+        chatter: (),
+    },
+    #[arg(subcommand)]
+    Lurkwith {
+        #[arg(rest)]
+        status: Option<String>,
+    },
+    #[arg(subcommand)]
+    Unlurk {
+        #[arg(rest, ignore)]
+        chatter: (),
+    },
+    #[arg(subcommand)]
+    Lurker {
+        user: Option<Mention>,
+        #[arg(rest, ignore)]
+        chatter: (),
+    },
+    #[arg(subcommand)]
+    Lurkers {
+        #[arg(rest, ignore)]
+        chatter: (),
+    },
+}
+
 type Username = String;
 type LurkStatus = Option<String>;
 
 /// A struct holding the users that are currently lurking
+#[command(
+    names = ["lurk", "lurkwith", "unlurk", "lurker", "lurkers"],
+    help = LurkArgs::invocation_usage(),
+    persist
+)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Lurk {
     /// A hash-map of the usernames of the users that are currently lurking mapped to their
     /// lurk-status
@@ -51,44 +91,16 @@ fn reply_ok(s: String) {
 }
 
 // what to do when an api reply fails
-fn reply_err(e: EventSubError) -> anyhow::Error {
+fn reply_err(e: PlatformError) -> CommandError {
     tracing::error!(error = ?e);
-    anyhow!("{:?}", e)
+    e.into()
 }
 
-impl ChatCommand for Lurk {
-    fn new() -> Self {
-        Lurk {
-            users_lurking: HashMap::new(),
-        }
-    }
-
-    fn names() -> Vec<String> {
-        vec![
-            "lurk".to_string(),
-            "lurkwith".to_string(),
-            "unlurk".to_string(),
-            "lurker".to_string(),
-            "lurkers".to_string(),
-        ]
-    }
-
-    fn help(&self) -> String {
-        "usage: `!lurk` or `!lurkwith <status>` or `!unlurk` or `!lurker [@username]` or `!lurkers`"
-            .to_string()
-    }
-
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        // split the words in the messages
-        let mut args = ctx.message.text.split_whitespace();
-        // get the first word of the message which should be the !lurk or !unlurk
-        let command_invoked = args.next().ok_or(anyhow!(
-            "invoked lurk command without any content in the message text"
-        ))?;
-
-        // match the command: lurk or unlurk
-        match command_invoked {
-            "!lurk" => {
+impl Lurk {
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        // the name the command was invoked with decides what to do
+        match LurkArgs::parse_invocation(&ctx.message.text)? {
+            LurkArgs::Lurk { .. } => {
                 match self.users_lurking.get(&ctx.chatter.name) {
                     // called !lurk while not previously lurking and will now start lurking
                     None => {
@@ -112,13 +124,10 @@ impl ChatCommand for Lurk {
                         .map_err(reply_err),
                 }
             }
-            "!lurkwith" => {
-                // join the rest of the words in the message into the lurk-status
-                let status: String = args.fold(String::new(), |a, b| a + " " + b);
-
+            LurkArgs::Lurkwith { status } => {
                 match self
                     .users_lurking
-                    .insert(ctx.chatter.name.clone(), Some(status))
+                    .insert(ctx.chatter.name.clone(), Some(status.unwrap_or_default()))
                 {
                     // called !lurkwith while not previously lurking and will now start lurking
                     None => api
@@ -139,7 +148,7 @@ impl ChatCommand for Lurk {
                         .map_err(reply_err),
                 }
             }
-            "!unlurk" => {
+            LurkArgs::Unlurk { .. } => {
                 match self.users_lurking.remove(&ctx.chatter.name) {
                     // called !unlurk while previously lurking and will now stop lurking
                     Some(previous_status) => api
@@ -159,20 +168,12 @@ impl ChatCommand for Lurk {
                         .map_err(reply_err),
                 }
             }
-            "!lurker" => {
-                // use the second word in the message as the username
-                let mut username = args
-                    .next()
-                    .map(|username| username.to_string())
-                    // if no username provided, assume chatter's name as username
+            LurkArgs::Lurker { user, .. } => {
+                // if no username provided, assume chatter's name as username
+                let username = user
+                    .map(|Mention(username)| username)
                     .unwrap_or(ctx.chatter.name.clone());
 
-                // if username starts with @, remove it
-                let mut chars = username.chars();
-                if chars.next().ok_or(anyhow!("chatter.name received empty"))? == '@' {
-                    username = chars.collect();
-                }
-
                 match self.users_lurking.get(&username) {
                     // called !lurker for username that is lurking with or without a status
                     Some(status) => api
@@ -195,7 +196,7 @@ impl ChatCommand for Lurk {
                         .map_err(reply_err),
                 }
             }
-            "!lurkers" => api
+            LurkArgs::Lurkers { .. } => api
                 .send_chat_message(
                     "Lurkers: ".to_string()
                         + &self
@@ -207,11 +208,6 @@ impl ChatCommand for Lurk {
                 )
                 .map(reply_ok)
                 .map_err(reply_err),
-
-            _ => Err(anyhow!(
-                "invoked lurk command without first word being any of the commands {:?}",
-                Self::names()
-            )),
         }
     }
 }
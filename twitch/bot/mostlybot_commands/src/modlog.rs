@@ -0,0 +1,69 @@
+//! shows moderators the last few messages the chat filters caught
+//!
+//! usage: ```!modlog```
+//!
+//! everything the filters catch is also written to ```moderation.jsonl``` in the data dir,
+//! with the full message, for going through after a stream
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use tracing::instrument;
+
+/// How many filtered messages ```!modlog``` lists
+const SHOWN: usize = 5;
+
+#[command(names = ["modlog"], help = "usage: !modlog", role = "moderator")]
+#[instrument(skip(api))]
+pub fn mostly_mod_log(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let recent = ctx.moderation().recent();
+    if recent.is_empty() {
+        return api.reply(ctx, "nothing filtered yet");
+    }
+
+    let shown: Vec<_> = recent
+        .iter()
+        .take(SHOWN)
+        .map(|event| format!("{} ({}, {})", event.user_name, event.filter, event.action))
+        .collect();
+    api.reply(ctx, format!("last filtered: {}", shown.join(", ")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, Metrics, MockTwitchEventSubApi, Moderation,
+        ModerationConfig, Services,
+    };
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let moderation = Moderation::default();
+        let services = Services {
+            moderation: moderation.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+
+        let msg = chat_message("modperson", "!modlog", &["moderator"]);
+        let ctx = CommandContext::with_services(&msg, services);
+        MostlyModLog::new().handle(&mut api, &ctx).unwrap();
+
+        let config = ModerationConfig {
+            enabled: true,
+            ..ModerationConfig::default()
+        };
+        for (login, text) in [("spammer", "visit evil.com"), ("shouter", "WHY IS NOBODY TALKING")] {
+            moderation.moderate(&chat_message(login, text, &[]), &config, &mut api, &Metrics::default());
+        }
+        MostlyModLog::new().handle(&mut api, &ctx).unwrap();
+
+        let sent = api.as_mock().unwrap().sent_messages();
+        assert_eq!(sent[0], "nothing filtered yet");
+        assert_eq!(
+            sent[sent.len() - 1],
+            "last filtered: shouter (caps, warn), spammer (links, warn)"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! lets a chatter post links for a couple of minutes, when the link filter would otherwise
+//! delete them
+//!
+//! usage: ```!permit @user```
+//!
+//! how long a permit lasts is ```links.permit``` under ```[moderation]``` in the config
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{
+    command, format_duration, CommandArgs, CommandContext, CommandResult, Mention,
+    TwitchApiWrapper,
+};
+use tracing::instrument;
+
+#[derive(Debug, PartialEq, CommandArgs)]
+struct PermitArgs {
+    user: Mention,
+}
+
+#[command(names = ["permit"], help = PermitArgs::usage("permit"), role = "moderator")]
+#[instrument(skip(api))]
+pub fn mostly_permit(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let PermitArgs {
+        user: Mention(user),
+    } = PermitArgs::parse(&ctx.message.text)?;
+
+    let duration = ctx.moderation().permit(&user);
+    api.reply(
+        ctx,
+        format!("@{user} can post links for the next {}", format_duration(duration)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, Filter, MockTwitchEventSubApi, Moderation,
+        ModerationConfig, Services,
+    };
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let moderation = Moderation::default();
+        let services = Services {
+            moderation: moderation.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        let config = ModerationConfig::default();
+        let link = chat_message("chatter", "look at example.com", &[]);
+        assert_eq!(moderation.check(&link, &config), Some(Filter::Links));
+
+        let msg = chat_message("modperson", "!permit @Chatter", &["moderator"]);
+        let ctx = CommandContext::with_services(&msg, services.clone());
+        MostlyPermit::new().handle(&mut api, &ctx).unwrap();
+        assert_eq!(moderation.check(&link, &config), None);
+
+        let msg = chat_message("modperson", "!permit", &["moderator"]);
+        let ctx = CommandContext::with_services(&msg, services);
+        assert!(MostlyPermit::new().handle(&mut api, &ctx).is_err());
+
+        assert_eq!(
+            api.as_mock().unwrap().sent_messages(),
+            ["@Chatter can post links for the next 2m"]
+        );
+    }
+}
@@ -4,54 +4,29 @@
 //!
 //! author: Cathyprime
 
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use tracing::{debug, error, instrument};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use tracing::instrument;
 
-pub struct MostlyPong {}
-
-impl ChatCommand for MostlyPong {
-    fn new() -> Self {
-        Self {}
-    }
-
-    fn names() -> Vec<String> {
-        vec!["pong".to_string()]
-    }
-
-    fn help(&self) -> String {
-        "usage: !pong".to_string()
-    }
-
-    #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        match api.send_chat_message_with_reply("FeelsWeirdMan", Some(&ctx.message_id)) {
-            Ok(s) => {
-                debug!(reply = %s);
-                Ok(())
-            }
-            Err(e) => {
-                error!(error = ?e);
-                Err(anyhow!("{:?}", e))
-            }
-        }
-    }
+#[command(names = ["pong"], help = "usage: !pong")]
+#[instrument(skip(api))]
+pub fn mostly_pong(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    api.reply(ctx, "FeelsWeirdMan")
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use mostlybot_api::{MockTwitchEventSubApi, TwitchApiWrapper};
+    use mostlybot_api::{BotRng, ChatCommand, MockTwitchEventSubApi};
 
     #[test]
     fn handle() {
-        let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
         let mut cmd = MostlyPong::new();
 
         let test_msg = r###"{"broadcaster_user_id":"938429017","broadcaster_user_name":"mostlymaxi","broadcaster_user_login":"mostlymaxi","chatter_user_id":"938429017","chatter_user_name":"mostlymaxi","chatter_user_login":"mostlymaxi","message_id":"3104f083-2bdb-4d6a-bb5d-30b407876ea4","message":{"text":"!pong","fragments":[{"type":"text","text":"!pong","cheermote":null,"emote":null,"mention":null}]},"color":"#FF0000","badges":[{"set_id":"broadcaster","id":"1","info":""},{"set_id":"subscriber","id":"0","info":"3"}],"message_type":"text","cheer":null,"reply":null,"channel_points_custom_reward_id":null,"channel_points_animation_id":null}"###;
 
-        let ctx = serde_json::from_str(test_msg).unwrap();
+        let msg = serde_json::from_str(test_msg).unwrap();
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(0));
         cmd.handle(&mut api, &ctx).unwrap();
     }
 }
@@ -4,40 +4,41 @@
 //!
 //! author: FreeFull
 
-use anyhow::anyhow;
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use rand::{thread_rng, Rng};
-use tracing::{debug, error, instrument};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use rand::Rng;
+use tracing::instrument;
 
-pub struct Progress {}
+#[command(names = ["progress"], help = "usage: !progress")]
+#[instrument(skip(api))]
+pub fn progress(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let progress = ctx.rng().gen_range(0.0..100.0);
+    api.reply(ctx, format!("Progress: {progress:.6}% done!"))
+}
 
-impl ChatCommand for Progress {
-    fn new() -> Self {
-        Progress {}
-    }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{test_util::chat_message, BotRng, ChatCommand, MockTwitchEventSubApi};
 
-    fn names() -> Vec<String> {
-        vec!["progress".to_string()]
-    }
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let mut cmd = Progress::new();
 
-    #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let progress = thread_rng().gen_range(0.0..100.0);
-        let progress = format!("Progress: {progress:.6}% done!");
-        match api.send_chat_message_with_reply(&progress, Some(&ctx.message_id)) {
-            Ok(s) => {
-                debug!(reply = %s);
-                Ok(())
-            }
-            Err(e) => {
-                error!(error = ?e);
-                Err(anyhow!("{:?}", e))
-            }
-        }
-    }
+        let msg = chat_message("mostlymaxi", "!progress", &[]);
+        let ctx = CommandContext::new(&msg, BotRng::seed_from_u64(42));
+        cmd.handle(&mut api, &ctx).unwrap();
+        cmd.handle(&mut api, &ctx).unwrap();
 
-    fn help(&self) -> String {
-        "usage: !progress".to_string()
+        let mut rng = BotRng::seed_from_u64(42);
+        let first: f64 = rng.gen_range(0.0..100.0);
+        let second: f64 = rng.gen_range(0.0..100.0);
+        assert_eq!(
+            api.as_mock().unwrap().sent_messages(),
+            [
+                format!("Progress: {first:.6}% done!"),
+                format!("Progress: {second:.6}% done!"),
+            ]
+        );
     }
 }
@@ -0,0 +1,16 @@
+//! reloads the bot config without restarting (broadcaster only)
+//!
+//! usage: ```!reload```
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{command, CommandContext, CommandResult, ReloadTrigger, TwitchApiWrapper};
+
+/// The reload happens right after this command returns, the bot replies with how it went
+#[command(names = ["reload"], help = "usage: !reload", cooldown = "5s", role = "broadcaster")]
+pub fn mostly_reload(_api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    ctx.reload().request(ReloadTrigger::Chat {
+        message_id: ctx.message_id.clone(),
+    });
+    Ok(())
+}
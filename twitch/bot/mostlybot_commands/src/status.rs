@@ -4,11 +4,16 @@
 //!
 //! author: lunispang
 
-use mostlybot_api::{ChatCommand, RateLimit, TwitchApiWrapper};
-use twitcheventsub::MessageData;
+use mostlybot_api::{
+    format_duration, register_command, ChatCommand, CommandContext, CommandResult, RateLimit,
+    TwitchApiWrapper,
+};
 
 pub struct MostlyStatus;
 
+// allows 5 uses a second, which #[command] can't express
+register_command!(MostlyStatus);
+
 impl ChatCommand for MostlyStatus {
     fn new() -> Self {
         Self {}
@@ -22,12 +27,69 @@ impl ChatCommand for MostlyStatus {
         "usage: !status".to_string()
     }
 
-    fn handle(&mut self, api: &mut TwitchApiWrapper, _: &MessageData) -> anyhow::Result<()> {
-        let _ = api.send_chat_message("Bot is offline");
-        Ok(())
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        let stats = ctx.stats();
+
+        let last_error = stats
+            .last_error()
+            .map_or("never".to_owned(), |ago| format!("{} ago", format_duration(ago)));
+        let storage = if stats.storage_ok(ctx.data_dir()) {
+            "ok"
+        } else {
+            "failing"
+        };
+
+        api.reply(
+            ctx,
+            format!(
+                "online for {} | v{} | {} commands | {} messages | last error {} | storage {}",
+                format_duration(stats.uptime()),
+                stats.version(),
+                stats.commands_loaded(),
+                ctx.metrics().messages_received(),
+                last_error,
+                storage,
+            ),
+        )
     }
 
     fn rate_limit(&self) -> RateLimit {
         RateLimit::new(5, std::time::Duration::from_secs(1))
     }
 }
+
+// Tests ----------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, MockTwitchEventSubApi, RuntimeStats, Services,
+    };
+
+    #[test]
+    fn handle() {
+        let data_dir =
+            std::env::temp_dir().join(format!("mostlybot-status-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let services = Services {
+            data_dir: data_dir.clone(),
+            stats: RuntimeStats::new("1.2.3"),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        services.stats.set_commands_loaded(12);
+        services.metrics.message_received();
+
+        let msg = chat_message("mostlymaxi", "!status", &[]);
+        let ctx = CommandContext::with_services(&msg, services);
+        MostlyStatus::new().handle(&mut api, &ctx).unwrap();
+
+        let reply = &api.as_mock().unwrap().sent_messages()[0];
+        assert!(reply.starts_with("online for 0.0s | v1.2.3 | 12 commands | 1 messages"));
+        assert!(reply.ends_with("| 1 messages | last error never | storage ok"));
+
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -4,33 +4,15 @@
 //!
 //! TODO: author: <twitch name>
 
-use anyhow::{anyhow, Result};
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use tracing::{debug, error, instrument};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use tracing::instrument;
 
-// TODO: rename struct
-struct CommandStruct {}
-
-// TODO: implement the ChatCommand trait for your CommandStruct
-impl ChatCommand for CommandStruct {
-    fn new() -> Self {
-        Self {}
-    }
-
-    // TODO: add command "names"
-    fn names() -> Vec<String> {
-        vec!["<name>".to_string()]
-    }
-
-    // TODO: create useful help message
-    fn help(&self) -> String {
-        "usage: !<name> <args>".to_string()
-    }
-
-    // TODO: do stuff
-    #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> Result<()> {
-        todo!()
-    }
+// TODO: add command "names" and a useful help message
+// (cooldown and role are optional, see ping.rs)
+#[command(names = ["<name>"], help = "usage: !<name> <args>")]
+#[instrument(skip(api))]
+// TODO: rename function, it becomes the command struct in CamelCase
+fn command_fn(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    // TODO: do stuff (return CommandError::Usage if the args are wrong, it replies with help)
+    todo!()
 }
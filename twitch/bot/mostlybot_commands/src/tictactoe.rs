@@ -1,13 +1,14 @@
 //! Play Tic Tac Toe with a computer because you don't have any REAL friends!
 //!
-//! usage (!tictactoe/!ttt) (print/reset/1..=9)
+//! usage: ```!tictactoe print``` or ```!tictactoe reset``` or ```!tictactoe <1-9>``` (or !ttt)
 //!
 //! author: lunispang
 
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
+use mostlybot_api::{command, CommandArgs, CommandContext, CommandResult, TwitchApiWrapper};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 enum Mark {
     X,
     O,
@@ -36,7 +37,7 @@ enum State {
     Winner(Mark),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 struct Board {
     marks: [Option<Mark>; 9],
 }
@@ -176,28 +177,32 @@ fn minimax(board: &Board) -> (usize, i8) {
     *results.iter().max_by_key(|t| t.1).unwrap()
 }
 
+#[derive(CommandArgs)]
+enum TicTacToeArgs {
+    #[arg(subcommand)]
+    Print,
+    #[arg(subcommand)]
+    Reset,
+    Place {
+        square: usize,
+    },
+}
+
+#[command(names = ["tictactoe", "ttt"], help = TicTacToeArgs::usage("tictactoe"), persist)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct TicTacToe {
     players: HashMap<String, Board>,
 }
 
-impl ChatCommand for TicTacToe {
-    fn new() -> Self {
-        Self {
-            players: HashMap::new(),
+impl TicTacToe {
+    fn print(api: &mut TwitchApiWrapper, board: &Board) {
+        for row in board.print() {
+            let _ = api.send_chat_message(row);
         }
     }
-    fn help(&self) -> String {
-        "usage: !tictactoe/!ttt (1-9/reset/print)".to_owned()
-    }
-    fn names() -> Vec<String> {
-        vec!["tictactoe".to_owned(), "ttt".to_owned()]
-    }
-    fn handle(
-        &mut self,
-        api: &mut TwitchApiWrapper,
-        ctx: &twitcheventsub::MessageData,
-    ) -> anyhow::Result<()> {
-        let arg = ctx.message.text.split_whitespace().nth(1);
+
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+        let args = TicTacToeArgs::parse(&ctx.message.text)?;
         if let Some(board) = self.players.get(&ctx.chatter.id) {
             match board.get_state() {
                 State::Turn(_) => {}
@@ -206,53 +211,29 @@ impl ChatCommand for TicTacToe {
                 }
             }
         }
-        match arg {
-            None => {
-                let _ = api.send_chat_message(self.help());
-                return Ok(());
-            }
-            Some("reset") => {
+        match args {
+            TicTacToeArgs::Reset => {
                 self.players.remove(&ctx.chatter.id);
             }
-            Some("print") => {
-                if !self.players.contains_key(&ctx.chatter.id) {
-                    self.players.insert(ctx.chatter.id.clone(), Board::new());
-                }
-                let board = self.players.get_mut(&ctx.chatter.id).unwrap();
-                for row in board.print() {
-                    let _ = api.send_chat_message(row);
-                }
+            TicTacToeArgs::Print => {
+                let board = self
+                    .players
+                    .entry(ctx.chatter.id.clone())
+                    .or_insert_with(Board::new);
+                Self::print(api, board);
             }
-            _ => {
-                if let Some(arg) = arg {
-                    match arg.chars().next() {
-                        Some(c @ '1'..='9') => {
-                            if !self.players.contains_key(&ctx.chatter.id) {
-                                self.players.insert(ctx.chatter.id.clone(), Board::new());
-                            }
-                            let board = self.players.get_mut(&ctx.chatter.id).unwrap();
-                            if board
-                                .place((c.to_digit(10).unwrap() - 1) as usize)
-                                .is_some()
-                            {
-                                let bot_move = minimax(board).0;
-                                board.place(bot_move);
-                                for row in board.print() {
-                                    let _ = api.send_chat_message(row);
-                                }
-                            } else {
-                                let _ = api.send_chat_message("Invalid move!");
-                            }
-                        }
-                        _ => {
-                            let _ = api.send_chat_message(self.help());
-                            return Ok(());
-                        }
-                    }
+            TicTacToeArgs::Place { square } => {
+                let board = self
+                    .players
+                    .entry(ctx.chatter.id.clone())
+                    .or_insert_with(Board::new);
+                if (1..=9).contains(&square) && board.place(square - 1).is_some() {
+                    let bot_move = minimax(board).0;
+                    board.place(bot_move);
+                    Self::print(api, board);
                 } else {
-                    let _ = api.send_chat_message(self.help());
+                    let _ = api.send_chat_message("Invalid move!");
                 }
-                return Ok(());
             }
         }
         Ok(())
@@ -0,0 +1,55 @@
+//! sends a train across the points-term
+//!
+//! usage: ```!train```
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
+use serde_json::json;
+use tracing::instrument;
+
+/// Publishes ```{"action": "train", "by": <chatter>}``` for the points-term to pick up,
+/// the topic is declared in the attribute so the command can't publish anywhere else
+#[command(names = ["train"], help = "usage: !train", cooldown = "30s", topics = ["points-term"])]
+#[instrument(skip(api))]
+pub fn mostly_train(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    ctx.publish(
+        "points-term",
+        &json!({ "action": "train", "by": ctx.chatter.name }),
+    )?;
+    api.reply(ctx, "choo choo")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, EventBus, MemoryEvents,
+        MockTwitchEventSubApi, Services,
+    };
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let mut cmd = MostlyTrain::new();
+        let events = MemoryEvents::default();
+        let services = Services {
+            events: EventBus::new(events.clone()),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+
+        let msg = chat_message("mostlymaxi", "!train", &[]);
+        let ctx = CommandContext::with_services(&msg, services).publishing_to(MostlyTrain::topics());
+
+        cmd.handle(&mut api, &ctx).unwrap();
+
+        assert_eq!(
+            events.events(),
+            [(
+                "points-term".to_owned(),
+                r#"{"action":"train","by":"mostlymaxi"}"#.to_owned()
+            )]
+        );
+        assert_eq!(api.as_mock().unwrap().sent_messages(), ["choo choo"]);
+    }
+}
@@ -0,0 +1,199 @@
+//! takes back what the bot said, for when a fun command says something unfortunate
+//!
+//! usage: ```!undo``` deletes the bot's last message, ```!undo @user``` every recent message
+//! the bot sent because of them
+//!
+//! only the last 100 messages are remembered, and twitch won't delete anything older than a
+//! few hours
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{
+    command, CommandArgs, CommandContext, CommandResult, Mention, ModAction, SentMessage,
+    TwitchApiWrapper,
+};
+use tracing::instrument;
+
+#[derive(Debug, PartialEq, CommandArgs)]
+struct UndoArgs {
+    user: Option<Mention>,
+}
+
+fn delete(api: &mut TwitchApiWrapper, message: SentMessage) -> CommandResult {
+    tracing::info!(id = %message.id, command = %message.command, user = %message.user_login, "undoing");
+    api.moderate(ModAction::Delete {
+        message_id: message.id,
+    })?;
+    Ok(())
+}
+
+// no cooldown, a moderator cleaning up after a spree shouldn't have to wait between messages,
+// and no undoing undo's own replies, the next !undo should take back what the spree said
+#[command(
+    names = ["undo"],
+    help = UndoArgs::usage("undo"),
+    cooldown = "0s",
+    role = "moderator",
+    no_undo
+)]
+#[instrument(skip(api))]
+pub fn mostly_undo(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let sent = ctx.sent_messages();
+
+    match UndoArgs::parse(&ctx.message.text)?.user {
+        // nothing to say when it worked, chat sees the message go
+        None => match sent.take_last() {
+            Some(message) => delete(api, message),
+            None => api.reply(ctx, "nothing to undo"),
+        },
+        Some(Mention(user)) => {
+            let messages = sent.take_by(&user);
+            if messages.is_empty() {
+                return api.reply(ctx, format!("nothing to undo for @{user}"));
+            }
+
+            // one message twitch won't delete shouldn't keep the rest up
+            let count = messages.len();
+            let deleted = messages
+                .into_iter()
+                .filter(|message| match delete(api, message.clone()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!(id = %message.id, error = ?e, "couldn't undo");
+                        false
+                    }
+                })
+                .count();
+
+            if deleted < count {
+                return api.reply(
+                    ctx,
+                    format!("deleted {deleted} of {count} messages for @{user}"),
+                );
+            }
+            let plural = if count == 1 { "" } else { "s" };
+            api.reply(ctx, format!("deleted {count} message{plural} for @{user}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, ChatPlatform, MockTwitchEventSubApi,
+        PlatformError, SentMessages, Services,
+    };
+
+    /// Twitch refusing to delete message ```b```
+    #[derive(Default)]
+    struct Refusing(Vec<String>);
+
+    impl ChatPlatform for Refusing {
+        fn send(&mut self, message: &str) -> Result<String, PlatformError> {
+            self.0.push(message.to_owned());
+            Ok(self.0.len().to_string())
+        }
+
+        fn reply(&mut self, message: &str, _parent_id: &str) -> Result<String, PlatformError> {
+            self.send(message)
+        }
+
+        fn announce(&mut self, message: &str) -> Result<(), PlatformError> {
+            self.send(message).map(|_| ())
+        }
+
+        fn moderate(&mut self, action: &ModAction) -> Result<(), PlatformError> {
+            match action {
+                ModAction::Delete { message_id } if message_id == "b" => {
+                    Err(PlatformError::Unsupported("deleting that"))
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn whisper(&mut self, _user_id: &str, _message: &str) -> Result<(), PlatformError> {
+            Err(PlatformError::Unsupported("whispering"))
+        }
+    }
+
+    fn sent(id: &str, login: &str) -> SentMessage {
+        SentMessage {
+            id: id.to_owned(),
+            user_id: format!("id_{login}"),
+            user_login: login.to_owned(),
+            command: "uwu".to_owned(),
+        }
+    }
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let messages = SentMessages::default();
+        let services = Services {
+            sent: messages.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        for (id, login) in [("a", "troll"), ("b", "chatter"), ("c", "troll"), ("d", "chatter")] {
+            messages.record(sent(id, login));
+        }
+
+        for text in ["!undo", "!undo @Troll", "!undo @troll", "!undo", "!undo"] {
+            let msg = chat_message("modperson", text, &["moderator"]);
+            let ctx = CommandContext::with_services(&msg, services.clone());
+            MostlyUndo::new().handle(&mut api, &ctx).unwrap();
+        }
+
+        let mock = api.as_mock().unwrap();
+        let deleted: Vec<_> = mock
+            .mod_actions()
+            .iter()
+            .map(|action| match action {
+                ModAction::Delete { message_id } => message_id.as_str(),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(deleted, ["d", "a", "c", "b"]);
+        assert_eq!(
+            mock.sent_messages(),
+            [
+                "deleted 2 messages for @Troll",
+                "nothing to undo for @troll",
+                "nothing to undo",
+            ]
+        );
+    }
+
+    #[test]
+    fn partly_failed() {
+        let mut api = TwitchApiWrapper::new(Refusing::default());
+        let messages = SentMessages::default();
+        let services = Services {
+            sent: messages.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+        for id in ["a", "b", "c"] {
+            messages.record(sent(id, "troll"));
+        }
+
+        let msg = chat_message("modperson", "!undo @troll", &["moderator"]);
+        let ctx = CommandContext::with_services(&msg, services);
+        MostlyUndo::new().handle(&mut api, &ctx).unwrap();
+
+        assert_eq!(
+            api.platform::<Refusing>().unwrap().0,
+            ["deleted 2 of 3 messages for @troll"]
+        );
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(UndoArgs::parse("!undo").unwrap(), UndoArgs { user: None });
+        assert_eq!(
+            UndoArgs::parse("!undo @someone").unwrap(),
+            UndoArgs {
+                user: Some(Mention("someone".to_owned()))
+            }
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! which commands chat actually uses, counted by the bot for every command it runs
+//!
+//! usage: ```!topcommands [stream]``` or ```!mystats```
+//!
+//! the full counts (per command, per chatter, per stream) can be exported with
+//! ```mostlybot usage-report [csv|json]```
+//!
+//! author: mostlymaxi
+
+use mostlybot_api::{
+    command, CommandArgs, CommandContext, CommandResult, TwitchApiWrapper, UsageReport,
+};
+use tracing::instrument;
+
+/// How many commands ```!topcommands``` lists
+const TOP: usize = 5;
+
+#[derive(Debug, PartialEq, CommandArgs)]
+enum TopArgs {
+    /// Only the current stream
+    #[arg(subcommand)]
+    Stream,
+    All,
+}
+
+#[command(names = ["topcommands"], help = TopArgs::usage("topcommands"), cooldown = "10s")]
+#[instrument(skip(api))]
+pub fn mostly_top_commands(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let args = TopArgs::parse(&ctx.message.text)?;
+    let report = ctx.usage().report();
+
+    let (what, commands) = match args {
+        TopArgs::All => ("top commands", Some(&report.commands)),
+        TopArgs::Stream => (
+            "top commands this stream",
+            report.current_stream().map(|stream| &stream.commands),
+        ),
+    };
+
+    let top: Vec<_> = commands
+        .map(|commands| UsageReport::top(commands, TOP))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, count)| format!("!{name} ({count})"))
+        .collect();
+    if top.is_empty() {
+        return api.reply(ctx, "no commands used yet");
+    }
+
+    api.reply(ctx, format!("{what}: {}", top.join(", ")))
+}
+
+#[command(names = ["mystats"], help = "usage: !mystats", cooldown = "5s")]
+#[instrument(skip(api))]
+pub fn mostly_my_stats(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    let report = ctx.usage().report();
+    let Some(user) = report.users.get(&ctx.chatter.id) else {
+        return api.reply(ctx, "you haven't used any commands yet");
+    };
+
+    let (favorite, count) = UsageReport::top(&user.commands, 1)[0];
+    api.reply(
+        ctx,
+        format!(
+            "you've used {} commands, mostly !{favorite} ({count})",
+            user.total()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mostlybot_api::{
+        test_util::chat_message, BotRng, ChatCommand, MockTwitchEventSubApi, Services, Usage,
+    };
+
+    #[test]
+    fn handle() {
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
+        let usage = Usage::default();
+        let services = Services {
+            usage: usage.clone(),
+            ..Services::new(BotRng::seed_from_u64(0))
+        };
+
+        let msg = chat_message("123", "!mystats", &[]);
+        let ctx = CommandContext::with_services(&msg, services.clone());
+        MostlyMyStats::new().handle(&mut api, &ctx).unwrap();
+
+        usage.record("ping", "123", "chatter", 0);
+        usage.record("ping", "123", "chatter", 1);
+        usage.record("lurk", "456", "lurker", 2);
+        MostlyMyStats::new().handle(&mut api, &ctx).unwrap();
+
+        let msg = chat_message("123", "!topcommands", &[]);
+        let ctx = CommandContext::with_services(&msg, services.clone());
+        MostlyTopCommands::new().handle(&mut api, &ctx).unwrap();
+
+        let msg = chat_message("123", "!topcommands stream", &[]);
+        let ctx = CommandContext::with_services(&msg, services);
+        MostlyTopCommands::new().handle(&mut api, &ctx).unwrap();
+
+        assert_eq!(
+            api.as_mock().unwrap().sent_messages(),
+            [
+                "you haven't used any commands yet",
+                "you've used 2 commands, mostly !ping (2)",
+                "top commands: !ping (2), !lurk (1)",
+                "top commands this stream: !ping (2), !lurk (1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(TopArgs::parse("!topcommands").unwrap(), TopArgs::All);
+        assert_eq!(TopArgs::parse("!topcommands stream").unwrap(), TopArgs::Stream);
+        assert!(TopArgs::parse("!topcommands everything").is_err());
+    }
+}
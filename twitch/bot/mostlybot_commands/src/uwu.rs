@@ -11,12 +11,10 @@
 
 use std::path::PathBuf;
 
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use rand::seq::SliceRandom;
+use mostlybot_api::{command, CommandArgs, CommandContext, CommandResult, TwitchApiWrapper};
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
-use twitcheventsub::MessageData;
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Kaomoji {
     string: String,
@@ -53,10 +51,8 @@ impl KaomojiList {
         self.0.len()
     }
 
-    fn random(&self) -> Kaomoji {
-        self.0
-            .choose(&mut rand::thread_rng())
-            .map_or(Kaomoji::default(), |v| v.clone())
+    fn random<R: Rng>(&self, rng: &mut R) -> Kaomoji {
+        self.0.choose(rng).map_or(Kaomoji::default(), |v| v.clone())
     }
 
     fn get_index(&self, index: isize) -> Option<Kaomoji> {
@@ -135,56 +131,33 @@ impl Default for KaomojiList {
 }
 
 /// This just parses the command arguments to make my life easier
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, CommandArgs)]
 enum MostlyUwUArgs {
     DisplayRandom,
-    DisplayIndex { index: isize },
-    AddKaomoji { kaomoji: String },
-    RemoveKaomoji { kaomoji: String },
-    RemoveIndex { index: isize },
+    DisplayIndex {
+        index: isize,
+    },
+    AddKaomoji {
+        #[arg(rest)]
+        kaomoji: String,
+    },
+    #[arg(subcommand = "remove")]
     RemoveLast,
+    #[arg(subcommand = "remove")]
+    RemoveIndex {
+        index: isize,
+    },
+    #[arg(subcommand = "remove")]
+    RemoveKaomoji {
+        #[arg(rest)]
+        kaomoji: String,
+    },
 }
 
-impl TryFrom<&twitcheventsub::MessageData> for MostlyUwUArgs {
-    type Error = anyhow::Error;
-
-    fn try_from(ctx: &twitcheventsub::MessageData) -> Result<Self, Self::Error> {
-        let content = ctx
-            .message
-            .text
-            .split(' ')
-            .skip(1)
-            .collect::<Vec<_>>()
-            .join(" ")
-            .trim()
-            .to_owned();
-
-        if content.is_empty() {
-            Ok(MostlyUwUArgs::DisplayRandom)
-        } else {
-            let first = content.split(' ').nth(0).unwrap();
-            if first.to_lowercase() == "remove" {
-                let rest = content.split(' ').skip(1).collect::<Vec<_>>().join(" ");
-                if rest.is_empty() {
-                    Ok(MostlyUwUArgs::RemoveLast)
-                } else if let Ok(index) = rest.parse::<isize>() {
-                    Ok(MostlyUwUArgs::RemoveIndex { index })
-                } else {
-                    Ok(MostlyUwUArgs::RemoveKaomoji {
-                        kaomoji: rest.trim().to_owned(),
-                    })
-                }
-            } else if let Ok(index) = first.parse::<isize>() {
-                Ok(MostlyUwUArgs::DisplayIndex { index })
-            } else {
-                Ok(MostlyUwUArgs::AddKaomoji {
-                    kaomoji: content.trim().to_owned(),
-                })
-            }
-        }
-    }
-}
-
+#[command(
+    names = ["uwu", "UwU", "owo", "OwO", "kaomoji"],
+    help = format!("Responds UwU kaomoji, {}", MostlyUwUArgs::usage("uwu"))
+)]
 pub struct MostlyUwU {
     kaomoji_file_path: PathBuf,
     /// If to allow any user to add a kaomoji
@@ -215,39 +188,24 @@ impl MostlyUwU {
     }
 }
 
-impl ChatCommand for MostlyUwU {
-    fn new() -> Self {
+impl Default for MostlyUwU {
+    fn default() -> Self {
         Self {
             kaomoji_file_path: "kaomoji.json".into(),
             kaomoji_add_permissions_user: false,
         }
     }
+}
 
-    fn names() -> Vec<String> {
-        vec![
-            "uwu".to_string(),
-            "UwU".to_string(),
-            "owo".to_string(),
-            "OwO".to_string(),
-            "kaomoji".to_string(),
-        ]
-    }
-
-    fn help(&self) -> String {
-        "Responds UwU kaomoji\nusage: \"!uwu ?[index]\" - Random kaomoji\n\"!uwu [kaomoji]\" - Add kaomoji\n\"!uwu remove ?[kaomoji / index]\" - Remove kaomoji".to_string()
-    }
-
+impl MostlyUwU {
     #[instrument(skip(self, api))]
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
+    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
         // Collect Args -> Load kaomoji -> Validate permissions -> Execute command
 
         // Parse command arguments
-        let Ok(args) = MostlyUwUArgs::try_from(ctx) else {
-            let _ = api.send_chat_message_with_reply("Invalid arguments.", Some(&ctx.message_id));
-            return Ok(());
-        };
+        let args = MostlyUwUArgs::parse(&ctx.message.text)?;
 
-        let mut invalid_permissions = || -> anyhow::Result<()> {
+        let mut invalid_permissions = || -> CommandResult {
             let _ = api
                 .send_chat_message_with_reply("You can't do that. (•̀⤙•́ )", Some(&ctx.message_id));
             Ok(())
@@ -257,7 +215,7 @@ impl ChatCommand for MostlyUwU {
 
         match args {
             MostlyUwUArgs::DisplayRandom => {
-                let kaomoji = kaomoji_list.random();
+                let kaomoji = kaomoji_list.random(&mut ctx.rng());
                 let _ = api.send_chat_message_with_reply(&kaomoji.string, Some(&ctx.message_id));
             }
             MostlyUwUArgs::DisplayIndex { index } => {
@@ -372,7 +330,7 @@ mod test {
     use twitcheventsub::MessageData;
 
     use super::*;
-    use mostlybot_api::{MockTwitchEventSubApi, TwitchApiWrapper};
+    use mostlybot_api::{BotRng, ChatCommand, MockTwitchEventSubApi, TwitchApiWrapper};
 
     fn create_test_msg(content: &str) -> MessageData {
         serde_json::from_str(&format!(
@@ -388,26 +346,74 @@ mod test {
         )).unwrap()
     }
 
+    /// Runs the command with a fixed seed and returns the reply it sent
+    fn reply(
+        cmd: &mut MostlyUwU,
+        api: &mut TwitchApiWrapper,
+        msg: &MessageData,
+    ) -> anyhow::Result<String> {
+        cmd.handle(api, &CommandContext::new(msg, BotRng::seed_from_u64(SEED)))?;
+        Ok(api
+            .as_mock()
+            .unwrap()
+            .sent_messages()
+            .last()
+            .unwrap()
+            .clone())
+    }
+
+    const SEED: u64 = 69;
+
     #[test]
     fn handle() -> anyhow::Result<()> {
-        let mut api = TwitchApiWrapper::Test(MockTwitchEventSubApi::init_twitch_api());
+        let mut api = TwitchApiWrapper::new(MockTwitchEventSubApi::init_twitch_api());
         let mut cmd = MostlyUwU::new();
         cmd.kaomoji_file_path = "kaomoji_test.json".into();
-
-        // Use `cargo test commands::uwu::test::handle -- --nocapture` to validate response output
-
-        cmd.handle(&mut api, &create_test_msg("!uwu"))?; // {}
-        cmd.handle(&mut api, &create_test_msg("!uwu remove UwU"))?; // UwU was removed
-        cmd.handle(&mut api, &create_test_msg("!uwu remove UwU"))?; // Could not find
-        cmd.handle(&mut api, &create_test_msg("!uwu UwU"))?; // UwU was added
-        cmd.handle(&mut api, &create_test_msg("!uwu UwU"))?; // UwU already exists
-
-        cmd.handle(&mut api, &create_test_msg("!uwu 0"))?; // OwO
-        cmd.handle(&mut api, &create_test_msg("!uwu -1"))?; // UwU
-
-        cmd.handle(&mut api, &create_test_user_msg("!uwu"))?; // {}
-        cmd.handle(&mut api, &create_test_user_msg("!uwu remove UwU"))?; // You can't do that.
-        cmd.handle(&mut api, &create_test_user_msg("!uwu UwU"))?; // You can't do that.
+        let rng = || BotRng::seed_from_u64(SEED);
+
+        // Use `cargo test commands::uwu::test::handle -- --nocapture` to see response output
+
+        let random = KaomojiList::default().random(&mut rng()).string;
+        assert_eq!(reply(&mut cmd, &mut api, &create_test_msg("!uwu"))?, random);
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu remove UwU"))?,
+            "UwU was removed ꃋᴖꃋ"
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu remove UwU"))?,
+            "Couldn't find kaomoji to remove (ㅠ‸ㅠ)"
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu UwU"))?,
+            "UwU was added ( ˶ˆᗜˆ˵ )"
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu UwU"))?,
+            "UwU Already exists (◔_◔)"
+        );
+
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu 0"))?,
+            "OwO"
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_msg("!uwu -1"))?,
+            "UwU"
+        );
+
+        let random = cmd.load_kaomoji_list()?.random(&mut rng()).string;
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_user_msg("!uwu"))?,
+            random
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_user_msg("!uwu remove UwU"))?,
+            "You can't do that. (•̀⤙•́ )"
+        );
+        assert_eq!(
+            reply(&mut cmd, &mut api, &create_test_user_msg("!uwu UwU"))?,
+            "You can't do that. (•̀⤙•́ )"
+        );
 
         cmd.delete_kaomoji_list()?;
 
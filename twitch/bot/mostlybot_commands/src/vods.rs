@@ -3,30 +3,9 @@
 //! usage: ```!vods```
 //!
 //! author: mostlymaxi
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
 
-pub struct MostlyVods {}
-
-impl ChatCommand for MostlyVods {
-    fn new() -> Self {
-        MostlyVods {}
-    }
-
-    fn help(&self) -> String {
-        "usage: !vods".to_string()
-    }
-
-    fn names() -> Vec<String> {
-        vec!["vods".to_string(), "vod".to_string()]
-    }
-
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let _ = api.send_chat_message_with_reply(
-            "check out maxi's vods on youtube!: https://www.youtube.com/@mostlyvods",
-            Some(&ctx.message_id),
-        );
-
-        Ok(())
-    }
+#[command(names = ["vods", "vod"], help = "usage: !vods")]
+pub fn mostly_vods(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    api.reply(ctx, "check out maxi's vods on youtube!: https://www.youtube.com/@mostlyvods")
 }
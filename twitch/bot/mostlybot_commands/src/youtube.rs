@@ -3,30 +3,9 @@
 //! usage: ```!youtube```
 //!
 //! author: mostlymaxi
-use mostlybot_api::{ChatCommand, TwitchApiWrapper};
-use twitcheventsub::MessageData;
+use mostlybot_api::{command, CommandContext, CommandResult, TwitchApiWrapper};
 
-pub struct MostlyYoutube {}
-
-impl ChatCommand for MostlyYoutube {
-    fn new() -> Self {
-        MostlyYoutube {}
-    }
-
-    fn help(&self) -> String {
-        "usage: !youtube".to_string()
-    }
-
-    fn names() -> Vec<String> {
-        vec!["youtube".to_string(), "yt".to_string()]
-    }
-
-    fn handle(&mut self, api: &mut TwitchApiWrapper, ctx: &MessageData) -> anyhow::Result<()> {
-        let _ = api.send_chat_message_with_reply(
-            "check out maxi's youtube!: https://www.youtube.com/@mostlymaxi",
-            Some(&ctx.message_id),
-        );
-
-        Ok(())
-    }
+#[command(names = ["youtube", "yt"], help = "usage: !youtube")]
+pub fn mostly_youtube(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+    api.reply(ctx, "check out maxi's youtube!: https://www.youtube.com/@mostlymaxi")
 }
@@ -0,0 +1,312 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Type, Variant,
+};
+
+/// How a single field is pulled out of the chat message
+enum FieldKind {
+    Positional,
+    Optional(Box<Type>),
+    Rest,
+    OptionalRest,
+    /// ```#[arg(rest, ignore)]```, the rest of the message is read and thrown away
+    Ignored,
+    Flag,
+}
+
+struct ArgField {
+    ident: Ident,
+    name: String,
+    ty: Type,
+    kind: FieldKind,
+}
+
+impl ArgField {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| syn::Error::new_spanned(field, "CommandArgs fields must be named"))?;
+
+        let mut rest = false;
+        let mut flag = false;
+        let mut ignore = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rest") {
+                    rest = true;
+                    Ok(())
+                } else if meta.path.is_ident("flag") {
+                    flag = true;
+                    Ok(())
+                } else if meta.path.is_ident("ignore") {
+                    ignore = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rest`, `flag` or `ignore`"))
+                }
+            })?;
+        }
+
+        if ignore && !rest {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only the rest can be ignored: `#[arg(rest, ignore)]`",
+            ));
+        }
+
+        let kind = match (flag, rest, option_inner(&field.ty)) {
+            (false, true, _) if ignore => FieldKind::Ignored,
+            (true, false, _) => FieldKind::Flag,
+            (false, true, Some(_)) => FieldKind::OptionalRest,
+            (false, true, None) => FieldKind::Rest,
+            (false, false, Some(inner)) => FieldKind::Optional(Box::new(inner.clone())),
+            (false, false, None) => FieldKind::Positional,
+            (true, true, _) => {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "a field can't be both `rest` and `flag`",
+                ))
+            }
+        };
+
+        Ok(Self {
+            name: ident.to_string().trim_start_matches("r#").to_owned(),
+            ident,
+            ty: field.ty.clone(),
+            kind,
+        })
+    }
+
+    fn is_flag(&self) -> bool {
+        matches!(self.kind, FieldKind::Flag)
+    }
+
+    fn is_ignored(&self) -> bool {
+        matches!(self.kind, FieldKind::Ignored)
+    }
+
+    fn parse_expr(&self) -> TokenStream {
+        let name = &self.name;
+        let ty = &self.ty;
+        match &self.kind {
+            FieldKind::Positional => quote! { args.positional::<#ty>(#name)? },
+            FieldKind::Optional(inner) => quote! { args.optional::<#inner>(#name)? },
+            FieldKind::Rest => quote! { args.rest(#name)? },
+            FieldKind::OptionalRest => quote! { args.optional_rest() },
+            FieldKind::Ignored => quote! {{
+                args.optional_rest();
+                <#ty as ::std::default::Default>::default()
+            }},
+            FieldKind::Flag => quote! { args.flag(#name) },
+        }
+    }
+
+    fn usage(&self) -> String {
+        let name = &self.name;
+        match self.kind {
+            FieldKind::Ignored => String::new(),
+            FieldKind::Positional => format!("<{name}>"),
+            FieldKind::Optional(_) => format!("[{name}]"),
+            FieldKind::Rest => format!("<{name}...>"),
+            FieldKind::OptionalRest => format!("[{name}...]"),
+            FieldKind::Flag => format!("[--{name}]"),
+        }
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return None;
+    };
+    match generics.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+/// Parses the fields (flags first, then everything else in order) and builds `constructor`
+fn parse_fields(fields: &Fields, constructor: TokenStream) -> syn::Result<(TokenStream, String)> {
+    let fields = match fields {
+        Fields::Named(named) => named
+            .named
+            .iter()
+            .map(ArgField::parse)
+            .collect::<syn::Result<Vec<_>>>()?,
+        Fields::Unit => Vec::new(),
+        Fields::Unnamed(unnamed) => {
+            return Err(syn::Error::new_spanned(
+                unnamed,
+                "CommandArgs fields must be named",
+            ))
+        }
+    };
+
+    let ordered: Vec<_> = fields
+        .iter()
+        .filter(|f| f.is_flag())
+        .chain(fields.iter().filter(|f| !f.is_flag()))
+        .collect();
+    let idents: Vec<_> = ordered.iter().map(|f| &f.ident).collect();
+    let exprs: Vec<_> = ordered.iter().map(|f| f.parse_expr()).collect();
+
+    // ignored fields are read once here so they don't show up as dead code
+    let ignored: Vec<_> = fields
+        .iter()
+        .filter(|f| f.is_ignored())
+        .map(|f| &f.ident)
+        .collect();
+
+    let body = if fields.is_empty() {
+        quote! { #constructor }
+    } else {
+        quote! {
+            #(let #idents = #exprs;)*
+            let parsed = #constructor { #(#idents),* };
+            #[allow(irrefutable_let_patterns)]
+            if let #constructor { #(#ignored,)* .. } = &parsed {
+                #(let _ = #ignored;)*
+            }
+            parsed
+        }
+    };
+
+    let usage = fields
+        .iter()
+        .filter(|f| !f.is_flag() && !f.is_ignored())
+        .chain(fields.iter().filter(|f| f.is_flag()))
+        .map(ArgField::usage)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Ok((body, usage))
+}
+
+/// The keyword a variant has to start with, if any
+fn subcommand(variant: &Variant) -> syn::Result<Option<String>> {
+    let mut keyword = None;
+    for attr in variant.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("subcommand") {
+                return Err(meta.error("expected `subcommand`"));
+            }
+            keyword = Some(match meta.value() {
+                Ok(value) => value.parse::<LitStr>()?.value().to_lowercase(),
+                Err(_) => variant.ident.to_string().to_lowercase(),
+            });
+            Ok(())
+        })?;
+    }
+    Ok(keyword)
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let (parse, usage_lines) = match &input.data {
+        Data::Struct(data) => {
+            let (body, usage) = parse_fields(&data.fields, quote! { Self })?;
+            (quote! { Ok({ #body }) }, quote! { vec![#usage.to_owned()] })
+        }
+        Data::Enum(data) => derive_enum(data)?,
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "CommandArgs can't be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl ::mostlybot_api::CommandArgs for #ident {
+            #[allow(unused_mut, unused_variables)]
+            fn parse_args(
+                args: &mut ::mostlybot_api::Args,
+            ) -> ::std::result::Result<Self, ::mostlybot_api::ArgError> {
+                #parse
+            }
+
+            fn usage_lines() -> ::std::vec::Vec<::std::string::String> {
+                #usage_lines
+            }
+        }
+    })
+}
+
+fn derive_enum(data: &syn::DataEnum) -> syn::Result<(TokenStream, TokenStream)> {
+    let mut keyword_arms = Vec::new();
+    let mut positional_arms = Vec::new();
+    let mut usage_lines = Vec::new();
+
+    for variant in &data.variants {
+        let variant_ident = &variant.ident;
+        let keyword = subcommand(variant)?;
+        let (body, usage) = parse_fields(&variant.fields, quote! { Self::#variant_ident })?;
+
+        // every variant gets its own copy of the args so a failed attempt doesn't eat words
+        let attempt = |skip: TokenStream| {
+            quote! {
+                let mut attempt = args.clone();
+                #skip
+                let parsed = (|args: &mut ::mostlybot_api::Args| -> ::std::result::Result<
+                    Self,
+                    ::mostlybot_api::ArgError,
+                > {
+                    let parsed = { #body };
+                    args.finish()?;
+                    Ok(parsed)
+                })(&mut attempt);
+                match parsed {
+                    Ok(parsed) => {
+                        *args = attempt;
+                        return Ok(parsed);
+                    }
+                    Err(err) => {
+                        first_err.get_or_insert(err);
+                    }
+                }
+            }
+        };
+
+        match keyword {
+            Some(keyword) => {
+                let attempt = attempt(quote! { attempt.next_word(); });
+                keyword_arms.push(quote! {
+                    if keyword.as_deref() == Some(#keyword) {
+                        #attempt
+                    }
+                });
+                usage_lines.push(format!("{keyword} {usage}").trim_end().to_owned());
+            }
+            None => {
+                positional_arms.push(attempt(quote! {}));
+                usage_lines.push(usage);
+            }
+        }
+    }
+
+    let parse = quote! {
+        let keyword = args.peek().map(|word| word.to_lowercase());
+        let mut first_err: ::std::option::Option<::mostlybot_api::ArgError> = None;
+
+        #(#keyword_arms)*
+
+        if first_err.is_none() {
+            #(#positional_arms)*
+        }
+
+        Err(first_err.unwrap_or_else(|| match args.peek() {
+            Some(word) => ::mostlybot_api::ArgError::Unexpected(word.to_owned()),
+            None => ::mostlybot_api::ArgError::Missing("arguments".to_owned()),
+        }))
+    };
+
+    Ok((parse, quote! { vec![#(#usage_lines.to_owned()),*] }))
+}
@@ -0,0 +1,313 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{meta::ParseNestedMeta, Expr, ExprArray, Ident, Item, ItemFn, ItemStruct, LitStr};
+
+/// Everything inside ```#[command(...)]```
+#[derive(Default)]
+pub struct CommandAttr {
+    names: Vec<LitStr>,
+    topics: Vec<LitStr>,
+    help: Option<Expr>,
+    cooldown_ms: Option<u64>,
+    role: Option<Ident>,
+    persist: bool,
+    no_undo: bool,
+}
+
+impl CommandAttr {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("names") {
+            self.names = parse_strings(meta, "names")?;
+            Ok(())
+        } else if meta.path.is_ident("topics") {
+            self.topics = parse_strings(meta, "topics")?;
+            Ok(())
+        } else if meta.path.is_ident("help") {
+            self.help = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("cooldown") {
+            let cooldown: LitStr = meta.value()?.parse()?;
+            let ms = parse_duration_ms(&cooldown.value()).ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &cooldown,
+                    "expected a duration like \"250ms\" or \"1m30s\"",
+                )
+            })?;
+            self.cooldown_ms = Some(ms);
+            Ok(())
+        } else if meta.path.is_ident("role") {
+            let role: LitStr = meta.value()?.parse()?;
+            let variant = match role.value().as_str() {
+                "everyone" => "Everyone",
+                "subscriber" => "Subscriber",
+                "vip" => "Vip",
+                "moderator" => "Moderator",
+                "broadcaster" => "Broadcaster",
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &role,
+                        "expected one of: everyone, subscriber, vip, moderator, broadcaster",
+                    ))
+                }
+            };
+            self.role = Some(Ident::new(variant, role.span()));
+            Ok(())
+        } else if meta.path.is_ident("persist") {
+            self.persist = true;
+            Ok(())
+        } else if meta.path.is_ident("no_undo") {
+            self.no_undo = true;
+            Ok(())
+        } else {
+            Err(meta.error(
+                "expected `names`, `help`, `cooldown`, `role`, `topics`, `persist` or `no_undo`",
+            ))
+        }
+    }
+
+    /// The ```ChatCommand``` methods that come straight from the attribute
+    fn trait_methods(&self) -> syn::Result<TokenStream> {
+        if self.names.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "#[command] needs at least one name: `names = [\"ping\"]`",
+            ));
+        }
+        let Some(help) = &self.help else {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "#[command] needs a help message: `help = \"usage: !ping\"`",
+            ));
+        };
+        let names = &self.names;
+        let topics = &self.topics;
+
+        let rate_limit = self.cooldown_ms.map(|ms| {
+            quote! {
+                fn rate_limit(&self) -> ::mostlybot_api::RateLimit {
+                    ::mostlybot_api::RateLimit::new(1, ::std::time::Duration::from_millis(#ms))
+                }
+            }
+        });
+
+        let topics = (!topics.is_empty()).then(|| {
+            quote! {
+                fn topics() -> ::std::vec::Vec<::std::string::String> {
+                    ::std::vec![#(::std::string::String::from(#topics)),*]
+                }
+            }
+        });
+
+        let role = self.role.as_ref().map(|role| {
+            quote! {
+                fn role(&self) -> ::mostlybot_api::Role {
+                    ::mostlybot_api::Role::#role
+                }
+            }
+        });
+
+        let undoable = self.no_undo.then(|| {
+            quote! {
+                fn undoable(&self) -> bool {
+                    false
+                }
+            }
+        });
+
+        Ok(quote! {
+            fn names() -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![#(::std::string::String::from(#names)),*]
+            }
+
+            fn help(&self) -> ::std::string::String {
+                ::std::string::ToString::to_string(&#help)
+            }
+
+            #topics
+            #rate_limit
+            #role
+            #undoable
+        })
+    }
+}
+
+pub fn expand(attr: CommandAttr, item: Item) -> syn::Result<TokenStream> {
+    match item {
+        Item::Fn(item) => expand_fn(attr, item),
+        Item::Struct(item) => expand_struct(attr, item),
+        other => Err(syn::Error::new_spanned(
+            other,
+            "#[command] goes on a handler function or a command struct",
+        )),
+    }
+}
+
+/// ```fn mostly_ping(api, ctx) -> CommandResult``` becomes ```struct MostlyPing```
+fn expand_fn(attr: CommandAttr, item: ItemFn) -> syn::Result<TokenStream> {
+    if item.sig.inputs.len() != 2 {
+        return Err(syn::Error::new_spanned(
+            &item.sig,
+            "expected `fn(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult`",
+        ));
+    }
+
+    if attr.persist {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`persist` needs state to save, put #[command] on a struct instead",
+        ));
+    }
+
+    let vis = &item.vis;
+    let func = &item.sig.ident;
+    let ident = format_ident!("{}", camel_case(&func.to_string()), span = func.span());
+    let methods = attr.trait_methods()?;
+    let doc = format!("Command struct generated by ```#[command]``` from [`{func}`]");
+
+    Ok(quote! {
+        #item
+
+        #[doc = #doc]
+        #vis struct #ident;
+
+        impl ::mostlybot_api::ChatCommand for #ident {
+            fn new() -> Self {
+                Self
+            }
+
+            #methods
+
+            fn handle(
+                &mut self,
+                api: &mut ::mostlybot_api::TwitchApiWrapper,
+                ctx: &::mostlybot_api::CommandContext,
+            ) -> ::mostlybot_api::CommandResult {
+                #func(api, ctx)
+            }
+        }
+
+        ::mostlybot_api::register_command!(#ident);
+    })
+}
+
+/// Stateful commands are built with ```Default``` and handled by their own ```handle``` method.
+/// With ```persist``` the whole struct is saved as json on shutdown and loaded back on startup
+fn expand_struct(attr: CommandAttr, item: ItemStruct) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "#[command] structs can't be generic",
+        ));
+    }
+
+    let ident = &item.ident;
+    let methods = attr.trait_methods()?;
+
+    let persist = attr.persist.then(|| {
+        let name = &attr.names[0];
+        quote! {
+            fn restore(
+                &mut self,
+                services: &::mostlybot_api::Services,
+            ) -> ::mostlybot_api::anyhow::Result<()> {
+                if let Some(state) = ::mostlybot_api::load_state(&services.data_dir, #name)? {
+                    *self = state;
+                }
+                Ok(())
+            }
+
+            fn shutdown(
+                &mut self,
+                services: &::mostlybot_api::Services,
+            ) -> ::mostlybot_api::anyhow::Result<()> {
+                ::mostlybot_api::save_state(&services.data_dir, #name, self)
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        impl ::mostlybot_api::ChatCommand for #ident {
+            fn new() -> Self {
+                ::std::default::Default::default()
+            }
+
+            #methods
+            #persist
+
+            fn handle(
+                &mut self,
+                api: &mut ::mostlybot_api::TwitchApiWrapper,
+                ctx: &::mostlybot_api::CommandContext,
+            ) -> ::mostlybot_api::CommandResult {
+                #ident::handle(self, api, ctx)
+            }
+        }
+
+        ::mostlybot_api::register_command!(#ident);
+    })
+}
+
+/// ```["a", "b"]```
+fn parse_strings(meta: ParseNestedMeta, what: &str) -> syn::Result<Vec<LitStr>> {
+    let array: ExprArray = meta.value()?.parse()?;
+    array
+        .elems
+        .into_iter()
+        .map(|elem| match elem {
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Ok(s),
+            other => Err(syn::Error::new_spanned(
+                other,
+                format!("{what} must be string literals"),
+            )),
+        })
+        .collect()
+}
+
+fn camel_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Same grammar as ```mostlybot_api::parse_duration```, checked at compile time
+fn parse_duration_ms(s: &str) -> Option<u64> {
+    if let Ok(secs) = s.parse::<u64>() {
+        return secs.checked_mul(1000);
+    }
+
+    let mut total: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let value: u64 = rest[..digits].parse().ok()?;
+        let tail = &rest[digits..];
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+
+        let ms = match &tail[..unit_len] {
+            "ms" => Some(value),
+            "s" => value.checked_mul(1000),
+            "m" => value.checked_mul(60 * 1000),
+            "h" => value.checked_mul(60 * 60 * 1000),
+            _ => return None,
+        };
+        total = total.checked_add(ms?)?;
+        rest = &tail[unit_len..];
+    }
+
+    (!s.is_empty()).then_some(total)
+}
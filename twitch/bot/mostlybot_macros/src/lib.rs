@@ -0,0 +1,47 @@
+//! macros for mostlybot commands, re-exported by ```mostlybot_api```
+
+mod args;
+mod command;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, Item};
+
+/// Derives ```CommandArgs``` for a struct or enum, see the trait docs in ```mostlybot_api```
+#[proc_macro_derive(CommandArgs, attributes(arg))]
+pub fn derive_command_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    args::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Turns a handler function or a command struct into a registered ```ChatCommand```.
+///
+/// ```ignore
+/// #[command(names = ["ping"], help = "usage: !ping", cooldown = "250ms", role = "everyone")]
+/// pub fn mostly_ping(api: &mut TwitchApiWrapper, ctx: &CommandContext) -> CommandResult {
+///     api.reply(ctx, "pong")
+/// }
+/// ```
+///
+/// A function gets a unit struct named after it (```mostly_ping``` -> ```MostlyPing```).
+/// A struct is built with ```Default``` and must have its own
+/// ```fn handle(&mut self, api, ctx) -> CommandResult``` method.
+///
+/// ```cooldown``` and ```role``` are optional and default to ```250ms``` and ```everyone```.
+/// ```topics = ["points-term"]``` lists the franz topics the command may publish to.
+/// A struct can also be marked ```persist``` to be saved to the data dir on shutdown and
+/// loaded back on startup, it then needs ```Serialize``` and ```Deserialize``` too.
+/// ```no_undo``` keeps ```!undo``` from taking back what the command says.
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut command_attr = command::CommandAttr::default();
+    let parser = syn::meta::parser(|meta| command_attr.parse(meta));
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as Item);
+
+    command::expand(command_attr, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::{select, signal, time};
 use tokio_util::sync::CancellationToken;
 use tracing::instrument;
@@ -72,8 +75,10 @@ impl DataCollector {
 
                 match event {
                     Event::ChatMessage(m) => {
-                        let m = serde_json::to_string(&m).unwrap();
-                        self.send("chat", &m).await;
+                        // mostlybot skips messages that are too old to answer by this
+                        let mut m = serde_json::to_value(&m).unwrap();
+                        m["received_at"] = serde_json::json!(unix_millis());
+                        self.send("chat", &m.to_string()).await;
                     }
                     Event::Follow(m) => {
                         let m = serde_json::to_string(&m).unwrap();
@@ -137,6 +142,13 @@ impl DataCollector {
     }
 }
 
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 async fn cancel_on_signal(twitter: CancellationToken) {
     match signal::ctrl_c().await {
         Ok(()) => tracing::info!("caught signal. shutting down..."),